@@ -1,6 +1,6 @@
 #include "../my_world.h"
 
-void my_world_update() {
+void my_world_update(float delta_seconds, float elapsed_seconds) {
     my_world_string_t my_string;
     my_world_string_set(&my_string, "404 Wasm App Not Found"); // TODO: think about how to print requested resource path too 
     
@@ -29,8 +29,8 @@ void createParticles() {
     leftMouseButton.tag = LEVO_PORTAL_MY_IMPORTS_MOUSE_BUTTON_LEFT;
 
     if (tick % 10 == 0 && particleCount < 100) {
-        particles[particleCount].x = ((float)rand() / RAND_MAX) * canvas_width;
-        particles[particleCount].y = 0.0;
+        particles[particleCount].x = ((float)rand() / RAND_MAX - 0.5) * canvas_width;
+        particles[particleCount].y = canvasSize.height / 2.0;
         particles[particleCount].speed = 500.0 + ((float)rand() / RAND_MAX) * 13.0;
         particles[particleCount].radius = 5.0 + ((float)rand() / RAND_MAX) * 5.0;
 
@@ -60,8 +60,8 @@ void killParticles() {
     levo_portal_my_imports_canvas_size(&canvasSize);
     float canvas_height = canvasSize.height;
     for (int i = 0; i < particleCount; i++) {
-        if (particles[i].y < -canvas_height) {
-            particles[i].y = 0.0;
+        if (particles[i].y < -canvas_height / 2.0) {
+            particles[i].y = canvas_height / 2.0;
         }
     }
 }
@@ -73,7 +73,7 @@ void drawParticles() {
     levo_portal_my_imports_size_t canvasSize;
     levo_portal_my_imports_canvas_size(&canvasSize);
     float canvas_height = canvasSize.height;
-    levo_portal_my_imports_fill_rect(0.0, 0.0, canvasSize.width, canvasSize.height);
+    levo_portal_my_imports_fill_rect(-canvasSize.width / 2.0, -canvasSize.height / 2.0, canvasSize.width, canvasSize.height);
 
     for (int i = 0; i < particleCount; i++) {
         levo_portal_my_imports_begin_path();
@@ -107,7 +107,7 @@ void drawHeart(float x_offset, float y_offset) {
     my_world_string_free(&fill_style);
 }
 
-void my_world_update() {
+void my_world_update(float delta_seconds, float elapsed_seconds) {
     createParticles();
     updateParticles();
     killParticles();
@@ -158,4 +158,6 @@ void my_world_setup() {
     my_world_string_set(&my_string, "setup from guest (C) has been called");
 
     levo_portal_my_imports_print(&my_string);
+    // The particles move every frame
+    levo_portal_my_imports_set_continuous_redraw(true);
 }
@@ -71,7 +71,7 @@ func drawParticles() {
 	var canvas_width float32 = world.LevoPortalMyImportsCanvasSize().Width
 	var canvas_height float32 = world.LevoPortalMyImportsCanvasSize().Height
         world.LevoPortalMyImportsFillStyle("royal_purple")
-        world.LevoPortalMyImportsFillRect(0, 0, canvas_width, canvas_height)
+        world.LevoPortalMyImportsFillRect(-canvas_width/2, -canvas_height/2, canvas_width, canvas_height)
         for i := 0; i < len(particles); i++ {
                 particle := &particles[i]
                 world.LevoPortalMyImportsBeginPath()
@@ -108,9 +108,11 @@ type HostImpl struct {
 
 func (e HostImpl) Setup() {
         world.LevoPortalMyImportsPrint("setup from guest (Go) has been called")
+        // The particles move every frame
+        world.LevoPortalMyImportsSetContinuousRedraw(true)
 }
 
-func (e HostImpl) Update() {
+func (e HostImpl) Update(deltaSeconds float32, elapsedSeconds float32) {
     createParticles()
     updateParticles()
     killParticles()
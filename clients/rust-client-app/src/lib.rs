@@ -42,7 +42,7 @@ lazy_static! {
 }
 
 fn create_particles() {
-    let canvas_width = canvas_size().width;
+    let canvas_size = canvas_size();
     let mut tick = TICK.lock().unwrap();
     let mut particles = PARTICLES.lock().unwrap();
     *tick += 1;
@@ -55,9 +55,10 @@ fn create_particles() {
                 {
                     cursor_position.x
                 } else {
-                    rand::thread_rng().gen_range(0.0..canvas_width)
+                    let half_width = canvas_size.width / 2.;
+                    rand::thread_rng().gen_range(-half_width..half_width)
                 },
-                y: 0.,
+                y: canvas_size.height / 2.,
                 speed: 500. + rand::thread_rng().gen_range(0.0..1.0) * 13.,
                 radius: 5. + rand::thread_rng().gen_range(0.0..1.0) * 5.,
                 color: "white".to_string(),
@@ -66,10 +67,10 @@ fn create_particles() {
     }
 }
 
-fn update_particles() {
+fn update_particles(delta_seconds: f32) {
     let mut particles = PARTICLES.lock().unwrap();
     for particle in particles.iter_mut() {
-        particle.y -= particle.speed * delta_seconds();
+        particle.y -= particle.speed * delta_seconds;
     }
 }
 
@@ -77,8 +78,8 @@ fn kill_particles() {
     let canvas_height = canvas_size().height;
     let mut particles = PARTICLES.lock().unwrap();
     for particle in particles.iter_mut() {
-        if particle.y < -canvas_height {
-            particle.y = 0.;
+        if particle.y < -canvas_height / 2. {
+            particle.y = canvas_height / 2.;
         }
     }
 }
@@ -86,7 +87,12 @@ fn kill_particles() {
 fn draw_particles() {
     let canvas_size = canvas_size();
     fill_style("royal_purple");
-    fill_rect(0., 0., canvas_size.width, canvas_size.height);
+    fill_rect(
+        -canvas_size.width / 2.,
+        -canvas_size.height / 2.,
+        canvas_size.width,
+        canvas_size.height,
+    );
     let mut particles = PARTICLES.lock().unwrap();
     for particle in particles.iter_mut() {
         begin_path();
@@ -128,9 +134,9 @@ fn draw_heart(x_offset: f32, y_offset: f32) {
 }
 
 impl Guest for MyWorld {
-    fn update() {
+    fn update(delta_seconds: f32, _elapsed_seconds: f32) {
         create_particles();
-        update_particles();
+        update_particles(delta_seconds);
         kill_particles();
         draw_particles();
 
@@ -139,19 +145,19 @@ impl Guest for MyWorld {
             let mut heart_offset = HEART_OFFSET.lock().unwrap();
             let heart_speed = 222.;
             if key_pressed(KeyCode::Left) {
-                heart_offset.0 = heart_offset.0 - heart_speed * delta_seconds();
+                heart_offset.0 = heart_offset.0 - heart_speed * delta_seconds;
             }
 
             if key_pressed(KeyCode::Right) {
-                heart_offset.0 = heart_offset.0 + heart_speed * delta_seconds();
+                heart_offset.0 = heart_offset.0 + heart_speed * delta_seconds;
             }
 
             if key_pressed(KeyCode::Up) {
-                heart_offset.1 = heart_offset.1 + heart_speed * delta_seconds();
+                heart_offset.1 = heart_offset.1 + heart_speed * delta_seconds;
             }
 
             if key_pressed(KeyCode::Down) {
-                heart_offset.1 = heart_offset.1 - heart_speed * delta_seconds();
+                heart_offset.1 = heart_offset.1 - heart_speed * delta_seconds;
             }
 
             draw_heart(heart_offset.0, heart_offset.1);
@@ -164,5 +170,7 @@ impl Guest for MyWorld {
 
     fn setup() {
         print("setup from guest (Rust) has been called");
+        // The particles move every frame
+        set_continuous_redraw(true);
     }
 }
@@ -0,0 +1,48 @@
+// generated by wit-bindgen, from the host.wit (`package levo:portal; interface my-imports {}`)
+use levo::portal::my_imports::*;
+
+// Use a procedural macro to generate bindings for the world we specified in
+// `host.wit`
+wit_bindgen::generate!({
+    path: "../../spec",
+    // the name of the world in the `*.wit` input file
+    world: "my-world",
+
+    // For all exported worlds, interfaces, and resources, this specifies what
+    // type they're corresponding to in this module. In this case the `MyHost`
+    // struct defined below is going to define the exports of the `world`,
+    // namely the `run` function.
+    exports: {
+        world: MyWorld,
+    },
+});
+
+struct MyWorld;
+
+struct MyWorld;
+
+impl Guest for MyWorld {
+    fn setup() {
+        print("setup from guest (Rust emoji) has been called");
+    }
+
+    fn update(_delta_seconds: f32, _elapsed_seconds: f32) {
+        set_text_align(TextAlign::Center);
+        // Plain text on either side of the emoji, which should stay in the label's own font
+        label("Hello 👋 world 🌍", 0., 100., 32., "white");
+        // A joiner sequence and a variation selector, neither of which should be split off
+        label("👩‍💻 ❤️ ✔", 0., 40., 32., "gold");
+        // Drawn in the label's color, since the fallback font's glyphs are monochrome
+        label("🍎 🍊 🍋", 0., -20., 32., "tomato");
+        set_text_align(TextAlign::Left);
+        label_box(
+            "Wrapped 🎉🎉🎉 across lines 🚀",
+            -150.,
+            -80.,
+            24.,
+            "white",
+            300.,
+            0.,
+        );
+    }
+}
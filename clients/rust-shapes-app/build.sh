@@ -0,0 +1,9 @@
+#!/bin/bash
+
+set -e
+
+cargo build --target wasm32-wasi --release
+wasm-tools component new ../../target/wasm32-wasi/release/rust_shapes_app.wasm \
+  -o my-component.wasm --adapt ../wasi_snapshot_preview1.reactor.wasm
+wasm-tools component wit my-component.wasm
+cargo run --package brotli-encoder --release -- my-component.wasm "../../levo-server/public/shapes.wasm"
@@ -0,0 +1,135 @@
+// generated by wit-bindgen, from the host.wit (`package levo:portal; interface my-imports {}`)
+use levo::portal::my_imports::*;
+
+// Use a procedural macro to generate bindings for the world we specified in
+// `host.wit`
+wit_bindgen::generate!({
+    path: "../../spec",
+    // the name of the world in the `*.wit` input file
+    world: "my-world",
+
+    // For all exported worlds, interfaces, and resources, this specifies what
+    // type they're corresponding to in this module. In this case the `MyHost`
+    // struct defined below is going to define the exports of the `world`,
+    // namely the `run` function.
+    exports: {
+        world: MyWorld,
+    },
+});
+
+struct MyWorld;
+
+fn draw_triangle() {
+    begin_path();
+    move_to(-300., -50.);
+    line_to(-200., 100.);
+    line_to(-100., -50.);
+    close_path();
+    fill_style("blue");
+    fill();
+}
+
+fn draw_quadratic_curve() {
+    begin_path();
+    move_to(0., -50.);
+    quadratic_bezier_to(100., 150., 200., -50.);
+    close_path();
+    fill_style("red");
+    fill();
+    stroke_style("white");
+    set_line_width(4.);
+    stroke();
+}
+
+fn draw_arc() {
+    // Drawn before any rect: a half circle of radius 50 centered on (-150, 150), whose flat side
+    // runs from (-200, 150) to (-100, 150).
+    begin_path();
+    arc(-150., 150., 50., std::f32::consts::PI, 0.);
+    close_path();
+    fill_style("yellow");
+    fill();
+}
+
+fn draw_offset_rect() {
+    // Bottom-left corner at (100, 100): the square should sit up and to the right of the
+    // canvas center, not centered on it.
+    fill_style("#ff8800");
+    fill_rect(100., 100., 10., 10.);
+}
+
+fn draw_aligned_labels() {
+    // Every label is anchored on the same point, marked by the small square: left aligned ones
+    // should start at it, right aligned ones end at it, and centered ones straddle it.
+    let (x, y) = (-200., 200.);
+    fill_style("white");
+    fill_rect(x - 2., y - 2., 4., 4.);
+    set_text_align(TextAlign::Left);
+    set_text_baseline(TextBaseline::Bottom);
+    label("left bottom", x, y, 16., "white");
+    set_text_align(TextAlign::Right);
+    set_text_baseline(TextBaseline::Top);
+    label("right top", x, y, 16., "white");
+    set_text_align(TextAlign::Center);
+    set_text_baseline(TextBaseline::Alphabetic);
+    label("center alphabetic", x, y + 40., 16., "white");
+    fill_rect(x - 2., y + 38., 4., 4.);
+    set_text_baseline(TextBaseline::Middle);
+}
+
+fn draw_rounded_square() {
+    // Each side runs straight into a quarter circle, with no kinks where they meet.
+    let (x, y, size, radius) = (200., 150., 100., 20.);
+    begin_path();
+    move_to(x + radius, y);
+    arc_to(x + size, y, x + size, y + size, radius);
+    arc_to(x + size, y + size, x, y + size, radius);
+    arc_to(x, y + size, x, y, radius);
+    arc_to(x, y, x + size, y, radius);
+    close_path();
+    stroke_style("green");
+    set_line_width(4.);
+    stroke();
+}
+
+fn draw_ellipses() {
+    // A wide ellipse tilted up to the right, and the top half of one cut off flat along its
+    // middle.
+    fill_style("purple");
+    fill_ellipse(-250., -200., 60., 25., 0.5, 0., std::f32::consts::TAU);
+    fill_ellipse(-100., -200., 50., 30., 0., 0., std::f32::consts::PI);
+}
+
+fn draw_pentagram() {
+    // Filled with the even-odd rule, the pentagon in the middle of the star is a hole that the
+    // background shows through.
+    let (x, y, radius) = (250., -200., 80.);
+    let points: Vec<f32> = (0..5)
+        .flat_map(|i| {
+            // Every second point of a pentagon, starting from the top
+            let angle = std::f32::consts::FRAC_PI_2 + i as f32 * 4. * std::f32::consts::PI / 5.;
+            [x + radius * angle.cos(), y + radius * angle.sin()]
+        })
+        .collect();
+    set_fill_rule(FillRule::EvenOdd);
+    fill_style("cyan");
+    fill_polygon(&points).unwrap();
+    set_fill_rule(FillRule::NonZero);
+}
+
+impl Guest for MyWorld {
+    fn setup() {
+        print("setup from guest (Rust shapes) has been called");
+    }
+
+    fn update(_delta_seconds: f32, _elapsed_seconds: f32) {
+        draw_arc();
+        draw_triangle();
+        draw_quadratic_curve();
+        draw_offset_rect();
+        draw_aligned_labels();
+        draw_rounded_square();
+        draw_ellipses();
+        draw_pentagram();
+    }
+}
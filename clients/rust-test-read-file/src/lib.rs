@@ -44,5 +44,5 @@ impl Guest for MyWorld {
         };
     }
 
-    fn update() {}
+    fn update(_delta_seconds: f32, _elapsed_seconds: f32) {}
 }
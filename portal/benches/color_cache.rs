@@ -0,0 +1,107 @@
+//! Parsing the colors of a frame that sets the same few over and over, through `ColorCache` and
+//! from scratch each time.
+//!
+//! Besides criterion's timings this prints how many allocations each way makes per color, counted
+//! by the global allocator below.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use portal::{string_to_bevy_color, ColorCache};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// What a busy frame might set, a thousand times over.
+const COLORS: [&str; 4] = [
+    "rgba(255, 128, 0, 0.5)",
+    "hsl(210deg, 50%, 40%)",
+    "#8c49a3",
+    "white",
+];
+const FRAME: usize = 1_000;
+
+/// The strings as the guest hands them over, which both ways get to own.
+fn frame() -> Vec<String> {
+    COLORS
+        .iter()
+        .cycle()
+        .take(FRAME)
+        .map(|color| color.to_string())
+        .collect()
+}
+
+/// Allocations per color while parsing a frame with `parse`, not counting the strings themselves.
+fn allocations_per_color(mut parse: impl FnMut(String)) -> f64 {
+    let frame = frame();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for color in frame {
+        parse(color);
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / FRAME as f64
+}
+
+fn parse(c: &mut Criterion) {
+    let mut cache = ColorCache::default();
+    // The first frame fills the cache, the ones after are what the guest pays every frame
+    allocations_per_color(|color| {
+        cache.parse(color);
+    });
+    println!(
+        "allocations per color: cached {}, uncached {}",
+        allocations_per_color(|color| {
+            black_box(cache.parse(color));
+        }),
+        allocations_per_color(|color| {
+            black_box(string_to_bevy_color(color));
+        }),
+    );
+
+    let mut group = c.benchmark_group("parse a frame of colors");
+    group.bench_function("cached", |b| {
+        b.iter_batched(
+            frame,
+            |frame| {
+                for color in frame {
+                    black_box(cache.parse(color));
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("uncached", |b| {
+        b.iter_batched(
+            frame,
+            |frame| {
+                for color in frame {
+                    black_box(string_to_bevy_color(color));
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Finding the region under the pointer among 10,000 of them, through the grid and by checking
+//! each one as before it.
+
+use bevy::math::{Rect, Vec2};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use portal::hit_grid::HitGrid;
+
+const REGIONS: u32 = 10_000;
+
+/// 100 by 100 overlapping 20px squares, 15px apart, like a dense grid of buttons.
+fn regions() -> Vec<(Rect, u32)> {
+    (0..REGIONS)
+        .map(|i| {
+            let min = Vec2::new((i % 100) as f32, (i / 100) as f32) * 15.;
+            (Rect::from_corners(min, min + 20.), i)
+        })
+        .collect()
+}
+
+/// Points spread over the regions and past them.
+fn points() -> Vec<Vec2> {
+    (0..1_000)
+        .map(|i| Vec2::new((i * 37 % 1_600) as f32, (i * 53 % 1_600) as f32))
+        .collect()
+}
+
+fn find(c: &mut Criterion) {
+    let regions = regions();
+    let points = points();
+    let mut grid = HitGrid::default();
+    grid.set(regions.clone());
+
+    let mut group = c.benchmark_group("find among 10k regions");
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            for &point in &points {
+                black_box(grid.find(black_box(point)));
+            }
+        })
+    });
+    group.bench_function("every region", |b| {
+        b.iter(|| {
+            for &point in &points {
+                black_box(
+                    regions
+                        .iter()
+                        .rev()
+                        .find(|(rect, _)| rect.contains(black_box(point)))
+                        .map(|(_, id)| id),
+                );
+            }
+        })
+    });
+    group.finish();
+}
+
+fn set(c: &mut Criterion) {
+    let regions = regions();
+    let mut group = c.benchmark_group("set 10k regions");
+    group.bench_function("changed", |b| {
+        b.iter_batched(
+            || (HitGrid::default(), regions.clone()),
+            |(mut grid, regions)| {
+                grid.set(regions);
+                grid
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    let mut grid = HitGrid::default();
+    grid.set(regions.clone());
+    group.bench_function("unchanged", |b| {
+        b.iter_batched(
+            || regions.clone(),
+            |regions| grid.set(regions),
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, find, set);
+criterion_main!(benches);
@@ -0,0 +1,240 @@
+use bevy::audio::{
+    AudioBundle, AudioSink, AudioSinkPlayback, AudioSource, GlobalVolume, PlaybackSettings, Volume,
+};
+use bevy::prelude::{Assets, Commands, Component, DespawnRecursiveExt, Entity, Query, Resource};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A sound or music track a guest is playing, despawned once it finishes or the guest stops it.
+#[derive(Component)]
+pub struct GuestSound {
+    pub handle: u32,
+    /// Music is controlled with the `*_music` functions and sounds with the `*_sound` ones
+    pub music: bool,
+}
+
+pub type GuestSoundQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GuestSound,
+        &'static mut PlaybackSettings,
+        Option<&'static AudioSink>,
+    ),
+>;
+
+enum AudioCommand {
+    Play {
+        handle: u32,
+        bytes: Vec<u8>,
+        music: bool,
+        looping: bool,
+    },
+    SetVolume {
+        handle: u32,
+        music: bool,
+        volume: f32,
+    },
+    Stop {
+        handle: u32,
+        music: bool,
+    },
+}
+
+/// The sounds and music a guest started, changed or stopped since the portal last played them,
+/// which happens on the main thread once `update` returns.
+#[derive(Default)]
+pub struct GuestAudio {
+    next_handle: u32,
+    commands: Vec<AudioCommand>,
+}
+
+impl GuestAudio {
+    /// Queues `bytes` to be played, returning the handle to control it with. Clips that aren't
+    /// Ogg Vorbis or WAV are ignored, though they still get a handle.
+    pub fn play(&mut self, bytes: Vec<u8>, music: bool, looping: bool) -> u32 {
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        if is_supported(&bytes) {
+            self.commands.push(AudioCommand::Play {
+                handle,
+                bytes,
+                music,
+                looping,
+            });
+        } else {
+            eprintln!("ignoring sound {handle}, which isn't Ogg Vorbis or WAV");
+        }
+        handle
+    }
+
+    /// Sets the volume of a sound relative to the portal's, from 0 (muted) to 1.
+    pub fn set_volume(&mut self, handle: u32, music: bool, volume: f32) {
+        let volume = clamp_volume(volume);
+        self.commands.push(AudioCommand::SetVolume {
+            handle,
+            music,
+            volume,
+        });
+    }
+
+    pub fn stop(&mut self, handle: u32, music: bool) {
+        self.commands.push(AudioCommand::Stop { handle, music });
+    }
+
+    /// Starts, changes and stops the queued sounds. They play at the portal's `GlobalVolume`.
+    pub fn apply(
+        &mut self,
+        commands: &mut Commands,
+        sources: &mut Assets<AudioSource>,
+        global_volume: &GlobalVolume,
+        sounds_q: &mut GuestSoundQuery,
+    ) {
+        // Sounds started in this batch aren't in the query yet, so changing them means replacing
+        // the settings they were spawned with
+        let mut started: HashMap<(u32, bool), (Entity, PlaybackSettings)> = HashMap::new();
+        for command in self.commands.drain(..) {
+            match command {
+                AudioCommand::Play {
+                    handle,
+                    bytes,
+                    music,
+                    looping,
+                } => {
+                    let source = sources.add(AudioSource {
+                        bytes: bytes.into(),
+                    });
+                    let settings = if looping {
+                        PlaybackSettings::LOOP
+                    } else {
+                        PlaybackSettings::DESPAWN
+                    };
+                    let entity = commands
+                        .spawn((
+                            AudioBundle { source, settings },
+                            GuestSound { handle, music },
+                        ))
+                        .id();
+                    started.insert((handle, music), (entity, settings));
+                }
+                AudioCommand::SetVolume {
+                    handle,
+                    music,
+                    volume,
+                } => {
+                    if let Some((entity, settings)) = started.get_mut(&(handle, music)) {
+                        *settings = settings.with_volume(Volume::new_relative(volume));
+                        commands.entity(*entity).insert(*settings);
+                    }
+                    for (_, sound, mut settings, sink) in sounds_q.iter_mut() {
+                        if sound.handle != handle || sound.music != music {
+                            continue;
+                        }
+                        // The settings only count until the sound starts, after which its sink
+                        // has the volume, scaled by the global volume
+                        settings.volume = Volume::new_relative(volume);
+                        if let Some(sink) = sink {
+                            sink.set_volume(volume * global_volume.volume.get());
+                        }
+                    }
+                }
+                AudioCommand::Stop { handle, music } => {
+                    let playing = sounds_q
+                        .iter()
+                        .filter(|(_, sound, ..)| sound.handle == handle && sound.music == music)
+                        .map(|(entity, ..)| entity);
+                    let started = started.remove(&(handle, music)).map(|(entity, _)| entity);
+                    for entity in started.into_iter().chain(playing) {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The volume every guest sound is scaled by, and whether the portal is muted, kept between
+/// sessions in the portal's settings file.
+#[derive(Resource)]
+pub struct MasterVolume {
+    /// From 0 to 1, kept while muted so that unmuting goes back to it
+    pub volume: f32,
+    pub muted: bool,
+    path: PathBuf,
+}
+
+impl MasterVolume {
+    /// The volume saved at `path` in an earlier session, or `volume` if it's given, which is
+    /// saved in its place and unmutes.
+    pub fn load(path: &Path, volume: Option<f32>) -> Self {
+        let saved: Option<serde_json::Value> = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        let saved_volume = saved.as_ref().and_then(|s| s["volume"].as_f64());
+        let saved_muted = saved.as_ref().and_then(|s| s["muted"].as_bool());
+        let master_volume = Self {
+            volume: clamp_volume(volume.unwrap_or(saved_volume.unwrap_or(1.) as f32)),
+            muted: volume.is_none() && saved_muted.unwrap_or(false),
+            path: path.to_path_buf(),
+        };
+        if volume.is_some() {
+            master_volume.save();
+        }
+        master_volume
+    }
+
+    /// What sounds actually play at.
+    pub fn global_volume(&self) -> GlobalVolume {
+        GlobalVolume::new(if self.muted { 0. } else { self.volume })
+    }
+
+    /// Mutes or unmutes, and saves which it is for the next session.
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.save();
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.write() {
+            eprintln!("failed to save {}: {e}", self.path.display());
+        }
+    }
+
+    /// Writes to a temporary file first, like `GuestStorage`.
+    fn write(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let settings = serde_json::json!({ "volume": self.volume, "muted": self.muted });
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&settings)?)?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+/// Brings the sounds already playing to a new global volume, which Bevy only applies to sounds as
+/// they start.
+pub fn apply_global_volume(global_volume: &GlobalVolume, sounds_q: &GuestSoundQuery) {
+    for (_, _, settings, sink) in sounds_q.iter() {
+        if let (Volume::Relative(volume), Some(sink)) = (&settings.volume, sink) {
+            sink.set_volume(volume.get() * global_volume.volume.get());
+        }
+    }
+}
+
+fn clamp_volume(volume: f32) -> f32 {
+    if volume.is_nan() {
+        0.
+    } else {
+        volume.clamp(0., 1.)
+    }
+}
+
+fn is_supported(bytes: &[u8]) -> bool {
+    match bytes {
+        [b'O', b'g', b'g', b'S', ..] => true,
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,29 @@
+use crate::levo::portal::my_imports::TextDirection;
+use unicode_bidi::{BidiInfo, Level};
+
+/// `text` with each line's characters in the order they're shown in, for the text renderer,
+/// which lays every line out left to right.
+///
+/// Lines are reordered with the Unicode Bidirectional Algorithm, starting in `direction` or, for
+/// `auto`, in the direction of the line's first strong character. Text without right-to-left
+/// characters in a left-to-right line is returned as it is.
+pub fn visual_order(text: &str, direction: TextDirection) -> String {
+    let level = match direction {
+        TextDirection::Auto => None,
+        TextDirection::Ltr => Some(Level::ltr()),
+        TextDirection::Rtl => Some(Level::rtl()),
+    };
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            let info = BidiInfo::new(line, level);
+            match info.paragraphs.first() {
+                Some(paragraph) if info.has_rtl() || direction == TextDirection::Rtl => info
+                    .reorder_line(paragraph, paragraph.range.clone())
+                    .into_owned(),
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
@@ -0,0 +1,178 @@
+use bevy::asset::{load_internal_asset, Asset, Assets, Handle};
+use bevy::math::Vec2;
+use bevy::prelude::{
+    Added, App, Color, ColorMaterial, Commands, Component, Entity, Image, Mesh, Plugin, PostUpdate,
+    Query, ResMut, Shader, Sprite, With,
+};
+use bevy::reflect::TypePath;
+use bevy::render::mesh::{Indices, MeshVertexBufferLayout, PrimitiveTopology};
+use bevy::render::render_resource::{
+    AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, RenderPipelineDescriptor,
+    ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy::sprite::{Material2d, Material2dKey, Material2dPlugin, Mesh2dHandle};
+
+const BLEND_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x3b7e_91c4_0a52_4f6d_8e23_d19a_46f0_7c85);
+
+/// Draws shapes and images that carry a `Blend`.
+///
+/// Lyon's shapes and Bevy's sprites are always alpha blended, so blended shapes have their
+/// `ColorMaterial` swapped for a `BlendMaterial`, whose pipeline is specialized with the blend
+/// state of its mode, and blended images are turned into quads with one.
+pub struct BlendPlugin;
+
+impl Plugin for BlendPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, BLEND_SHADER_HANDLE, "blend.wgsl", Shader::from_wgsl);
+        app.add_plugins(Material2dPlugin::<BlendMaterial>::default())
+            .add_systems(PostUpdate, prepare_blends);
+    }
+}
+
+/// How what's drawn is combined with what's already there, like a canvas'
+/// `globalCompositeOperation`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum BlendMode {
+    /// Drawn over, the usual
+    #[default]
+    SrcOver,
+    /// Added to, which only ever lightens
+    Add,
+    /// Multiplied with, which only ever darkens
+    Multiply,
+    /// The inverse of multiplying the inverses, which lightens like light from two projectors
+    Screen,
+}
+
+impl BlendMode {
+    /// The blend state for the premultiplied colors `blend.wgsl` outputs.
+    fn blend_state(self) -> BlendState {
+        let (src_factor, dst_factor) = match self {
+            BlendMode::SrcOver => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Add => (BlendFactor::One, BlendFactor::One),
+            BlendMode::Multiply => (BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Screen => (BlendFactor::One, BlendFactor::OneMinusSrc),
+        };
+        BlendState {
+            color: BlendComponent {
+                src_factor,
+                dst_factor,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+        }
+    }
+}
+
+/// Blends the entity's shape or sprite with the mode. It's only added for modes other than
+/// `BlendMode::SrcOver`, which is how everything is drawn anyway.
+#[derive(Component)]
+pub struct Blend(pub BlendMode);
+
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+#[bind_group_data(BlendMode)]
+pub struct BlendMaterial {
+    /// Multiplied with the texture and the mesh's vertex colors
+    #[uniform(0)]
+    color: Color,
+    /// `None` for shapes, which are colored by their vertices
+    #[texture(1)]
+    #[sampler(2)]
+    texture: Option<Handle<Image>>,
+    mode: BlendMode,
+}
+
+impl From<&BlendMaterial> for BlendMode {
+    fn from(material: &BlendMaterial) -> Self {
+        material.mode
+    }
+}
+
+impl Material2d for BlendMaterial {
+    fn fragment_shader() -> ShaderRef {
+        BLEND_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(fragment) = &mut descriptor.fragment {
+            for target in fragment.targets.iter_mut().flatten() {
+                target.blend = Some(key.bind_group_data.blend_state());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gives shapes and images spawned with a `Blend` their material.
+///
+/// Gradient filled shapes have no `ColorMaterial` to swap and text isn't a mesh, so both are
+/// left alpha blended.
+fn prepare_blends(
+    mut commands: Commands,
+    shapes: Query<(Entity, &Blend), (Added<Blend>, With<Handle<ColorMaterial>>)>,
+    sprites: Query<(Entity, &Blend, &Sprite, &Handle<Image>), Added<Blend>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BlendMaterial>>,
+) {
+    for (entity, Blend(mode)) in shapes.iter() {
+        let material = materials.add(BlendMaterial {
+            color: Color::WHITE,
+            texture: None,
+            mode: *mode,
+        });
+        commands
+            .entity(entity)
+            .insert(material)
+            .remove::<Handle<ColorMaterial>>();
+    }
+    for (entity, Blend(mode), sprite, image) in sprites.iter() {
+        let size = sprite.custom_size.unwrap_or(Vec2::ONE);
+        let mesh = meshes.add(quad(size, sprite.flip_x, sprite.flip_y));
+        let material = materials.add(BlendMaterial {
+            color: sprite.color,
+            texture: Some(image.clone()),
+            mode: *mode,
+        });
+        commands
+            .entity(entity)
+            .insert((Mesh2dHandle(mesh), material))
+            .remove::<(Sprite, Handle<Image>)>();
+    }
+}
+
+/// A `size` rectangle centered on the origin, textured like a sprite flipped along the axes
+/// that are flipped.
+fn quad(size: Vec2, flip_x: bool, flip_y: bool) -> Mesh {
+    let half = size / 2.;
+    let corners = [
+        (Vec2::new(-half.x, -half.y), [0., 1.]),
+        (Vec2::new(half.x, -half.y), [1., 1.]),
+        (Vec2::new(half.x, half.y), [1., 0.]),
+        (Vec2::new(-half.x, half.y), [0., 0.]),
+    ];
+    let positions: Vec<[f32; 3]> = corners.iter().map(|(p, _)| [p.x, p.y, 0.]).collect();
+    let uvs: Vec<[f32; 2]> = corners
+        .iter()
+        .map(|(_, [u, v])| {
+            [
+                if flip_x { 1. - u } else { *u },
+                if flip_y { 1. - v } else { *v },
+            ]
+        })
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 4]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 0, 2, 3])));
+    mesh
+}
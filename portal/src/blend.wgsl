@@ -0,0 +1,16 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(1) @binding(0) var<uniform> color: vec4<f32>;
+@group(1) @binding(1) var blend_texture: texture_2d<f32>;
+@group(1) @binding(2) var blend_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    // Shapes have no texture coordinates, so they sample the corner of the white fallback texture
+    var output = color * textureSample(blend_texture, blend_sampler, mesh.uv);
+#ifdef VERTEX_COLORS
+    output = output * mesh.color;
+#endif
+    // Premultiplied, which the blend states in `blend.rs` expect
+    return vec4<f32>(output.rgb * output.a, output.a);
+}
@@ -0,0 +1,80 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+/// On-disk cache entry for the component served at one URL.
+///
+/// Components are stored compiled, via `Component::serialize`, next to the ETag they were served
+/// with so that they can be revalidated instead of downloaded again.
+pub struct ComponentCache {
+    component_path: PathBuf,
+    etag_path: PathBuf,
+}
+
+impl ComponentCache {
+    pub fn new(dir: &Path, url: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        let key = format!("{:016x}", hasher.finish());
+        Self {
+            component_path: dir.join(format!("{key}.cwasm")),
+            etag_path: dir.join(format!("{key}.etag")),
+        }
+    }
+
+    /// The cached component and its ETag, if there's a component `engine` can run.
+    pub fn load(&self, engine: &Engine) -> Option<(Component, Option<String>)> {
+        let bytes = std::fs::read(&self.component_path).ok()?;
+        // SAFETY: the cache only holds components the portal serialized itself, and
+        // `deserialize` rejects ones from another Wasmtime version or engine configuration.
+        let component = match unsafe { Component::deserialize(engine, bytes) } {
+            Ok(component) => component,
+            Err(e) => {
+                eprintln!(
+                    "ignoring cached component {}: {e}",
+                    self.component_path.display()
+                );
+                return None;
+            }
+        };
+        let etag = std::fs::read_to_string(&self.etag_path).ok();
+        Some((component, etag))
+    }
+
+    pub fn store(&self, component: &Component, etag: Option<&str>) -> wasmtime::Result<()> {
+        if let Some(dir) = self.component_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.component_path, component.serialize()?)?;
+        match etag {
+            Some(etag) => std::fs::write(&self.etag_path, etag)?,
+            None => remove_if_exists(&self.etag_path)?,
+        }
+        Ok(())
+    }
+}
+
+/// `$XDG_CACHE_HOME/levo`, falling back to `~/.cache/levo` and then the temp directory.
+pub fn default_cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("levo")
+}
+
+pub fn clear_cache(dir: &Path) -> std::io::Result<()> {
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// What a guest may do besides drawing, taking input, playing sounds, setting timers and what
+/// WASI gives it apart from files, like clocks and printing.
+///
+/// Every import is linked either way, since a component importing one that isn't can't be
+/// instantiated at all, and a guest that would only fetch now and then shouldn't fail to load.
+/// Instead the imports reader mode doesn't allow call `check`, which traps.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Capabilities {
+    #[default]
+    Full,
+    /// For hosts that aren't trusted: no fetches, storage, navigation, links, files or reading
+    /// back pixels
+    Reader,
+}
+
+impl Capabilities {
+    /// Traps a guest calling `import`, by its name in the wit, if it isn't allowed, saying why so
+    /// its author can tell.
+    pub fn check(self, import: &str) -> wasmtime::Result<()> {
+        match self {
+            Self::Full => Ok(()),
+            Self::Reader => Err(wasmtime::Error::msg(format!(
+                "{import} isn't allowed in reader mode"
+            ))),
+        }
+    }
+}
+
+/// The hosts whose guests run in reader mode, kept between sessions.
+#[derive(Clone, Debug)]
+pub struct ReaderHosts {
+    /// Set by `--reader-mode`, which puts every host in reader mode
+    all: bool,
+    /// Origins as `host_key` gives them, like permission decisions, so F4 and Shift+F4 act on
+    /// the same guests
+    hosts: BTreeSet<String>,
+    path: PathBuf,
+}
+
+impl ReaderHosts {
+    /// The hosts saved at `path` in an earlier session.
+    pub fn load(path: &Path, all: bool) -> Self {
+        let hosts = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            all,
+            hosts,
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// What guests loaded from `url` may do. Guests without a host, like local files, always get
+    /// full capabilities unless `--reader-mode` was given.
+    pub fn capabilities(&self, url: &str) -> Capabilities {
+        let reader = self.all || host_key(url).is_some_and(|host| self.hosts.contains(&host));
+        if reader {
+            Capabilities::Reader
+        } else {
+            Capabilities::Full
+        }
+    }
+
+    /// Puts the origin of `url` in or out of reader mode and saves it for the next session,
+    /// returning what its guests may now do, or why it can't be toggled.
+    pub fn toggle(&mut self, url: &str) -> Result<Capabilities, String> {
+        if self.all {
+            return Err("every host is in reader mode with --reader-mode".to_string());
+        }
+        let host = host_key(url).ok_or_else(|| format!("{url} has no host"))?;
+        if !self.hosts.remove(&host) {
+            self.hosts.insert(host);
+        }
+        if let Err(e) = self.write() {
+            eprintln!("failed to save {}: {e}", self.path.display());
+        }
+        Ok(self.capabilities(url))
+    }
+
+    /// Writes to a temporary file first, like `GuestStorage`.
+    fn write(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&self.hosts)?)?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+/// What guests need the user's permission for, asked once per origin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Permission {
+    Fetch,
+    Storage,
+    Navigate,
+    ReadFile,
+}
+
+impl Permission {
+    /// The name decisions are saved under
+    fn key(self) -> &'static str {
+        match self {
+            Self::Fetch => "fetch",
+            Self::Storage => "storage",
+            Self::Navigate => "navigate",
+            Self::ReadFile => "read-file",
+        }
+    }
+
+    /// What the prompt asks to let the host's guests do.
+    pub fn request(self) -> &'static str {
+        match self {
+            Self::Fetch => "download files from its server",
+            Self::Storage => "keep data on this computer",
+            Self::Navigate => "take you to other pages",
+            Self::ReadFile => "read files the portal was given with --allow-read",
+        }
+    }
+}
+
+/// The origin of `url`, its scheme, host and port, the way permission prompts show it and
+/// decisions are saved under, so `http://` and `https://` guests from one host are asked apart.
+/// `None` for URLs without a host, like local files.
+pub fn host_key(url: &str) -> Option<String> {
+    let origin = url::Url::parse(url).ok()?.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// What the user allowed and denied each host's guests, kept between sessions, and what guests
+/// asked for that the user hasn't answered yet. Clones share their decisions, so guests running
+/// in the background see answers given in the prompt.
+#[derive(Clone, Debug)]
+pub struct HostPermissions(Arc<Mutex<PermissionState>>);
+
+#[derive(Debug)]
+struct PermissionState {
+    /// Whether each permission is allowed, by host and then by `Permission::key`
+    decisions: BTreeMap<String, BTreeMap<String, bool>>,
+    /// Asked for but not yet answered, oldest first, each only once
+    pending: VecDeque<(String, Permission)>,
+    path: PathBuf,
+}
+
+impl HostPermissions {
+    /// The decisions saved at `path` in an earlier session.
+    pub fn load(path: &Path) -> Self {
+        let decisions = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self(Arc::new(Mutex::new(PermissionState {
+            decisions,
+            pending: VecDeque::new(),
+            path: path.to_path_buf(),
+        })))
+    }
+
+    /// Whether guests from `host` may go ahead with `import`, which needs `permission`. Traps if
+    /// the user denied it. Until the user answers, asks them and is `false`, so the call can fail
+    /// the way it would for any other reason.
+    pub fn check(
+        &self,
+        host: &str,
+        permission: Permission,
+        import: &str,
+    ) -> wasmtime::Result<bool> {
+        let mut state = self.0.lock().unwrap();
+        let decision = state
+            .decisions
+            .get(host)
+            .and_then(|decisions| decisions.get(permission.key()));
+        match decision {
+            Some(true) => Ok(true),
+            Some(false) => Err(wasmtime::Error::msg(format!(
+                "{import} was denied for {host}; press Shift+F4 to ask again"
+            ))),
+            None => {
+                let request = (host.to_string(), permission);
+                if !state.pending.contains(&request) {
+                    state.pending.push_back(request);
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// The oldest request the user hasn't answered yet.
+    pub fn pending(&self) -> Option<(String, Permission)> {
+        self.0.lock().unwrap().pending.front().cloned()
+    }
+
+    /// Answers a request, saving the answer for the next session.
+    pub fn decide(&self, host: &str, permission: Permission, allow: bool) {
+        let mut state = self.0.lock().unwrap();
+        state
+            .pending
+            .retain(|request| *request != (host.to_string(), permission));
+        state
+            .decisions
+            .entry(host.to_string())
+            .or_default()
+            .insert(permission.key().to_string(), allow);
+        state.save();
+    }
+
+    /// Forgets what was allowed and denied for `host`, so its guests are asked again.
+    pub fn reset(&self, host: &str) {
+        let mut state = self.0.lock().unwrap();
+        state.pending.retain(|(pending, _)| pending != host);
+        if state.decisions.remove(host).is_some() {
+            state.save();
+        }
+    }
+}
+
+impl PermissionState {
+    fn save(&self) {
+        if let Err(e) = self.write() {
+            eprintln!("failed to save {}: {e}", self.path.display());
+        }
+    }
+
+    /// Writes to a temporary file first, like `GuestStorage`.
+    fn write(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&self.decisions)?)?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+/// Forgets every host's decisions, for `--reset-permissions`.
+pub fn clear_permissions(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
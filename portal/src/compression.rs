@@ -0,0 +1,154 @@
+use std::io::Read;
+
+/// What every WebAssembly binary, component or module, starts with.
+const WASM_MAGIC: &[u8] = b"\0asm";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The encodings the portal asks servers for, most preferred first.
+pub const ACCEPT_ENCODING: &str = "br, zstd, gzip";
+/// Components that decompress to over this many bytes fail, so that a small download can't fill
+/// the portal's memory.
+pub const MAX_DECOMPRESSED_BYTES: u64 = 256 << 20;
+
+/// Decompresses a downloaded component according to its `Content-Encoding`, if the server sent
+/// one.
+///
+/// Bytes that already start with the WebAssembly magic are used as they are, whatever the server
+/// claimed. Without an encoding, gzip and zstd are recognized by their magic bytes, and anything
+/// else is taken to be brotli, which has no magic bytes and is what `levo-server` sends.
+pub fn decompress(bytes: &[u8], encoding: Option<&str>) -> Result<Vec<u8>, String> {
+    decompress_at_most(bytes, encoding, MAX_DECOMPRESSED_BYTES)
+}
+
+/// `decompress` with a limit of `max_bytes`.
+fn decompress_at_most(
+    bytes: &[u8],
+    encoding: Option<&str>,
+    max_bytes: u64,
+) -> Result<Vec<u8>, String> {
+    if bytes.starts_with(WASM_MAGIC) {
+        return Ok(bytes.to_vec());
+    }
+    let encoding = match encoding.map(|e| e.trim().to_ascii_lowercase()) {
+        Some(encoding) if encoding != "identity" => encoding,
+        _ if bytes.starts_with(GZIP_MAGIC) => "gzip".to_string(),
+        _ if bytes.starts_with(ZSTD_MAGIC) => "zstd".to_string(),
+        _ => "br".to_string(),
+    };
+    let failed = |e: std::io::Error| format!("failed to decompress the {encoding} component: {e}");
+    let decoder: Box<dyn Read> = match encoding.as_str() {
+        "br" => Box::new(brotli::Decompressor::new(bytes, 4096)),
+        "gzip" | "x-gzip" => Box::new(flate2::read::GzDecoder::new(bytes)),
+        "zstd" => Box::new(zstd::stream::read::Decoder::new(bytes).map_err(failed)?),
+        other => return Err(format!("unsupported Content-Encoding '{other}'")),
+    };
+    let mut decoded = Vec::new();
+    // One byte past the limit tells a component that's too big from one that's just big enough
+    decoder
+        .take(max_bytes + 1)
+        .read_to_end(&mut decoded)
+        .map_err(failed)?;
+    if decoded.len() as u64 > max_bytes {
+        return Err(format!(
+            "the {encoding} component decompresses to over {max_bytes} bytes"
+        ));
+    }
+    if !decoded.starts_with(WASM_MAGIC) {
+        return Err(format!(
+            "the component decompressed with {encoding} isn't WebAssembly"
+        ));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// The start of a component, which is all `decompress` looks at.
+    const COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(bytes).unwrap();
+        encoder.into_inner()
+    }
+
+    #[test]
+    fn uncompressed_components_are_used_as_they_are() {
+        assert_eq!(decompress(COMPONENT, None).unwrap(), COMPONENT);
+        // Whatever the server claimed
+        assert_eq!(decompress(COMPONENT, Some("br")).unwrap(), COMPONENT);
+    }
+
+    #[test]
+    fn decompresses_what_the_server_says() {
+        let zstd = zstd::encode_all(COMPONENT, 0).unwrap();
+        assert_eq!(decompress(&zstd, Some("zstd")).unwrap(), COMPONENT);
+        assert_eq!(
+            decompress(&gzip(COMPONENT), Some(" GZip ")).unwrap(),
+            COMPONENT
+        );
+        assert_eq!(
+            decompress(&gzip(COMPONENT), Some("x-gzip")).unwrap(),
+            COMPONENT
+        );
+        assert_eq!(
+            decompress(&brotli(COMPONENT), Some("br")).unwrap(),
+            COMPONENT
+        );
+    }
+
+    #[test]
+    fn recognizes_encodings_the_server_left_out() {
+        let zstd = zstd::encode_all(COMPONENT, 0).unwrap();
+        assert_eq!(decompress(&zstd, None).unwrap(), COMPONENT);
+        assert_eq!(decompress(&gzip(COMPONENT), None).unwrap(), COMPONENT);
+        // `identity` says nothing about bytes that aren't WebAssembly, so they're recognized too
+        assert_eq!(
+            decompress(&gzip(COMPONENT), Some("identity")).unwrap(),
+            COMPONENT
+        );
+        // And brotli has no magic bytes to recognize it by
+        assert_eq!(decompress(&brotli(COMPONENT), None).unwrap(), COMPONENT);
+    }
+
+    #[test]
+    fn fails_unknown_encodings() {
+        assert_eq!(
+            decompress(b"compressed", Some("compress")),
+            Err("unsupported Content-Encoding 'compress'".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_what_isnt_webassembly() {
+        assert_eq!(
+            decompress(&gzip(b"<html>"), Some("gzip")),
+            Err("the component decompressed with gzip isn't WebAssembly".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_components_that_decompress_past_the_limit() {
+        let big = [COMPONENT, &[0; 1024]].concat();
+        let zstd = zstd::encode_all(&big[..], 0).unwrap();
+        assert_eq!(
+            decompress_at_most(&zstd, None, 1024),
+            Err("the zstd component decompresses to over 1024 bytes".to_string())
+        );
+        // Right at the limit is fine
+        assert_eq!(
+            decompress_at_most(&zstd, None, big.len() as u64).unwrap(),
+            big
+        );
+    }
+}
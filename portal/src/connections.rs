@@ -0,0 +1,80 @@
+use bevy::prelude::Resource;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use wtransport::endpoint::endpoint_side::Client;
+use wtransport::{Connection, Endpoint, RecvStream, SendStream};
+
+/// WebTransport connections kept open between loads, by the `host:port` they go to, so reloading
+/// a guest opens a new stream rather than waiting for another handshake.
+#[derive(Resource, Clone, Default)]
+pub struct WebTransportConnections {
+    /// Each server's slot is locked for the whole of opening a stream, so loads racing to the
+    /// same server share the connection the first one opens, while loads from other servers
+    /// don't wait on its handshake
+    connections: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+type Slot = Arc<tokio::sync::Mutex<Option<Open>>>;
+
+struct Open {
+    /// Kept for as long as the connection, which it drives
+    _endpoint: Endpoint<Client>,
+    connection: Connection,
+}
+
+impl WebTransportConnections {
+    /// A new stream to `server`, over the connection an earlier load left open if it's still
+    /// alive, or else over a new one from `connect`, which isn't awaited otherwise. A kept
+    /// connection that can't open a stream is taken to be dead, and replaced.
+    pub async fn open_stream(
+        &self,
+        server: &str,
+        connect: impl Future<Output = Result<(Endpoint<Client>, Connection), String>>,
+    ) -> Result<(SendStream, RecvStream), String> {
+        let slot = self.slot(server);
+        let mut open = slot.lock().await;
+        if let Some(kept) = open.as_ref() {
+            match open_bi(&kept.connection).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    eprintln!("reconnecting to {server}, the kept connection failed: {e}");
+                    *open = None;
+                }
+            }
+        }
+        let (endpoint, connection) = connect.await?;
+        let stream = open_bi(&connection).await?;
+        *open = Some(Open {
+            _endpoint: endpoint,
+            connection,
+        });
+        Ok(stream)
+    }
+
+    /// Drops the connection kept for `server`, for when a stream over it failed. If another load
+    /// is opening a stream to `server` right now, it finds out for itself whether the connection
+    /// is dead, and replaces it.
+    pub fn forget(&self, server: &str) {
+        let slot = self.connections.lock().unwrap().get(server).cloned();
+        if let Some(Ok(mut open)) = slot.as_ref().map(|slot| slot.try_lock()) {
+            *open = None;
+        }
+    }
+
+    /// The slot for `server`'s connection, made empty if there isn't one yet. The map is only
+    /// locked for as long as this takes.
+    fn slot(&self, server: &str) -> Slot {
+        self.connections
+            .lock()
+            .unwrap()
+            .entry(server.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+async fn open_bi(connection: &Connection) -> Result<(SendStream, RecvStream), String> {
+    let opening = connection.open_bi().await.map_err(|e| e.to_string())?;
+    opening.await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,271 @@
+use crate::levo::portal::my_imports::FetchStatus;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+/// Past this many unfinished or untaken fetches, `fetch_start` fails.
+pub const MAX_FETCHES: usize = 16;
+/// Responses over this many bytes fail rather than fill the portal's memory.
+pub const MAX_FETCH_BYTES: usize = 32 << 20;
+/// Fetches that take longer than this, body and all, fail.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Past this many redirects, a fetch fails, like reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+type FetchResult = Arc<Mutex<Option<Result<Vec<u8>, String>>>>;
+
+/// The HTTP GETs a guest started with `fetch_start`, which run on the Tokio runtime while the
+/// guest polls for them.
+///
+/// Guests may only fetch from the origin they were served from, so a guest can't use the portal
+/// to reach servers its own host couldn't. That goes for redirects too: one to another origin
+/// fails the fetch.
+pub struct GuestFetches {
+    runtime: tokio::runtime::Handle,
+    client: reqwest::Client,
+    /// The guest's URL, `None` for guests that weren't served over HTTP(S)
+    guest_url: Option<Url>,
+    next_handle: u32,
+    fetches: HashMap<u32, Fetch>,
+}
+
+struct Fetch {
+    /// Filled in once the request finishes
+    result: FetchResult,
+    /// Whether the guest has been updated since the fetch finished
+    announced: bool,
+}
+
+impl GuestFetches {
+    /// Fetches for the guest at `guest_url` with a client from `client`, which gets the redirect
+    /// policy and timeout guest fetches need added.
+    pub fn new(
+        runtime: tokio::runtime::Handle,
+        client: reqwest::ClientBuilder,
+        guest_url: &str,
+    ) -> reqwest::Result<Self> {
+        let guest_url = Url::parse(guest_url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"));
+        let origin = guest_url.as_ref().map(Url::origin);
+        let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if Some(attempt.url().origin()) != origin {
+                let error = format!("redirected to {}, on another origin", attempt.url());
+                attempt.error(error)
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = client.redirect(redirects).timeout(FETCH_TIMEOUT).build()?;
+        Ok(Self {
+            runtime,
+            client,
+            guest_url,
+            next_handle: 0,
+            fetches: HashMap::new(),
+        })
+    }
+
+    /// Starts fetching `url`, which may be relative to the guest's URL. Fails without fetching
+    /// if it's invalid or from another origin.
+    pub fn start(&mut self, url: &str) -> Result<u32, String> {
+        let url = self.resolve(url)?;
+        if self.fetches.len() >= MAX_FETCHES {
+            return Err(format!(
+                "{MAX_FETCHES} fetches are already waiting to be taken"
+            ));
+        }
+        let result = FetchResult::default();
+        let request = self.client.get(url.clone());
+        let slot = result.clone();
+        self.runtime.spawn(async move {
+            let response = get(request)
+                .await
+                .map_err(|e| format!("failed to fetch {url}: {e}"));
+            *slot.lock().unwrap() = Some(response);
+        });
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.fetches.insert(
+            handle,
+            Fetch {
+                result,
+                announced: false,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// `url` joined to the guest's URL, if it's from the guest's origin.
+    fn resolve(&self, url: &str) -> Result<Url, String> {
+        let guest_url = self
+            .guest_url
+            .as_ref()
+            .ok_or("guests that weren't served over HTTP can't fetch")?;
+        let url = guest_url
+            .join(url)
+            .map_err(|e| format!("invalid URL '{url}': {e}"))?;
+        if url.origin() != guest_url.origin() {
+            return Err(format!(
+                "{url} isn't from {}, where the guest was loaded from",
+                guest_url.origin().ascii_serialization()
+            ));
+        }
+        Ok(url)
+    }
+
+    pub fn poll(&self, handle: u32) -> FetchStatus {
+        match self.fetches.get(&handle) {
+            Some(fetch) if fetch.result.lock().unwrap().is_some() => FetchStatus::Ready,
+            Some(_) => FetchStatus::Pending,
+            None => FetchStatus::Unknown,
+        }
+    }
+
+    /// The body of a finished fetch, which frees its handle.
+    pub fn take(&mut self, handle: u32) -> Result<Vec<u8>, String> {
+        let fetch = self
+            .fetches
+            .get(&handle)
+            .ok_or_else(|| format!("no fetch with handle {handle}"))?;
+        let result = fetch
+            .result
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| format!("fetch {handle} hasn't finished"))?;
+        self.fetches.remove(&handle);
+        result
+    }
+
+    /// Whether a fetch finished since this was last asked, which the guest needs an `update` to
+    /// find out about.
+    pub fn newly_finished(&mut self) -> bool {
+        let mut finished = false;
+        for fetch in self.fetches.values_mut() {
+            if !fetch.announced && fetch.result.lock().unwrap().is_some() {
+                fetch.announced = true;
+                finished = true;
+            }
+        }
+        finished
+    }
+}
+
+async fn get(
+    request: reqwest::RequestBuilder,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut response = request.send().await?.error_for_status()?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_FETCH_BYTES {
+            return Err(format!("the response is over {MAX_FETCH_BYTES} bytes").into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::Redirect;
+    use axum::routing::get;
+
+    fn guest_fetches(runtime: &tokio::runtime::Runtime, guest_url: &str) -> GuestFetches {
+        GuestFetches::new(
+            runtime.handle().clone(),
+            reqwest::Client::builder(),
+            guest_url,
+        )
+        .unwrap()
+    }
+
+    /// The result of a fetch once it's finished.
+    fn finish(fetches: &mut GuestFetches, handle: u32) -> Result<Vec<u8>, String> {
+        for _ in 0..1000 {
+            if matches!(fetches.poll(handle), FetchStatus::Ready) {
+                return fetches.take(handle);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("fetch {handle} didn't finish");
+    }
+
+    #[test]
+    fn relative_urls_resolve_against_the_guest() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let fetches = guest_fetches(&runtime, "https://example.com/games/pong.wasm");
+        let resolve = |url| fetches.resolve(url).map(String::from);
+        assert_eq!(
+            resolve("levels/1.json"),
+            Ok("https://example.com/games/levels/1.json".to_string())
+        );
+        assert_eq!(
+            resolve("/scores?top=10"),
+            Ok("https://example.com/scores?top=10".to_string())
+        );
+        assert_eq!(
+            resolve("//example.com/protocol-relative"),
+            Ok("https://example.com/protocol-relative".to_string())
+        );
+    }
+
+    #[test]
+    fn other_origins_are_refused() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut fetches = guest_fetches(&runtime, "https://example.com/pong.wasm");
+        for url in [
+            "https://example.org/",
+            "http://example.com/",
+            "https://example.com:8443/",
+            "//example.org/",
+            "file:///etc/passwd",
+        ] {
+            let refused = fetches.start(url).unwrap_err();
+            assert!(
+                refused
+                    .ends_with("isn't from https://example.com, where the guest was loaded from"),
+                "{refused}"
+            );
+        }
+        assert!(fetches.fetches.is_empty());
+        let mut local = guest_fetches(&runtime, "file:///home/pong.wasm");
+        assert_eq!(
+            local.start("https://example.com/"),
+            Err("guests that weren't served over HTTP can't fetch".to_string())
+        );
+    }
+
+    #[test]
+    fn redirects_to_other_origins_fail() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // `localhost` is the same server, but another origin than `127.0.0.1`
+        let elsewhere = format!("http://localhost:{port}/data");
+        let app = axum::Router::new()
+            .route("/data", get(|| async { "data" }))
+            .route("/same", get(|| async { Redirect::temporary("/data") }))
+            .route(
+                "/other",
+                get(move || {
+                    let elsewhere = elsewhere.clone();
+                    async move { Redirect::temporary(&elsewhere) }
+                }),
+            );
+        runtime.spawn(async move { axum::serve(listener, app).await });
+
+        let mut fetches = guest_fetches(&runtime, &format!("http://127.0.0.1:{port}/guest.wasm"));
+        let same = fetches.start("same").unwrap();
+        assert_eq!(finish(&mut fetches, same), Ok(b"data".to_vec()));
+        let other = fetches.start("other").unwrap();
+        let error = finish(&mut fetches, other).unwrap_err();
+        assert!(error.contains("on another origin"), "{error}");
+    }
+}
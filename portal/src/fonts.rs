@@ -0,0 +1,222 @@
+use ab_glyph::{Font as _, FontArc, ScaleFont};
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::{AssetServer, Handle};
+use bevy::math::Vec2;
+use bevy::prelude::Resource;
+use bevy::text::Font;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// The font in `assets/fonts` that labels fall back to for characters their own font has no glyph
+/// for, like emoji. Labels go without it if the file isn't there.
+const EMOJI_FONT: &str = "NotoEmoji-Regular";
+
+/// The font face picked with `set_font`, `set_font_weight` and `set_font_style`.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct FontChoice {
+    /// `None` for the default font, which only has a regular face
+    pub name: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    /// The handle of a font from `load_font`, which takes the place of the named font and only
+    /// has the face the guest loaded
+    pub guest: Option<u32>,
+}
+
+impl FontChoice {
+    /// The font file for this face relative to the assets directory, falling back to the
+    /// closest face there is a file for, or `None` for the default font.
+    fn path(&self) -> Option<String> {
+        let name = self.name.as_deref()?;
+        let suffixes: &[&str] = match (self.bold, self.italic) {
+            (false, false) => &[""],
+            (true, false) => &["-Bold", ""],
+            (false, true) => &["-Italic", ""],
+            (true, true) => &["-BoldItalic", "-Bold", "-Italic", ""],
+        };
+        let found = suffixes
+            .iter()
+            .enumerate()
+            .find_map(|(i, suffix)| font_path(&format!("{name}{suffix}")).map(|path| (i, path)));
+        match found {
+            None => {
+                eprintln!("no font named {name:?}, using the default font");
+                None
+            }
+            Some((0, path)) => Some(path),
+            Some((_, path)) => {
+                eprintln!(
+                    "no {} face for font {name:?}, using {path}",
+                    &suffixes[0][1..]
+                );
+                Some(path)
+            }
+        }
+    }
+}
+
+/// Fonts guests pick by name with `set_font`, loaded on first use from `assets/fonts/<name>.ttf`
+/// or `.otf`, with bold and italic faces in `<name>-Bold`, `<name>-Italic` and
+/// `<name>-BoldItalic`.
+#[derive(Resource, Default)]
+pub struct GuestFonts {
+    /// `None` for faces without a font file, so the warning is only logged once
+    loaded: HashMap<FontChoice, Option<Handle<Font>>>,
+    /// `None` until the emoji font is first asked for
+    emoji: Option<Option<Handle<Font>>>,
+}
+
+impl GuestFonts {
+    /// The chosen font face, or the default font if there's no such font.
+    pub fn get(&mut self, font: &FontChoice, asset_server: &AssetServer) -> Handle<Font> {
+        self.loaded
+            .entry(font.clone())
+            .or_insert_with(|| font.path().map(|path| asset_server.load(path)))
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// The font labels fall back to for glyphs theirs doesn't have, if there's a file for it.
+    pub fn emoji(&mut self, asset_server: &AssetServer) -> Option<Handle<Font>> {
+        self.emoji
+            .get_or_insert_with(|| font_path(EMOJI_FONT).map(|path| asset_server.load(path)))
+            .clone()
+    }
+}
+
+/// Measures text for `measure_text` the way Bevy lays out labels, with the same font files.
+///
+/// Guests measure in the middle of `update`, so fonts are read from disk here rather than waiting
+/// for the asset server to load them.
+#[derive(Default)]
+pub struct TextMeasurer {
+    /// Bevy's built-in font, copied from its asset before the guest's `setup` runs
+    pub default_font: Option<FontArc>,
+    /// `None` for faces without a readable font file, which are measured with the default font
+    named: HashMap<FontChoice, Option<FontArc>>,
+    /// The fonts from `load_font`, by handle
+    guest: HashMap<u32, FontArc>,
+    /// `None` until the emoji font is first needed
+    emoji: Option<Option<FontArc>>,
+}
+
+impl TextMeasurer {
+    pub fn add_guest_font(&mut self, handle: u32, font: FontArc) {
+        self.guest.insert(handle, font);
+    }
+
+    /// The size of `text` at `size` pixels in the chosen font face.
+    pub fn measure(&mut self, font: &FontChoice, text: &str, size: f32) -> Vec2 {
+        let Some(font) = self.face(font) else {
+            return Vec2::ZERO;
+        };
+        let emoji = self.emoji().map(|emoji| emoji.into_scaled(size));
+        let font = font.as_scaled(size);
+        let mut width: f32 = 0.;
+        let mut lines = 0;
+        for line in text.split('\n') {
+            let mut line_width = 0.;
+            let mut previous = None;
+            for c in line.chars() {
+                let glyph = font.glyph_id(c);
+                match &emoji {
+                    // Glyphs from the fallback font aren't kerned against their neighbours
+                    Some(emoji) if glyph.0 == 0 && emoji.glyph_id(c).0 != 0 => {
+                        line_width += emoji.h_advance(emoji.glyph_id(c));
+                        previous = None;
+                        continue;
+                    }
+                    _ => {}
+                }
+                if let Some(previous) = previous {
+                    line_width += font.kern(previous, glyph);
+                }
+                line_width += font.h_advance(glyph);
+                previous = Some(glyph);
+            }
+            width = width.max(line_width);
+            lines += 1;
+        }
+        let height = lines as f32 * font.height() + (lines - 1) as f32 * font.line_gap();
+        Vec2::new(width, height)
+    }
+
+    /// Splits `text` into the runs to draw in the chosen font face and, marked `true`, the runs
+    /// of characters it has no glyph for that the emoji font does. Characters neither font has
+    /// stay in the run they're in, so joiners and variation selectors don't split up emoji.
+    pub fn fallback_runs(&mut self, font: &FontChoice, text: &str) -> Vec<(Range<usize>, bool)> {
+        let whole = vec![(0..text.len(), false)];
+        let (Some(font), Some(emoji)) = (self.face(font), self.emoji()) else {
+            return whole;
+        };
+        let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            let fallback = match (font.glyph_id(c).0 != 0, emoji.glyph_id(c).0 != 0) {
+                (true, _) => false,
+                (false, true) => true,
+                (false, false) => runs.last().map_or(false, |(_, fallback)| *fallback),
+            };
+            match runs.last_mut() {
+                Some((range, last)) if *last == fallback => range.end = end,
+                _ => runs.push((i..end, fallback)),
+            }
+        }
+        if runs.is_empty() {
+            return whole;
+        }
+        runs
+    }
+
+    /// The font for a face, or the default font if it has no readable file.
+    fn face(&mut self, font: &FontChoice) -> Option<FontArc> {
+        let font = match font.guest {
+            Some(handle) => self.guest.get(&handle),
+            None => self
+                .named
+                .entry(font.clone())
+                .or_insert_with(|| read_font(&font.path()?))
+                .as_ref(),
+        };
+        font.or(self.default_font.as_ref()).cloned()
+    }
+
+    fn emoji(&mut self) -> Option<FontArc> {
+        self.emoji
+            .get_or_insert_with(|| read_font(&font_path(EMOJI_FONT)?))
+            .clone()
+    }
+}
+
+fn read_font(path: &str) -> Option<FontArc> {
+    let path = FileAssetReader::get_base_path().join("assets").join(path);
+    let font = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| FontArc::try_from_vec(bytes).map_err(|e| e.to_string()));
+    match font {
+        Ok(font) => Some(font),
+        Err(e) => {
+            eprintln!("can't measure with {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// The font file for `name` relative to the assets directory, if there is one.
+fn font_path(name: &str) -> Option<String> {
+    // Names come from guests, so they mustn't be able to point outside the fonts directory
+    let is_plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if !is_plain {
+        return None;
+    }
+    let fonts_dir: PathBuf = FileAssetReader::get_base_path().join("assets/fonts");
+    ["ttf", "otf"]
+        .into_iter()
+        .map(|extension| format!("{name}.{extension}"))
+        .find(|file| fonts_dir.join(file).is_file())
+        .map(|file| format!("fonts/{file}"))
+}
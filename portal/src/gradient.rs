@@ -0,0 +1,173 @@
+use bevy::asset::{load_internal_asset, Asset, Assets, Handle};
+use bevy::math::{Mat4, Vec2, Vec4};
+use bevy::prelude::{
+    default, Added, App, Color, Commands, Component, Entity, Plugin, PostUpdate, Query, ResMut,
+    Shader, Transform,
+};
+use bevy::reflect::TypePath;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef, ShaderType};
+use bevy::sprite::{Material2d, Material2dPlugin};
+
+/// Stops past this many are dropped, which keeps the shader's uniform a fixed size.
+pub const MAX_GRADIENT_STOPS: usize = 16;
+
+const GRADIENT_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x8f3c_2a61_5d47_4b0e_9a1f_6c2d_7e80_b513);
+
+/// Draws shapes that carry a `GradientFill`.
+///
+/// Lyon's `Fill` is a single color, so gradient filled shapes are tessellated white as usual and
+/// drawn with `GradientMaterial` instead of a `ColorMaterial`, whose shader works out each
+/// fragment's color from the gradient.
+pub struct GradientPlugin;
+
+impl Plugin for GradientPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            GRADIENT_SHADER_HANDLE,
+            "gradient.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(Material2dPlugin::<GradientMaterial>::default())
+            .add_systems(PostUpdate, prepare_gradient_fills);
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GradientKind {
+    /// Colors change from `start` to `end` and stay the same across that line
+    Linear { start: Vec2, end: Vec2 },
+    /// Colors change from the start circle to the end circle
+    Radial {
+        start: Vec2,
+        start_radius: f32,
+        end: Vec2,
+        end_radius: f32,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Sorted by offset, with stops at the same offset in the order they were added
+    pub stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind) -> Self {
+        Self {
+            kind,
+            stops: Vec::new(),
+        }
+    }
+
+    /// Adds a stop after any others at the same offset, so two stops at one offset make a hard
+    /// edge, as on a canvas.
+    pub fn add_color_stop(&mut self, offset: f32, color: Color) {
+        let index = self.stops.partition_point(|(other, _)| *other <= offset);
+        self.stops.insert(index, (offset, color));
+    }
+
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        for (_, color) in &mut self.stops {
+            color.set_a(color.a() * alpha);
+        }
+        self
+    }
+
+    /// The same gradient in coordinates shifted by `offset`.
+    pub fn translated(mut self, offset: Vec2) -> Self {
+        match &mut self.kind {
+            GradientKind::Linear { start, end } | GradientKind::Radial { start, end, .. } => {
+                *start += offset;
+                *end += offset;
+            }
+        }
+        self
+    }
+}
+
+/// Fills the shape's mesh with a gradient given in the mesh's local coordinates.
+#[derive(Component)]
+pub struct GradientFill(pub Gradient);
+
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+pub struct GradientMaterial {
+    #[uniform(0)]
+    gradient: GradientUniform,
+}
+
+impl Material2d for GradientMaterial {
+    fn fragment_shader() -> ShaderRef {
+        GRADIENT_SHADER_HANDLE.into()
+    }
+}
+
+/// Mirrors `Gradient` in `gradient.wgsl`.
+#[derive(ShaderType, Clone, Default, Debug)]
+struct GradientUniform {
+    /// Takes fragments back to the mesh's local coordinates, which the gradient is given in
+    world_to_local: Mat4,
+    start: Vec2,
+    end: Vec2,
+    start_radius: f32,
+    end_radius: f32,
+    /// 0 for linear gradients and 1 for radial ones
+    kind: u32,
+    stop_count: u32,
+    /// Packed four to a vector, since uniform arrays are laid out 16 bytes to an element
+    offsets: [Vec4; MAX_GRADIENT_STOPS / 4],
+    /// Linear RGBA
+    colors: [Vec4; MAX_GRADIENT_STOPS],
+}
+
+impl GradientUniform {
+    fn new(gradient: &Gradient, transform: &Transform) -> Self {
+        let mut uniform = Self {
+            world_to_local: transform.compute_matrix().inverse(),
+            ..default()
+        };
+        match gradient.kind {
+            GradientKind::Linear { start, end } => {
+                uniform.start = start;
+                uniform.end = end;
+            }
+            GradientKind::Radial {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => {
+                uniform.start = start;
+                uniform.end = end;
+                uniform.start_radius = start_radius;
+                uniform.end_radius = end_radius;
+                uniform.kind = 1;
+            }
+        }
+        let stops = &gradient.stops[..gradient.stops.len().min(MAX_GRADIENT_STOPS)];
+        uniform.stop_count = stops.len() as u32;
+        for (i, (offset, color)) in stops.iter().enumerate() {
+            uniform.offsets[i / 4][i % 4] = *offset;
+            uniform.colors[i] = Vec4::from(color.as_linear_rgba_f32());
+        }
+        uniform
+    }
+}
+
+/// Gives shapes spawned with a `GradientFill` their material.
+///
+/// Guest shapes have no parent, so their `Transform` is already their world transform.
+fn prepare_gradient_fills(
+    mut commands: Commands,
+    fills: Query<(Entity, &GradientFill, &Transform), Added<GradientFill>>,
+    mut materials: ResMut<Assets<GradientMaterial>>,
+) {
+    for (entity, fill, transform) in fills.iter() {
+        let material = materials.add(GradientMaterial {
+            gradient: GradientUniform::new(&fill.0, transform),
+        });
+        commands.entity(entity).insert(material);
+    }
+}
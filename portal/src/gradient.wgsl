@@ -0,0 +1,96 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+// Mirrors `GradientUniform` in `gradient.rs`.
+struct Gradient {
+    world_to_local: mat4x4<f32>,
+    start: vec2<f32>,
+    end: vec2<f32>,
+    start_radius: f32,
+    end_radius: f32,
+    kind: u32,
+    stop_count: u32,
+    offsets: array<vec4<f32>, 4>,
+    colors: array<vec4<f32>, 16>,
+};
+
+@group(1) @binding(0) var<uniform> gradient: Gradient;
+
+fn stop_offset(i: u32) -> f32 {
+    return gradient.offsets[i / 4u][i % 4u];
+}
+
+// Colors are mixed in linear space, which makes midpoints a little lighter than a browser's.
+fn color_at(t: f32) -> vec4<f32> {
+    if t <= stop_offset(0u) {
+        return gradient.colors[0];
+    }
+    for (var i = 1u; i < gradient.stop_count; i++) {
+        let offset = stop_offset(i);
+        if t < offset {
+            let previous = stop_offset(i - 1u);
+            let mixed = (t - previous) / (offset - previous);
+            return mix(gradient.colors[i - 1u], gradient.colors[i], mixed);
+        }
+    }
+    return gradient.colors[gradient.stop_count - 1u];
+}
+
+// The largest `t` for which `position` lies on the circle interpolated between the start and
+// end circles with a radius of at least zero, as canvas radial gradients are defined. `valid` is
+// false where no such circle passes through `position`.
+struct RadialT {
+    t: f32,
+    valid: bool,
+};
+
+fn radial_t(position: vec2<f32>) -> RadialT {
+    let center_delta = gradient.end - gradient.start;
+    let radius_delta = gradient.end_radius - gradient.start_radius;
+    let from_start = position - gradient.start;
+    // |from_start - t * center_delta| = start_radius + t * radius_delta, squared, is
+    // a * t^2 - 2 * b * t + c = 0
+    let a = dot(center_delta, center_delta) - radius_delta * radius_delta;
+    let b = dot(from_start, center_delta) + gradient.start_radius * radius_delta;
+    let c = dot(from_start, from_start) - gradient.start_radius * gradient.start_radius;
+    if abs(a) < 1e-6 {
+        if b == 0.0 {
+            return RadialT(0.0, false);
+        }
+        let t = c / (2.0 * b);
+        return RadialT(t, gradient.start_radius + t * radius_delta >= 0.0);
+    }
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return RadialT(0.0, false);
+    }
+    let root = sqrt(discriminant);
+    let larger = max((b + root) / a, (b - root) / a);
+    if gradient.start_radius + larger * radius_delta >= 0.0 {
+        return RadialT(larger, true);
+    }
+    let smaller = min((b + root) / a, (b - root) / a);
+    return RadialT(smaller, gradient.start_radius + smaller * radius_delta >= 0.0);
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    // A gradient without stops paints nothing
+    if gradient.stop_count == 0u {
+        return vec4<f32>(0.0);
+    }
+    let position = (gradient.world_to_local * mesh.world_position).xy;
+    if gradient.kind == 1u {
+        let radial = radial_t(position);
+        if !radial.valid {
+            return vec4<f32>(0.0);
+        }
+        return color_at(radial.t);
+    }
+    let direction = gradient.end - gradient.start;
+    let length_squared = dot(direction, direction);
+    // Like on a canvas, a linear gradient with no length paints nothing
+    if length_squared == 0.0 {
+        return vec4<f32>(0.0);
+    }
+    return color_at(dot(position - gradient.start, direction) / length_squared);
+}
@@ -0,0 +1,209 @@
+use crate::{
+    call_drag_hooks, call_hook, compile_component, guest_engine, instantiate_guest, Canvas,
+    HostEvent, PortalConfig,
+};
+use bevy::input::mouse::MouseButton;
+use bevy::math::Vec2;
+
+/// Headless guests get a fixed 60 frames a second, whatever the machine running them manages.
+const FRAME_SECONDS: f32 = 1. / 60.;
+/// The canvas headless guests draw on unless `--canvas-size` says otherwise.
+const DEFAULT_CANVAS_SIZE: Vec2 = Vec2::new(800., 600.);
+
+/// Something a headless guest queued in a frame, like a shape or a sound, which shows as its
+/// `Debug` format.
+pub struct RecordedEvent(HostEvent);
+
+impl std::fmt::Debug for RecordedEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Input a headless guest gets at the start of one of its frames.
+#[derive(Clone, Debug)]
+pub struct ScriptedInput {
+    /// Counting from 0, the frame `setup` runs in
+    pub frame: u32,
+    pub action: InputAction,
+}
+
+#[derive(Clone, Debug)]
+pub enum InputAction {
+    /// The cursor moves to a point in guest coordinates
+    Move(Vec2),
+    Press(MouseButton),
+    Release(MouseButton),
+    /// The canvas changes size
+    Resize(Vec2),
+}
+
+/// Parses an input script, one input per line, like `12 move 40 -8`, `12 press left`,
+/// `20 release left` or `30 resize 640 480`. Empty lines and lines starting with `#` are skipped.
+pub fn parse_script(script: &str) -> Result<Vec<ScriptedInput>, String> {
+    let mut inputs = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let input = parse_input(line).map_err(|e| format!("line {}: {e}", number + 1))?;
+        inputs.push(input);
+    }
+    Ok(inputs)
+}
+
+fn parse_input(line: &str) -> Result<ScriptedInput, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let frame = words[0]
+        .parse()
+        .map_err(|_| format!("'{}' isn't a frame number", words[0]))?;
+    let action = match words[1..] {
+        ["move", x, y] => InputAction::Move(parse_point(x, y)?),
+        ["press", button] => InputAction::Press(parse_button(button)?),
+        ["release", button] => InputAction::Release(parse_button(button)?),
+        ["resize", width, height] => InputAction::Resize(parse_point(width, height)?),
+        _ => return Err(format!("'{line}' isn't a move, press, release or resize")),
+    };
+    Ok(ScriptedInput { frame, action })
+}
+
+fn parse_point(x: &str, y: &str) -> Result<Vec2, String> {
+    let parse = |n: &str| n.parse().map_err(|_| format!("'{n}' isn't a number"));
+    Ok(Vec2::new(parse(x)?, parse(y)?))
+}
+
+fn parse_button(button: &str) -> Result<MouseButton, String> {
+    match button {
+        "left" => Ok(MouseButton::Left),
+        "right" => Ok(MouseButton::Right),
+        "middle" => Ok(MouseButton::Middle),
+        _ => Err(format!("'{button}' isn't left, right or middle")),
+    }
+}
+
+/// Runs a guest for `frames` frames without a window, feeding it `script`, and returns what it
+/// queued each frame. `setup` runs in the first frame, before its `update`.
+///
+/// Nothing is drawn, so hooks that depend on what was drawn, like the region hooks, never fire,
+/// and text is measured without the window's default font. Guests still get their budgets,
+/// storage and files as they would in a window.
+pub fn run_headless(
+    component_bytes: &[u8],
+    url: &str,
+    frames: u32,
+    script: &[ScriptedInput],
+    config: &PortalConfig,
+) -> Result<Vec<Vec<RecordedEvent>>, Box<dyn std::error::Error>> {
+    // Guest fetches run on a Tokio runtime, which the window gets from bevy-tokio-tasks
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let _runtime = runtime.enter();
+    let engine = guest_engine()?;
+    let component = compile_component(&engine, component_bytes)?;
+    let canvas = Canvas {
+        size: config.canvas.size.unwrap_or(DEFAULT_CANVAS_SIZE),
+        position: Vec2::ZERO,
+    };
+    let (mut store, bindings) = instantiate_guest(engine, &component, url, canvas, config)?;
+    let store = &mut store.store;
+    let hooks = bindings.hooks;
+    let failed = |call: &str, e: wasmtime::Error| format!("guest {call} failed: {e:?}");
+    store.data_mut().allow_read = config.allow_read.clone();
+
+    let mut recorded = Vec::new();
+    for frame in 0..frames {
+        let elapsed_seconds = frame as f32 * FRAME_SECONDS;
+        let delta_seconds = if frame == 0 { 0. } else { FRAME_SECONDS };
+        let data = store.data_mut();
+        data.delta_seconds = delta_seconds;
+        data.timers.set_now(elapsed_seconds as f64);
+        data.inputs.mouse_buttons_just_pressed.clear();
+        data.inputs.mouse_buttons_just_released.clear();
+        let previous_size = data.canvas.size;
+        let mut buttons = Vec::new();
+        for input in script.iter().filter(|input| input.frame == frame) {
+            let inputs = &mut data.inputs;
+            match input.action {
+                InputAction::Move(p) => {
+                    inputs.cursor_position = Some(p);
+                    inputs.last_cursor_position = p;
+                }
+                InputAction::Press(button) => {
+                    inputs.mouse_buttons_just_pressed.push(button);
+                    if !inputs.mouse_buttons_pressed.contains(&button) {
+                        inputs.mouse_buttons_pressed.push(button);
+                    }
+                    buttons.push((button, true));
+                }
+                InputAction::Release(button) => {
+                    inputs.mouse_buttons_just_released.push(button);
+                    inputs.mouse_buttons_pressed.retain(|&b| b != button);
+                    buttons.push((button, false));
+                }
+                InputAction::Resize(size) => data.canvas.size = size,
+            }
+        }
+        let resized = data.canvas.size != previous_size;
+
+        store
+            .set_fuel(config.fuel_per_frame)
+            .expect("fuel should be enabled in get_wasm");
+        store.set_epoch_deadline(config.call_timeout_ms);
+        if frame == 0 {
+            bindings
+                .bindings
+                .call_setup(&mut *store)
+                .map_err(|e| failed("setup", e))?;
+        }
+        if resized {
+            let data = store.data();
+            let size = data.canvas_config.visible_size(data.canvas.size);
+            call_hook(store, hooks.on_resize, (size.x, size.y))
+                .map_err(|e| failed("on-resize", e))?;
+        }
+        let cursor = store.data().inputs.last_cursor_position;
+        let mut drags = Vec::new();
+        store.data_mut().inputs.drags.moved(cursor, &mut drags);
+        call_drag_hooks(store, &hooks, drags).map_err(|(name, e)| failed(name, e))?;
+        for (button, pressed) in buttons {
+            let mut drags = Vec::new();
+            let inputs = &mut store.data_mut().inputs;
+            if pressed {
+                inputs.drags.press(button, cursor);
+            } else {
+                inputs.drags.release(button, cursor, &mut drags);
+            }
+            call_drag_hooks(store, &hooks, drags).map_err(|(name, e)| failed(name, e))?;
+            if pressed {
+                let count = store.data_mut().inputs.clicks.press(
+                    button,
+                    elapsed_seconds as f64,
+                    cursor,
+                    config.multi_click_interval,
+                );
+                let params = (cursor.x, cursor.y, button.into(), count);
+                call_hook(store, hooks.on_mouse_down, params)
+                    .map_err(|e| failed("on-mouse-down", e))?;
+            } else {
+                let params = (cursor.x, cursor.y, button.into());
+                call_hook(store, hooks.on_mouse_up, params)
+                    .map_err(|e| failed("on-mouse-up", e))?;
+            }
+        }
+        let due = store.data().timers.due();
+        for handle in due {
+            if store.data_mut().timers.fire(handle) {
+                call_hook(store, hooks.on_timer, (handle,)).map_err(|e| failed("on-timer", e))?;
+            }
+        }
+        bindings
+            .bindings
+            .call_update(&mut *store, delta_seconds, elapsed_seconds)
+            .map_err(|e| failed("update", e))?;
+        let queue = std::mem::take(&mut store.data_mut().queue);
+        recorded.push(queue.into_iter().map(RecordedEvent).collect());
+    }
+    Ok(recorded)
+}
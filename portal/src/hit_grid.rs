@@ -0,0 +1,134 @@
+use bevy::math::{IVec2, Rect, Vec2};
+use std::collections::HashMap;
+
+/// World space size of a grid cell.
+const CELL_SIZE: f32 = 64.;
+/// Rectangles covering more cells than this are checked for every point instead, so a few huge
+/// ones can't fill the grid.
+const MAX_CELLS_PER_ENTRY: i64 = 256;
+
+/// Rectangles bucketed by the grid cells they overlap, so finding the ones under a point only
+/// looks at those near it rather than at all of them.
+pub struct HitGrid<T> {
+    /// In the order they were defined
+    entries: Vec<(Rect, T)>,
+    /// Indices into `entries`, ascending
+    cells: HashMap<IVec2, Vec<usize>>,
+    /// Indices of the entries too big for the grid, ascending
+    oversized: Vec<usize>,
+}
+
+impl<T> Default for HitGrid<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+        }
+    }
+}
+
+impl<T: PartialEq> HitGrid<T> {
+    /// Replaces the rectangles, only rebuilding the grid if they changed.
+    pub fn set(&mut self, entries: Vec<(Rect, T)>) {
+        if entries == self.entries {
+            return;
+        }
+        self.cells.clear();
+        self.oversized.clear();
+        for (i, (rect, _)) in entries.iter().enumerate() {
+            let min = cell(rect.min);
+            let max = cell(rect.max);
+            let cells = (max.x as i64 - min.x as i64 + 1) * (max.y as i64 - min.y as i64 + 1);
+            if !rect.min.is_finite() || !rect.max.is_finite() || cells > MAX_CELLS_PER_ENTRY {
+                self.oversized.push(i);
+                continue;
+            }
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.cells.entry(IVec2::new(x, y)).or_default().push(i);
+                }
+            }
+        }
+        self.entries = entries;
+    }
+
+    /// The last defined rectangle containing `point`, if any does.
+    pub fn find(&self, point: Vec2) -> Option<&T> {
+        let near = self.cells.get(&cell(point)).map_or(&[][..], Vec::as_slice);
+        let last = |indices: &[usize]| {
+            indices
+                .iter()
+                .rev()
+                .copied()
+                .find(|&i| self.entries[i].0.contains(point))
+        };
+        let i = match (last(near), last(&self.oversized)) {
+            (Some(a), Some(b)) => a.max(b),
+            (a, b) => a.or(b)?,
+        };
+        Some(&self.entries[i].1)
+    }
+}
+
+fn cell(point: Vec2) -> IVec2 {
+    (point / CELL_SIZE).floor().as_ivec2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Rect {
+        Rect::from_corners(Vec2::new(x, y), Vec2::new(x + size, y + size))
+    }
+
+    #[test]
+    fn finds_the_rectangle_under_a_point() {
+        let mut grid = HitGrid::default();
+        grid.set(vec![
+            (square(0., 0., 10.), "a"),
+            (square(100., 100., 10.), "b"),
+        ]);
+        assert_eq!(grid.find(Vec2::new(5., 5.)), Some(&"a"));
+        assert_eq!(grid.find(Vec2::new(105., 105.)), Some(&"b"));
+        assert_eq!(grid.find(Vec2::new(50., 50.)), None);
+        // Across a cell boundary
+        grid.set(vec![(square(60., 60., 10.), "c")]);
+        assert_eq!(grid.find(Vec2::new(62., 62.)), Some(&"c"));
+        assert_eq!(grid.find(Vec2::new(66., 66.)), Some(&"c"));
+        assert_eq!(grid.find(Vec2::new(5., 5.)), None);
+    }
+
+    #[test]
+    fn last_defined_rectangle_wins_where_they_overlap() {
+        let mut grid = HitGrid::default();
+        grid.set(vec![(square(0., 0., 20.), 1), (square(10., 10., 20.), 2)]);
+        assert_eq!(grid.find(Vec2::new(5., 5.)), Some(&1));
+        assert_eq!(grid.find(Vec2::new(15., 15.)), Some(&2));
+        grid.set(vec![(square(10., 10., 20.), 2), (square(0., 0., 20.), 1)]);
+        assert_eq!(grid.find(Vec2::new(15., 15.)), Some(&1));
+    }
+
+    #[test]
+    fn oversized_rectangles_keep_their_order() {
+        let huge = square(-1e6, -1e6, 2e6);
+        let everywhere = Rect::from_corners(Vec2::NEG_INFINITY, Vec2::INFINITY);
+        let mut grid = HitGrid::default();
+        grid.set(vec![(square(0., 0., 10.), "small"), (huge, "huge")]);
+        assert_eq!(grid.oversized, [1]);
+        assert_eq!(grid.find(Vec2::new(5., 5.)), Some(&"huge"));
+        assert_eq!(grid.find(Vec2::new(5e5, -5e5)), Some(&"huge"));
+        grid.set(vec![(huge, "huge"), (square(0., 0., 10.), "small")]);
+        assert_eq!(grid.find(Vec2::new(5., 5.)), Some(&"small"));
+        assert_eq!(grid.find(Vec2::new(50., 50.)), Some(&"huge"));
+        // Infinite corners don't fit the grid either
+        grid.set(vec![
+            (square(0., 0., 10.), "small"),
+            (everywhere, "everywhere"),
+        ]);
+        assert_eq!(grid.oversized, [1]);
+        assert_eq!(grid.find(Vec2::new(5., 5.)), Some(&"everywhere"));
+        assert_eq!(grid.find(Vec2::new(-1e30, 1e30)), Some(&"everywhere"));
+    }
+}
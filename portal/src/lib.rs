@@ -0,0 +1,7092 @@
+use bevy::core_pipeline::clear_color::{ClearColor, ClearColorConfig};
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::ecs::schedule::IntoSystemConfigs;
+use bevy::ecs::system::SystemParam;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButton, MouseButtonInput, MouseScrollUnit, MouseWheel};
+use bevy::input::ButtonState;
+use bevy::math::{Affine2, Quat, Rect, Vec3};
+use bevy::prelude::{
+    apply_deferred, default, App, AssetServer, Assets, BuildChildren, ButtonBundle, Camera,
+    Camera2d, Camera2dBundle, Changed, Children, Color, ColorMaterial, Commands,
+    DespawnRecursiveExt, Entity, EventReader, GlobalTransform, Handle, HierarchyQueryExt, Image,
+    Input, KeyCode, Mut, OrthographicProjection, PostUpdate, Query, Res, ResMut, Resource,
+    SpatialBundle, Sprite, SpriteBundle, Startup, TextBundle, Transform, Update, Vec2, Visibility,
+    With, Without, World,
+};
+use bevy::render::camera::{RenderTarget, ScalingMode, Viewport};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::texture::{CompressedImageFormats, ImageFormat, ImageSampler, ImageType};
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::Anchor;
+use bevy::text::{Font, Text, Text2dBounds, Text2dBundle, TextAlignment, TextSection, TextStyle};
+use bevy::time::Time;
+use bevy::ui::{BackgroundColor, Display, Interaction, Style, UiCameraConfig};
+use bevy::window::{CursorIcon, PrimaryWindow, Window};
+use bevy::DefaultPlugins;
+use bevy_cosmic_edit::*;
+
+use bevy_prototype_lyon::prelude::{
+    Fill, FillOptions, GeometryBuilder, PathBuilder, ShapeBundle, ShapePlugin, Stroke,
+    StrokeOptions,
+};
+use bevy_prototype_lyon::shapes::{Circle, Polygon, Rectangle, RectangleOrigin};
+use bevy_tokio_tasks::TokioTasksRuntime;
+use clap::Parser;
+use levo::portal::my_imports::{
+    CornerRadii, FetchStatus, FillRule, FontStyle, FontWeight, Host, LineCap, LineJoin,
+    PatternRepeat, TextAlign, TextBaseline, TextDirection,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use url::Url;
+use wasmtime::{component::*, ResourceLimiter};
+use wasmtime::{Config, Engine, Precompiled, Store};
+use wasmtime_wasi::preview2::command::sync;
+use wasmtime_wasi::preview2::{DirPerms, FilePerms, Table, WasiCtx, WasiCtxBuilder, WasiView};
+
+#[path = "ui.rs"]
+mod ui;
+pub use ui::*;
+
+mod audio;
+use audio::{GuestAudio, GuestSound, GuestSoundQuery, MasterVolume};
+
+mod bidi;
+
+mod blend;
+use blend::{Blend, BlendMode, BlendPlugin};
+
+mod cache;
+use cache::ComponentCache;
+
+mod capabilities;
+use capabilities::{Capabilities, HostPermissions, Permission, ReaderHosts};
+
+mod compression;
+
+#[cfg(feature = "webtransport")]
+mod connections;
+
+mod fetch;
+use fetch::GuestFetches;
+
+mod fonts;
+use fonts::{FontChoice, GuestFonts, TextMeasurer};
+
+mod gradient;
+use gradient::{Gradient, GradientFill, GradientKind, GradientPlugin, MAX_GRADIENT_STOPS};
+
+pub mod headless;
+
+pub mod hit_grid;
+use hit_grid::HitGrid;
+
+mod pattern;
+use pattern::{Pattern, PatternFill, PatternPlugin};
+
+mod random;
+use random::GuestRandom;
+
+mod readback;
+use readback::GuestReadbacks;
+
+mod storage;
+use storage::GuestStorage;
+
+mod timers;
+use timers::GuestTimers;
+
+mod wasi_output;
+use wasi_output::GuestOutput;
+
+bindgen!({
+    world: "my-world",
+    path: "../spec",
+    async: false,
+});
+
+/// Levo Portal
+#[derive(Parser, Debug, Resource)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Allow read access to this path
+    #[arg(short, long)]
+    allow_read: Option<PathBuf>,
+    /// Don't show the "chrome" (not yet implemented)
+    #[arg(short, long)]
+    bare: bool,
+    /// Path to the WASM file to run with `--headless`
+    #[arg(short, long)]
+    run: Option<PathBuf>,
+    /// URL to launch the portal with (not yet implemented)
+    #[arg(short, long)]
+    url: Option<String>,
+    /// Path to the WASM file to watch, run and reload on changes (not yet implemented)
+    #[arg(short, long)]
+    watch: Option<PathBuf>,
+    /// Master volume of guest sounds, from 0 (muted) to 1. Cmd/Ctrl+M mutes and unmutes them
+    /// [default: the volume of the last session, or 1]
+    #[arg(long)]
+    volume: Option<f32>,
+    /// Fuel a guest may burn in one `setup` or `update` call, or in its start functions while
+    /// it's instantiated, roughly one unit per instruction
+    #[arg(long, default_value_t = 100_000_000)]
+    fuel_per_frame: u64,
+    /// Milliseconds a guest may spend in one `setup` or `update` call before it's interrupted
+    #[arg(long, default_value_t = 16)]
+    call_timeout_ms: u64,
+    /// Milliseconds between presses for them to count as a double or triple click
+    #[arg(long, default_value_t = 300)]
+    multi_click_ms: u64,
+    /// Milliseconds to wait for a guest's server to connect and answer before the load fails
+    #[arg(long, default_value_t = 10_000)]
+    connect_timeout_ms: u64,
+    /// Milliseconds a guest's server may go without sending anything while its component
+    /// downloads before the load fails
+    #[arg(long, default_value_t = 30_000)]
+    read_timeout_ms: u64,
+    /// Times to try downloading a component when the connection fails or times out, rather than
+    /// the server answering
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    load_attempts: u32,
+    /// Milliseconds to wait before the first retry of a failed download, doubling for each one
+    /// after it
+    #[arg(long, default_value_t = 500)]
+    retry_delay_ms: u64,
+    /// Mebibytes each of a guest's linear memories may grow to
+    #[arg(long, default_value_t = 256)]
+    memory_limit_mib: usize,
+    /// Elements each of a guest's tables may grow to
+    #[arg(long, default_value_t = 100_000)]
+    table_elements_limit: u32,
+    /// Accept any server certificate, for local development against self-signed servers
+    #[arg(long, conflicts_with = "pinned_cert")]
+    no_cert_validation: bool,
+    /// Trust only the certificate in this PEM or DER file instead of the system roots
+    #[arg(long)]
+    pinned_cert: Option<PathBuf>,
+    /// Where downloaded components are cached [default: $XDG_CACHE_HOME/levo]
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+    /// Always download components, without reading or writing the cache
+    #[arg(long)]
+    no_cache: bool,
+    /// Empty the component cache on startup
+    #[arg(long)]
+    clear_cache: bool,
+    /// Forget what every host was allowed and denied on startup, so their guests ask again
+    #[arg(long)]
+    reset_permissions: bool,
+    /// Where guests keep what they store with `storage_set` [default: $XDG_DATA_HOME/levo/storage]
+    #[arg(long)]
+    storage_dir: Option<PathBuf>,
+    /// Where each host gets a directory of files its guests see as `/` through WASI
+    /// [default: $XDG_DATA_HOME/levo/files]
+    #[arg(long)]
+    files_dir: Option<PathBuf>,
+    /// Let guests write to their host's files directory rather than only read from it
+    #[arg(long)]
+    writable_files: bool,
+    /// Run guests from every host in reader mode, which stops them fetching, storing, navigating
+    /// and reading files, rather than just the hosts it's been switched on for with F4
+    #[arg(long)]
+    reader_mode: bool,
+    /// Size of the canvas guests draw on, as `<width>x<height>`, scaled to fit the window
+    /// [default: the size of the window's canvas area]
+    #[arg(long, value_parser = parse_canvas_size)]
+    canvas_size: Option<Vec2>,
+    /// Where guest coordinates start: the center of the canvas with y going up, or its top left
+    /// corner with y going down
+    #[arg(long, value_enum, default_value_t = CanvasOrigin::Center)]
+    origin: CanvasOrigin,
+    /// Color the window is cleared to before guests draw, in any form `fill_style` takes
+    #[arg(long)]
+    background: Option<String>,
+    /// Seed for `random_seed`, `random_f32` and WASI's insecure random numbers, so guests can be
+    /// rerun with the same numbers [default: a different one every session]
+    #[arg(long)]
+    random_seed: Option<u64>,
+    /// An environment variable for guests, as `NAME=value`. Can be given more than once
+    #[arg(long = "guest-env", value_name = "NAME=VALUE", value_parser = parse_guest_env)]
+    guest_env: Vec<(String, String)>,
+    /// An argument for guests, after the guest's URL in their WASI arguments. Can be given more
+    /// than once
+    #[arg(long = "guest-arg", value_name = "ARG")]
+    guest_args: Vec<String>,
+    /// Run the component at `--run` for this many frames without a window, printing what it
+    /// queues each frame
+    #[arg(long, value_name = "FRAMES", requires = "run")]
+    headless: Option<u32>,
+    /// Input for `--headless` guests, one `<frame> move <x> <y>`, `<frame> press <button>`,
+    /// `<frame> release <button>` or `<frame> resize <width> <height>` per line
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    input_script: Option<PathBuf>,
+}
+
+fn parse_guest_env(variable: &str) -> Result<(String, String), String> {
+    match variable.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("'{variable}' isn't like NAME=value")),
+    }
+}
+
+fn parse_canvas_size(size: &str) -> Result<Vec2, String> {
+    let parsed = size.split_once('x').and_then(|(width, height)| {
+        let size = Vec2::new(width.trim().parse().ok()?, height.trim().parse().ok()?);
+        (size.x > 0. && size.y > 0. && size.is_finite()).then_some(size)
+    });
+    parsed.ok_or_else(|| format!("'{size}' isn't a size like 800x600"))
+}
+
+/// How the portal checks the certificates of the servers it loads guests from.
+#[derive(Clone, Debug)]
+enum TlsMode {
+    NoValidation,
+    SystemRoots,
+    PinnedCert(PathBuf),
+}
+
+/// Settings the portal loads and runs guests with.
+#[derive(Resource, Clone, Debug)]
+pub struct PortalConfig {
+    allow_read: Option<PathBuf>,
+    tls_mode: TlsMode,
+    /// `None` when the cache is bypassed
+    cache_dir: Option<PathBuf>,
+    storage_dir: PathBuf,
+    files_dir: PathBuf,
+    writable_files: bool,
+    /// Which hosts' guests run in reader mode
+    reader_hosts: ReaderHosts,
+    /// What the user allowed each host's guests, shared by every guest and the prompt
+    permissions: HostPermissions,
+    canvas: CanvasConfig,
+    background: Color,
+    random_seed: u64,
+    guest_env: Vec<(String, String)>,
+    guest_args: Vec<String>,
+    fuel_per_frame: u64,
+    call_timeout_ms: u64,
+    /// Seconds between presses for them to count as a double or triple click
+    multi_click_interval: f64,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    /// At least 1
+    load_attempts: u32,
+    retry_delay: Duration,
+    limits: GuestLimits,
+}
+
+impl From<&Args> for PortalConfig {
+    fn from(args: &Args) -> Self {
+        let tls_mode = if args.no_cert_validation {
+            TlsMode::NoValidation
+        } else if let Some(path) = &args.pinned_cert {
+            TlsMode::PinnedCert(path.clone())
+        } else {
+            TlsMode::SystemRoots
+        };
+        Self {
+            allow_read: args.allow_read.clone(),
+            tls_mode,
+            cache_dir: (!args.no_cache).then(|| {
+                args.cache_dir
+                    .clone()
+                    .unwrap_or_else(cache::default_cache_dir)
+            }),
+            storage_dir: args
+                .storage_dir
+                .clone()
+                .unwrap_or_else(storage::default_storage_dir),
+            files_dir: args
+                .files_dir
+                .clone()
+                .unwrap_or_else(storage::default_files_dir),
+            writable_files: args.writable_files,
+            reader_hosts: ReaderHosts::load(
+                &storage::default_reader_hosts_path(),
+                args.reader_mode,
+            ),
+            permissions: HostPermissions::load(&storage::default_permissions_path()),
+            canvas: CanvasConfig {
+                size: args.canvas_size,
+                origin: args.origin,
+            },
+            background: args
+                .background
+                .clone()
+                .map_or(ClearColor::default().0, string_to_bevy_color),
+            random_seed: args.random_seed.unwrap_or_else(random::session_seed),
+            guest_env: args.guest_env.clone(),
+            guest_args: args.guest_args.clone(),
+            fuel_per_frame: args.fuel_per_frame,
+            call_timeout_ms: args.call_timeout_ms,
+            multi_click_interval: args.multi_click_ms as f64 / 1000.,
+            connect_timeout: Duration::from_millis(args.connect_timeout_ms),
+            read_timeout: Duration::from_millis(args.read_timeout_ms),
+            load_attempts: args.load_attempts,
+            retry_delay: Duration::from_millis(args.retry_delay_ms),
+            limits: GuestLimits {
+                memory_size: args.memory_limit_mib << 20,
+                table_elements: args.table_elements_limit,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FillRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Debug)]
+struct RoundRect {
+    rect: FillRect,
+    /// Top left, top right, bottom right and bottom left, as on screen
+    radii: [f32; 4],
+}
+
+impl FillRect {
+    /// The rectangle spans from its `(x, y)` corner to `(x + width, y + height)`.
+    fn bounds(&self) -> Rect {
+        Rect::from_corners(
+            Vec2::new(self.x, self.y),
+            Vec2::new(self.x + self.width, self.y + self.height),
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Arc {
+    x: f32,
+    y: f32,
+    radius: f32,
+    sweep_angle: f32,
+    x_rotation: f32,
+}
+
+/// An arc of `radius` touching the line from the current point to `(x1, y1)` and the line from
+/// there to `(x2, y2)`, as for canvas `arcTo`.
+#[derive(Clone, PartialEq, Debug)]
+struct ArcTo {
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    radius: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Ellipse {
+    x: f32,
+    y: f32,
+    radius_x: f32,
+    radius_y: f32,
+    rotation: f32,
+    start_angle: f32,
+    end_angle: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct QuadraticBezierTo {
+    cx: f32,
+    cy: f32,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct CubicBezierTo {
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    x3: f32,
+    y3: f32,
+}
+
+#[derive(PartialEq, Debug)]
+struct Link {
+    url: String,
+    text: String,
+    x: f32,
+    y: f32,
+    size: f32,
+}
+
+#[derive(Debug)]
+struct DrawImage {
+    image: u32,
+    rect: FillRect,
+}
+
+#[derive(PartialEq, Debug)]
+struct Label {
+    text: String,
+    x: f32,
+    y: f32,
+    size: f32,
+    color: String,
+    /// Wraps lines at the width, `None` for labels that don't wrap
+    max_size: Option<Vec2>,
+}
+
+/// A command from a `draw_commands` batch, which replays as a call to the import of the same
+/// name.
+#[derive(Debug)]
+enum BatchedCommand {
+    BeginPath,
+    ClosePath,
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    QuadraticBezierTo([f32; 4]),
+    CubicBezierTo([f32; 6]),
+    Arc([f32; 5]),
+    ArcTo([f32; 5]),
+    Fill,
+    Stroke,
+    Clip,
+    FillRect([f32; 4]),
+    ClearRect([f32; 4]),
+    FillCircle([f32; 3]),
+    StrokeCircle([f32; 3]),
+    DrawLine([f32; 4]),
+    Save,
+    Restore,
+    Translate(f32, f32),
+    Rotate(f32),
+    Scale(f32, f32),
+    SetTransform([f32; 6]),
+    ResetTransform,
+    LineWidth(f32),
+    GlobalAlpha(f32),
+    FillStyle(String),
+    StrokeStyle(String),
+}
+
+/// Reads the arguments of batched commands, in the format `draw-commands` documents.
+struct BatchReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BatchReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or_else(|| format!("the batch ends inside the command at byte {}", self.offset))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], String> {
+        let mut values = [0.; N];
+        for value in &mut values {
+            *value = f32::from_le_bytes(self.take(4)?.try_into().unwrap());
+        }
+        Ok(values)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap());
+        let offset = self.offset;
+        String::from_utf8(self.take(len as usize)?.to_vec())
+            .map_err(|_| format!("the string at byte {offset} isn't UTF-8"))
+    }
+}
+
+/// Decodes a whole `draw_commands` batch, so that a malformed one draws nothing.
+fn decode_batch(bytes: &[u8]) -> Result<Vec<BatchedCommand>, String> {
+    use BatchedCommand as C;
+    let mut reader = BatchReader { bytes, offset: 0 };
+    let mut commands = Vec::new();
+    while let Some(&opcode) = bytes.get(reader.offset) {
+        reader.offset += 1;
+        let command = match opcode {
+            0 => C::BeginPath,
+            1 => C::ClosePath,
+            2 => {
+                let [x, y] = reader.f32s()?;
+                C::MoveTo(x, y)
+            }
+            3 => {
+                let [x, y] = reader.f32s()?;
+                C::LineTo(x, y)
+            }
+            4 => C::QuadraticBezierTo(reader.f32s()?),
+            5 => C::CubicBezierTo(reader.f32s()?),
+            6 => C::Arc(reader.f32s()?),
+            7 => C::ArcTo(reader.f32s()?),
+            8 => C::Fill,
+            9 => C::Stroke,
+            10 => C::Clip,
+            11 => C::FillRect(reader.f32s()?),
+            12 => C::ClearRect(reader.f32s()?),
+            13 => C::FillCircle(reader.f32s()?),
+            14 => C::StrokeCircle(reader.f32s()?),
+            15 => C::DrawLine(reader.f32s()?),
+            16 => C::Save,
+            17 => C::Restore,
+            18 => {
+                let [x, y] = reader.f32s()?;
+                C::Translate(x, y)
+            }
+            19 => C::Rotate(reader.f32s::<1>()?[0]),
+            20 => {
+                let [x, y] = reader.f32s()?;
+                C::Scale(x, y)
+            }
+            21 => C::SetTransform(reader.f32s()?),
+            22 => C::ResetTransform,
+            23 => C::LineWidth(reader.f32s::<1>()?[0]),
+            24 => C::GlobalAlpha(reader.f32s::<1>()?[0]),
+            25 => C::FillStyle(reader.string()?),
+            26 => C::StrokeStyle(reader.string()?),
+            opcode => {
+                return Err(format!(
+                    "unknown command {opcode} at byte {}",
+                    reader.offset - 1
+                ))
+            }
+        };
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+#[derive(Debug)]
+enum HostEvent {
+    Arc(Arc),
+    ArcTo(ArcTo),
+    BeginPath,
+    BlendMode(BlendMode),
+    ClearRect(FillRect),
+    Clip,
+    ClosePath,
+    CubicBezierTo(CubicBezierTo),
+    DefineLink(FillRect, String),
+    DefineRegion(u32, FillRect),
+    DrawImage(DrawImage),
+    Fill,
+    /// Center and radius, from `fill_circle`
+    FillCircle(Vec2, f32),
+    FillEllipse(Ellipse),
+    FillGradient(Gradient),
+    /// An image handle and how it repeats, from `set_fill_pattern`
+    FillPattern(u32, PatternRepeat),
+    FillPolygon(Vec<Vec2>),
+    FillRect(FillRect),
+    FillRoundRect(RoundRect),
+    FillRule(FillRule),
+    FillStyle(String),
+    Font(FontChoice),
+    GlobalAlpha(f32),
+    Label(Label),
+    /// From and to, from `draw_line`
+    Line(Vec2, Vec2),
+    LineTo((f32, f32)),
+    LineCap(LineCap),
+    LineDash(Vec<f32>),
+    LineDashOffset(f32),
+    LineJoin(LineJoin),
+    LineWidth(f32),
+    Link(Link),
+    MiterLimit(f32),
+    MoveTo((f32, f32)),
+    QuadraticBezierTo(QuadraticBezierTo),
+    ResetTransform,
+    Restore,
+    Rotate(f32),
+    Save,
+    Scale((f32, f32)),
+    SetTransform(Affine2),
+    ShadowBlur(f32),
+    ShadowColor(String),
+    ShadowOffset(Vec2),
+    Stroke,
+    /// Center and radius, from `stroke_circle`
+    StrokeCircle(Vec2, f32),
+    StrokeStyle(String),
+    /// The layer later drawing goes to, `None` for the screen
+    Target(Option<usize>),
+    TextAlign(TextAlign),
+    TextBaseline(TextBaseline),
+    TextDirection(TextDirection),
+    Translate((f32, f32)),
+}
+
+#[derive(Default)]
+struct Inputs {
+    keys_just_pressed: Vec<KeyCode>,
+    keys_pressed: Vec<KeyCode>,
+    keys_just_released: Vec<KeyCode>,
+    mouse_buttons_just_pressed: Vec<MouseButton>,
+    mouse_buttons_just_released: Vec<MouseButton>,
+    mouse_buttons_pressed: Vec<MouseButton>,
+    /// In guest coordinates, `None` while the cursor is outside the window
+    cursor_position: Option<Vec2>,
+    /// In guest coordinates, where the cursor was last seen inside the window
+    last_cursor_position: Vec2,
+    clicks: ClickCounter,
+    drags: DragTracker,
+}
+
+/// Presses further apart than this many guest pixels don't make a double click.
+const MULTI_CLICK_DISTANCE: f32 = 4.;
+/// A held button has to move further than this many guest pixels to start a drag.
+const DRAG_DISTANCE: f32 = 4.;
+
+/// A drag for the guest's drag hooks, with positions in guest coordinates.
+#[derive(Debug)]
+enum DragEvent {
+    Start(Vec2, MouseButton),
+    /// The cursor position and how far it moved since the last event
+    Move(Vec2, Vec2, MouseButton),
+    /// The cursor position and how far it moved over the whole drag
+    End(Vec2, Vec2, MouseButton),
+}
+
+/// Turns a button held across cursor moves into drag events.
+#[derive(Default)]
+struct DragTracker {
+    held: Option<Drag>,
+}
+
+struct Drag {
+    button: MouseButton,
+    start: Vec2,
+    /// Where the last `DragEvent::Move` left the cursor
+    last: Vec2,
+    /// Whether the cursor got far enough from `start` for this to be a drag rather than a click
+    started: bool,
+}
+
+impl DragTracker {
+    fn press(&mut self, button: MouseButton, position: Vec2) {
+        if self.held.is_none() {
+            self.held = Some(Drag {
+                button,
+                start: position,
+                last: position,
+                started: false,
+            });
+        }
+    }
+
+    fn moved(&mut self, position: Vec2, events: &mut Vec<DragEvent>) {
+        let Some(drag) = &mut self.held else {
+            return;
+        };
+        if !drag.started {
+            if drag.start.distance(position) <= DRAG_DISTANCE {
+                return;
+            }
+            drag.started = true;
+            events.push(DragEvent::Start(drag.start, drag.button));
+        }
+        if position != drag.last {
+            events.push(DragEvent::Move(position, position - drag.last, drag.button));
+            drag.last = position;
+        }
+    }
+
+    fn release(&mut self, button: MouseButton, position: Vec2, events: &mut Vec<DragEvent>) {
+        if !self.held.as_ref().is_some_and(|drag| drag.button == button) {
+            return;
+        }
+        self.moved(position, events);
+        if let Some(drag) = self.held.take().filter(|drag| drag.started) {
+            events.push(DragEvent::End(position, position - drag.start, button));
+        }
+    }
+}
+
+/// Counts presses of the same button in quick succession, for the `click-count` of
+/// `on-mouse-down`.
+#[derive(Default)]
+struct ClickCounter {
+    /// The latest press's button, time in seconds and position, and its click count
+    last: Option<(MouseButton, f64, Vec2, u32)>,
+}
+
+impl ClickCounter {
+    /// The click count of a press of `button` at `position`, `seconds` into the session.
+    fn press(&mut self, button: MouseButton, seconds: f64, position: Vec2, interval: f64) -> u32 {
+        let count = match self.last {
+            Some((last_button, last_seconds, last_position, count))
+                if last_button == button
+                    && seconds - last_seconds <= interval
+                    && last_position.distance(position) <= MULTI_CLICK_DISTANCE =>
+            {
+                count + 1
+            }
+            _ => 1,
+        };
+        self.last = Some((button, seconds, position, count));
+        count
+    }
+}
+
+#[derive(Debug)]
+struct Canvas {
+    size: Vec2,
+    position: Vec2,
+}
+
+/// Where guest coordinates start, and which way y goes.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum CanvasOrigin {
+    /// The center of the canvas, with y going up
+    #[default]
+    Center,
+    /// The top left corner of the canvas, with y going down like on a web canvas
+    TopLeft,
+}
+
+/// How guest coordinates map onto the canvas area of the window.
+#[derive(Clone, Copy, Debug)]
+struct CanvasConfig {
+    /// The guest's canvas size, scaled to fit the canvas area and centered in it, or `None` for
+    /// the canvas area's own size
+    size: Option<Vec2>,
+    origin: CanvasOrigin,
+}
+
+impl CanvasConfig {
+    /// How many pixels of the canvas area a guest unit takes up.
+    fn scale(&self, area: Vec2) -> f32 {
+        match self.size {
+            Some(size) if area.x > 0. && area.y > 0. => (area / size).min_element(),
+            _ => 1.,
+        }
+    }
+
+    /// The size guests see from `viewport_size`: the configured one, or the canvas area's.
+    fn viewport_size(&self, area: Vec2) -> Vec2 {
+        self.size.unwrap_or(area)
+    }
+
+    /// The size of the whole canvas area in guest units, which is more than the configured size
+    /// along an axis the window has room to spare in.
+    fn visible_size(&self, area: Vec2) -> Vec2 {
+        area / self.scale(area)
+    }
+
+    /// Maps guest coordinates to the centered, y up ones shapes are built in.
+    fn transform(&self, area: Vec2) -> Affine2 {
+        let scale = self.scale(area);
+        match self.origin {
+            CanvasOrigin::Center => Affine2::from_scale(Vec2::splat(scale)),
+            CanvasOrigin::TopLeft => {
+                let size = self.viewport_size(area) * scale;
+                Affine2::from_translation(Vec2::new(-size.x, size.y) / 2.)
+                    * Affine2::from_scale(Vec2::new(scale, -scale))
+            }
+        }
+    }
+}
+
+/// Canvas style drawing state that applies to every shape drawn after it's set.
+#[derive(Clone, Debug)]
+struct DrawState {
+    fill: Paint,
+    fill_rule: FillRule,
+    stroke: Color,
+    line_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    /// Empty for solid lines, see `LineDash`
+    line_dash: Vec<f32>,
+    line_dash_offset: f32,
+    /// Opacity multiplied into every fill and stroke
+    global_alpha: f32,
+    blend_mode: BlendMode,
+    shadow_color: Color,
+    shadow_blur: f32,
+    /// In guest coordinates, but not transformed by `transform`
+    shadow_offset: Vec2,
+    /// Guest space transform accumulated from `translate`, `rotate` and `scale`
+    transform: Affine2,
+    /// `CanvasConfig::transform`, which the portal sets every frame and guests can't change
+    origin: Affine2,
+    text_align: TextAlign,
+    text_baseline: TextBaseline,
+    text_direction: TextDirection,
+    font: FontChoice,
+    /// World space rectangle that shapes drawn from now on are clipped to
+    clip: Option<Rect>,
+}
+
+impl Default for DrawState {
+    fn default() -> Self {
+        Self {
+            fill: Paint::Color(Color::RED),
+            fill_rule: FillRule::NonZero,
+            stroke: Color::RED,
+            line_width: 1.,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.,
+            line_dash: Vec::new(),
+            line_dash_offset: 0.,
+            global_alpha: 1.,
+            blend_mode: BlendMode::SrcOver,
+            shadow_color: Color::NONE,
+            shadow_blur: 0.,
+            shadow_offset: Vec2::ZERO,
+            transform: Affine2::IDENTITY,
+            origin: Affine2::IDENTITY,
+            text_align: TextAlign::Center,
+            text_baseline: TextBaseline::Middle,
+            text_direction: TextDirection::Auto,
+            font: FontChoice::default(),
+            clip: None,
+        }
+    }
+}
+
+impl DrawState {
+    /// The shadow shapes drawn now get, `None` if it wouldn't show, as on a canvas.
+    fn shadow(&self) -> Option<Shadow> {
+        let color = with_alpha(self.shadow_color, self.global_alpha);
+        if color.a() == 0. || (self.shadow_blur == 0. && self.shadow_offset == Vec2::ZERO) {
+            return None;
+        }
+        Some(Shadow {
+            color,
+            offset: self.origin.transform_vector2(self.shadow_offset),
+        })
+    }
+
+    fn fill(&self) -> Paint {
+        self.fill.clone().with_alpha(self.global_alpha)
+    }
+
+    fn stroke(&self) -> StrokePaint {
+        StrokePaint {
+            color: with_alpha(self.stroke, self.global_alpha),
+            line_width: self.line_width,
+            cap: self.line_cap,
+            join: self.line_join,
+            miter_limit: self.miter_limit,
+            dash: (!self.line_dash.is_empty()).then(|| LineDash {
+                segments: self.line_dash.clone(),
+                offset: self.line_dash_offset,
+            }),
+        }
+    }
+
+    /// Applies the guest transform on top of `local`.
+    ///
+    /// Guest coordinates are shifted by `canvas_position` when they're queued, so the transform
+    /// is conjugated by that shift to rotate and scale around the guest's origin.
+    fn entity_transform(&self, canvas_position: Vec2, local: Transform) -> Transform {
+        let world = Affine2::from_translation(-canvas_position)
+            * self.origin
+            * self.transform
+            * Affine2::from_translation(canvas_position);
+        let (scale, angle, translation) = world.to_scale_angle_translation();
+        Transform {
+            translation: translation.extend(0.),
+            rotation: Quat::from_rotation_z(angle),
+            scale: scale.extend(1.),
+        }
+        .mul_transform(local)
+    }
+
+    /// -1 when the canvas origin turns y down, which would mirror text and images that aren't
+    /// scaled by it to turn them back up, and otherwise 1.
+    fn upright(&self) -> f32 {
+        self.origin.matrix2.determinant().signum()
+    }
+}
+
+/// Caps guest memories and tables. Growing past a cap traps rather than failing the grow, so the
+/// guest stops at the allocation and the portal can say why.
+#[derive(Clone, Copy, Debug)]
+struct GuestLimits {
+    memory_size: usize,
+    table_elements: u32,
+}
+
+#[derive(Debug)]
+struct MemoryLimitExceeded;
+
+impl std::fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "guest exceeded memory limit")
+    }
+}
+
+impl std::error::Error for MemoryLimitExceeded {}
+
+impl ResourceLimiter for GuestLimits {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.memory_size {
+            return Err(MemoryLimitExceeded.into());
+        }
+        // Growing past the memory's own declared maximum fails the usual way
+        Ok(!matches!(maximum, Some(maximum) if desired > maximum))
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.table_elements {
+            return Err(MemoryLimitExceeded.into());
+        }
+        Ok(!matches!(maximum, Some(maximum) if desired > maximum))
+    }
+}
+
+struct MyCtx {
+    table: Table,
+    wasi: WasiCtx,
+    queue: Vec<HostEvent>,
+    /// Persists across frames until the guest changes it, like a canvas context does
+    draw_state: DrawState,
+    saved_draw_states: Vec<DrawState>,
+    /// Set once `update` returns, so only finished frames are rendered
+    frame_ready: bool,
+    /// Set by `request_redraw` and cleared when `update` runs
+    redraw_requested: bool,
+    /// Runs `update` every frame for guests that animate, set by `set_continuous_redraw`
+    continuous_redraw: bool,
+    /// Set by `show_diagnostics` until `handle_guest_stats` takes it
+    show_diagnostics: Option<bool>,
+    delta_seconds: f32,
+    limits: GuestLimits,
+    inputs: Inputs,
+    canvas: Canvas,
+    /// The window's physical pixels per logical pixel
+    scale_factor: f32,
+    allow_read: Option<PathBuf>,
+    /// What the guest printed and logged since the console last picked it up
+    printed: Vec<(LogLevel, String)>,
+    /// What the guest wrote to WASI's stdout and stderr, which also ends up on the console
+    stdout: GuestOutput,
+    stderr: GuestOutput,
+    /// The colors the guest set by name, parsed
+    colors: ColorCache,
+    /// Indexed by the handles `create_linear_gradient` and `create_radial_gradient` return
+    gradients: Vec<Gradient>,
+    /// Indexed by the handles `create_pattern` returns, the image handle and how it repeats
+    patterns: Vec<(u32, PatternRepeat)>,
+    /// Indexed by the handles `load_image` returns, `None` until the image is uploaded
+    images: Vec<Option<Handle<Image>>>,
+    /// Decoded images, with their handles, waiting to be added to the image assets
+    pending_images: Vec<(u32, Image)>,
+    /// The pixels of every image `load_image` decoded, counted against `MAX_IMAGE_PIXELS`
+    image_pixels: u64,
+    /// Indexed by the handles `load_font` returns, `None` until the font is added to the assets
+    fonts: Vec<Option<Handle<Font>>>,
+    /// Parsed fonts, with their handles, waiting to be added to the font assets
+    pending_fonts: Vec<(u32, Font)>,
+    /// The layers `create_layer` created, in order, whose handles are image handles
+    layers: Vec<GuestLayer>,
+    text_measurer: TextMeasurer,
+    /// The font the guest picked, kept up to date as the guest draws rather than once the frame
+    /// is resolved like `draw_state`, so that `measure_text` can use it
+    font: FontChoice,
+    /// The fonts `save` saved
+    saved_fonts: Vec<FontChoice>,
+    /// Reader mode traps the imports it doesn't allow, see `Capabilities::check`
+    capabilities: Capabilities,
+    /// The guest's origin as `capabilities::host_key` gives it, `None` for guests without one,
+    /// which don't need permission for anything
+    host: Option<String>,
+    permissions: HostPermissions,
+    /// `None` for guests served from a URL without a host, and in reader mode
+    storage: Option<GuestStorage>,
+    fetches: GuestFetches,
+    readbacks: GuestReadbacks,
+    /// Where the guest asked to go with `navigate`, until `handle_navigation` takes it
+    navigation: Option<String>,
+    /// The hit region the guest was last told the cursor is over
+    hovered_region: Option<u32>,
+    audio: GuestAudio,
+    timers: GuestTimers,
+    canvas_config: CanvasConfig,
+    random: GuestRandom,
+}
+
+/// Past this many, creating a gradient traps, since gradients are never freed.
+const MAX_GRADIENTS: usize = 10_000;
+/// Past this many, loading an image traps, since images live until the guest is reloaded.
+const MAX_IMAGES: usize = 1_000;
+/// Past this many pixels across every image a guest loaded, 512 MiB once decoded, loading another
+/// fails. The decoded images live outside the guest's memory, where `GuestLimits` can't see them.
+const MAX_IMAGE_PIXELS: u64 = 1 << 27;
+/// Past this many, loading a font traps, since fonts live until the guest is reloaded.
+const MAX_FONTS: usize = 100;
+/// Past this many, creating a pattern traps, since patterns are never freed.
+const MAX_PATTERNS: usize = 10_000;
+
+impl MyCtx {
+    /// Whether the guest may go ahead with `import`, which needs `permission`. Traps in reader mode
+    /// and if the user denied it, and is `false` while the user is still being asked.
+    fn allowed(&self, permission: Permission, import: &str) -> wasmtime::Result<bool> {
+        self.capabilities.check(import)?;
+        match &self.host {
+            Some(host) => self.permissions.check(host, permission, import),
+            None => Ok(true),
+        }
+    }
+
+    fn add_gradient(&mut self, kind: GradientKind) -> wasmtime::Result<u32> {
+        if self.gradients.len() >= MAX_GRADIENTS {
+            wasmtime::bail!("guest created more than {MAX_GRADIENTS} gradients");
+        }
+        self.gradients.push(Gradient::new(kind));
+        Ok(self.gradients.len() as u32 - 1)
+    }
+}
+
+impl WasiView for MyCtx {
+    fn table(&self) -> &Table {
+        &self.table
+    }
+    fn table_mut(&mut self) -> &mut Table {
+        &mut self.table
+    }
+    fn ctx(&self) -> &WasiCtx {
+        &self.wasi
+    }
+    fn ctx_mut(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+/// Why a call that needs permission failed while the user is still being asked.
+const ASKING_PERMISSION: &str = "waiting for the user to allow this";
+
+// #[async_trait::async_trait]
+impl Host for MyCtx {
+    fn print(&mut self, from_wasm: String) -> wasmtime::Result<()> {
+        println!("{from_wasm}");
+        self.printed.push((LogLevel::Info, from_wasm));
+        Ok(())
+    }
+
+    fn log(
+        &mut self,
+        level: levo::portal::my_imports::LogLevel,
+        message: String,
+    ) -> wasmtime::Result<()> {
+        let level = LogLevel::from(level);
+        println!("[{}] {message}", level.name());
+        self.printed.push((level, message));
+        Ok(())
+    }
+
+    fn translate(&mut self, x: f32, y: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Translate((x, y)));
+        Ok(())
+    }
+
+    fn rotate(&mut self, radians: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Rotate(radians));
+        Ok(())
+    }
+
+    fn scale(&mut self, sx: f32, sy: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Scale((sx, sy)));
+        Ok(())
+    }
+
+    fn set_transform(
+        &mut self,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue
+            .push(HostEvent::SetTransform(Affine2::from_cols_array(&[
+                a, b, c, d, e, f,
+            ])));
+        Ok(())
+    }
+
+    fn reset_transform(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::ResetTransform);
+        Ok(())
+    }
+
+    fn save(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Save);
+        self.saved_fonts.push(self.font.clone());
+        Ok(())
+    }
+
+    fn restore(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Restore);
+        if let Some(font) = self.saved_fonts.pop() {
+            self.font = font;
+        }
+        Ok(())
+    }
+
+    fn fill_style(&mut self, color: String) -> wasmtime::Result<()> {
+        // Nothing can be drawn with a style that's replaced straight away, so it isn't kept
+        match self.queue.last_mut() {
+            Some(HostEvent::FillStyle(last)) => *last = color,
+            _ => self.queue.push(HostEvent::FillStyle(color)),
+        }
+        Ok(())
+    }
+
+    fn stroke_style(&mut self, color: String) -> wasmtime::Result<()> {
+        match self.queue.last_mut() {
+            Some(HostEvent::StrokeStyle(last)) => *last = color,
+            _ => self.queue.push(HostEvent::StrokeStyle(color)),
+        }
+        Ok(())
+    }
+
+    fn set_line_width(&mut self, width: f32) -> wasmtime::Result<()> {
+        if !width.is_finite() || width <= 0.0 {
+            eprintln!("ignoring line width {width}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::LineWidth(width));
+        Ok(())
+    }
+
+    fn set_fill_rule(&mut self, rule: FillRule) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::FillRule(rule));
+        Ok(())
+    }
+
+    fn set_line_cap(&mut self, cap: LineCap) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::LineCap(cap));
+        Ok(())
+    }
+
+    fn set_line_join(&mut self, join: LineJoin) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::LineJoin(join));
+        Ok(())
+    }
+
+    fn set_miter_limit(&mut self, limit: f32) -> wasmtime::Result<()> {
+        // Lyon can't miter below a limit of 1, and a canvas ignores limits that aren't positive
+        if limit.is_finite() && limit > 0. {
+            self.queue.push(HostEvent::MiterLimit(limit.max(1.)));
+        }
+        Ok(())
+    }
+
+    fn set_line_dash(&mut self, segments: Vec<f32>) -> wasmtime::Result<()> {
+        // Like a canvas, ignore patterns with lengths that can't be measured out
+        if segments
+            .iter()
+            .any(|length| !length.is_finite() || *length < 0.)
+        {
+            eprintln!("ignoring line dash {segments:?}");
+            return Ok(());
+        }
+        // An odd number of lengths is repeated to make the pattern, and a pattern that adds up
+        // to nothing draws solid lines
+        let segments = if segments.iter().sum::<f32>() == 0. {
+            Vec::new()
+        } else if segments.len() % 2 == 1 {
+            segments.repeat(2)
+        } else {
+            segments
+        };
+        self.queue.push(HostEvent::LineDash(segments));
+        Ok(())
+    }
+
+    fn set_line_dash_offset(&mut self, offset: f32) -> wasmtime::Result<()> {
+        if offset.is_finite() {
+            self.queue.push(HostEvent::LineDashOffset(offset));
+        }
+        Ok(())
+    }
+
+    fn set_global_alpha(&mut self, alpha: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::GlobalAlpha(alpha));
+        Ok(())
+    }
+
+    fn set_blend_mode(
+        &mut self,
+        mode: levo::portal::my_imports::BlendMode,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::BlendMode(mode.into()));
+        Ok(())
+    }
+
+    fn set_shadow_color(&mut self, color: String) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::ShadowColor(color));
+        Ok(())
+    }
+
+    fn set_shadow_blur(&mut self, blur: f32) -> wasmtime::Result<()> {
+        // A canvas ignores negative and non-finite blurs
+        if !(blur >= 0. && blur.is_finite()) {
+            eprintln!("ignoring shadow blur {blur}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::ShadowBlur(blur));
+        Ok(())
+    }
+
+    fn set_shadow_offset(&mut self, x: f32, y: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::ShadowOffset(Vec2::new(x, y)));
+        Ok(())
+    }
+
+    fn create_linear_gradient(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+    ) -> wasmtime::Result<u32> {
+        self.add_gradient(GradientKind::Linear {
+            start: Vec2::new(x0, y0),
+            end: Vec2::new(x1, y1),
+        })
+    }
+
+    fn create_radial_gradient(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        r0: f32,
+        x1: f32,
+        y1: f32,
+        r1: f32,
+    ) -> wasmtime::Result<u32> {
+        if r0 < 0. || r1 < 0. {
+            eprintln!("clamping negative radial gradient radii {r0} and {r1} to zero");
+        }
+        self.add_gradient(GradientKind::Radial {
+            start: Vec2::new(x0, y0),
+            start_radius: r0.max(0.),
+            end: Vec2::new(x1, y1),
+            end_radius: r1.max(0.),
+        })
+    }
+
+    fn add_color_stop(
+        &mut self,
+        gradient: u32,
+        offset: f32,
+        color: String,
+    ) -> wasmtime::Result<()> {
+        let Some(gradient) = self.gradients.get_mut(gradient as usize) else {
+            eprintln!("ignoring color stop for unknown gradient {gradient}");
+            return Ok(());
+        };
+        if !(0. ..=1.).contains(&offset) {
+            eprintln!("ignoring color stop at {offset}, outside 0 to 1");
+        } else if gradient.stops.len() >= MAX_GRADIENT_STOPS {
+            eprintln!("ignoring color stop past the first {MAX_GRADIENT_STOPS}");
+        } else {
+            gradient.add_color_stop(offset, self.colors.parse(color));
+        }
+        Ok(())
+    }
+
+    fn set_fill_gradient(&mut self, gradient: u32) -> wasmtime::Result<()> {
+        match self.gradients.get(gradient as usize) {
+            Some(gradient) => self.queue.push(HostEvent::FillGradient(gradient.clone())),
+            None => eprintln!("ignoring unknown gradient {gradient}"),
+        }
+        Ok(())
+    }
+
+    fn create_pattern(
+        &mut self,
+        image: u32,
+        repeat: PatternRepeat,
+    ) -> wasmtime::Result<Result<u32, String>> {
+        if self.patterns.len() >= MAX_PATTERNS {
+            wasmtime::bail!("guest created more than {MAX_PATTERNS} patterns");
+        }
+        if image as usize >= self.images.len() {
+            return Ok(Err(format!("no image with handle {image}")));
+        }
+        let handle = self.patterns.len() as u32;
+        self.patterns.push((image, repeat));
+        Ok(Ok(handle))
+    }
+
+    fn set_fill_pattern(&mut self, pattern: u32) -> wasmtime::Result<()> {
+        match self.patterns.get(pattern as usize) {
+            Some((image, repeat)) => self.queue.push(HostEvent::FillPattern(*image, *repeat)),
+            None => eprintln!("ignoring unknown pattern {pattern}"),
+        }
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::FillRect(FillRect {
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            width,
+            height,
+        }));
+        Ok(())
+    }
+
+    fn fill_round_rect(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+    ) -> wasmtime::Result<()> {
+        self.fill_round_rect_corners(
+            x,
+            y,
+            width,
+            height,
+            CornerRadii {
+                top_left: radius,
+                top_right: radius,
+                bottom_right: radius,
+                bottom_left: radius,
+            },
+        )
+    }
+
+    fn fill_round_rect_corners(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radii: CornerRadii,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::FillRoundRect(RoundRect {
+            rect: FillRect {
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+                width,
+                height,
+            },
+            radii: [
+                radii.top_left,
+                radii.top_right,
+                radii.bottom_right,
+                radii.bottom_left,
+            ],
+        }));
+        Ok(())
+    }
+
+    fn fill_polygon(&mut self, points: Vec<f32>) -> wasmtime::Result<Result<(), String>> {
+        if points.len() % 2 != 0 {
+            return Ok(Err(format!(
+                "expected x, y pairs but got {} values",
+                points.len()
+            )));
+        }
+        let points = points
+            .chunks_exact(2)
+            .map(|point| Vec2::new(point[0], point[1]) - self.canvas.position)
+            .collect();
+        self.queue.push(HostEvent::FillPolygon(points));
+        Ok(Ok(()))
+    }
+
+    fn fill_ellipse(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius_x: f32,
+        radius_y: f32,
+        rotation: f32,
+        start_angle: f32,
+        end_angle: f32,
+    ) -> wasmtime::Result<()> {
+        // A canvas throws on negative radii
+        if radius_x < 0. || radius_y < 0. {
+            eprintln!("ignoring ellipse with negative radii {radius_x}, {radius_y}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::FillEllipse(Ellipse {
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            radius_x,
+            radius_y,
+            rotation,
+            start_angle,
+            end_angle,
+        }));
+        Ok(())
+    }
+
+    fn fill_circle(&mut self, x: f32, y: f32, radius: f32) -> wasmtime::Result<()> {
+        if radius < 0. {
+            eprintln!("ignoring circle with negative radius {radius}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::FillCircle(
+            Vec2::new(x, y) - self.canvas.position,
+            radius,
+        ));
+        Ok(())
+    }
+
+    fn stroke_circle(&mut self, x: f32, y: f32, radius: f32) -> wasmtime::Result<()> {
+        if radius < 0. {
+            eprintln!("ignoring circle with negative radius {radius}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::StrokeCircle(
+            Vec2::new(x, y) - self.canvas.position,
+            radius,
+        ));
+        Ok(())
+    }
+
+    fn clear_rect(&mut self, x: f32, y: f32, width: f32, height: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::ClearRect(FillRect {
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            width,
+            height,
+        }));
+        Ok(())
+    }
+
+    fn begin_path(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::BeginPath);
+        Ok(())
+    }
+
+    fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        sweep_angle: f32,
+        x_rotation: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Arc(Arc {
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            radius,
+            sweep_angle,
+            x_rotation,
+        }));
+        Ok(())
+    }
+
+    fn arc_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, radius: f32) -> wasmtime::Result<()> {
+        // A canvas throws on a negative radius, which would draw the arc inside out
+        if radius < 0. {
+            eprintln!("ignoring arc_to with negative radius {radius}");
+            return Ok(());
+        }
+        self.queue.push(HostEvent::ArcTo(ArcTo {
+            x1: x1 - self.canvas.position.x,
+            y1: y1 - self.canvas.position.y,
+            x2: x2 - self.canvas.position.x,
+            y2: y2 - self.canvas.position.y,
+            radius,
+        }));
+        Ok(())
+    }
+
+    fn close_path(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::ClosePath);
+        Ok(())
+    }
+
+    fn draw_commands(&mut self, encoded: Vec<u8>) -> wasmtime::Result<Result<(), String>> {
+        use BatchedCommand as C;
+        let commands = match decode_batch(&encoded) {
+            Ok(commands) => commands,
+            Err(e) => return Ok(Err(e)),
+        };
+        for command in commands {
+            match command {
+                C::BeginPath => self.begin_path()?,
+                C::ClosePath => self.close_path()?,
+                C::MoveTo(x, y) => self.move_to(x, y)?,
+                C::LineTo(x, y) => self.line_to(x, y)?,
+                C::QuadraticBezierTo([cx, cy, x, y]) => self.quadratic_bezier_to(cx, cy, x, y)?,
+                C::CubicBezierTo([x1, y1, x2, y2, x3, y3]) => {
+                    self.cubic_bezier_to(x1, y1, x2, y2, x3, y3)?
+                }
+                C::Arc([x, y, radius, sweep_angle, x_rotation]) => {
+                    self.arc(x, y, radius, sweep_angle, x_rotation)?
+                }
+                C::ArcTo([x1, y1, x2, y2, radius]) => self.arc_to(x1, y1, x2, y2, radius)?,
+                C::Fill => self.fill()?,
+                C::Stroke => self.stroke()?,
+                C::Clip => self.clip()?,
+                C::FillRect([x, y, width, height]) => self.fill_rect(x, y, width, height)?,
+                C::ClearRect([x, y, width, height]) => self.clear_rect(x, y, width, height)?,
+                C::FillCircle([x, y, radius]) => self.fill_circle(x, y, radius)?,
+                C::StrokeCircle([x, y, radius]) => self.stroke_circle(x, y, radius)?,
+                C::DrawLine([x1, y1, x2, y2]) => self.draw_line(x1, y1, x2, y2)?,
+                C::Save => self.save()?,
+                C::Restore => self.restore()?,
+                C::Translate(x, y) => self.translate(x, y)?,
+                C::Rotate(radians) => self.rotate(radians)?,
+                C::Scale(x, y) => self.scale(x, y)?,
+                C::SetTransform([a, b, c, d, e, f]) => self.set_transform(a, b, c, d, e, f)?,
+                C::ResetTransform => self.reset_transform()?,
+                C::LineWidth(width) => self.set_line_width(width)?,
+                C::GlobalAlpha(alpha) => self.set_global_alpha(alpha)?,
+                C::FillStyle(color) => self.fill_style(color)?,
+                C::StrokeStyle(color) => self.stroke_style(color)?,
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    fn clip(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Clip);
+        Ok(())
+    }
+
+    fn fill(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Fill);
+        Ok(())
+    }
+
+    fn stroke(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Stroke);
+        Ok(())
+    }
+
+    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Line(
+            Vec2::new(x1, y1) - self.canvas.position,
+            Vec2::new(x2, y2) - self.canvas.position,
+        ));
+        Ok(())
+    }
+
+    fn move_to(&mut self, x: f32, y: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::MoveTo((
+            x - self.canvas.position.x,
+            y - self.canvas.position.y,
+        )));
+        Ok(())
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::LineTo((
+            x - self.canvas.position.x,
+            y - self.canvas.position.y,
+        )));
+        Ok(())
+    }
+
+    fn quadratic_bezier_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) -> wasmtime::Result<()> {
+        self.queue
+            .push(HostEvent::QuadraticBezierTo(QuadraticBezierTo {
+                cx: cx - self.canvas.position.x,
+                cy: cy - self.canvas.position.y,
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+            }));
+        Ok(())
+    }
+
+    fn cubic_bezier_to(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        x3: f32,
+        y3: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::CubicBezierTo(CubicBezierTo {
+            x1: x1 - self.canvas.position.x,
+            y1: y1 - self.canvas.position.y,
+            x2: x2 - self.canvas.position.x,
+            y2: y2 - self.canvas.position.y,
+            x3: x3 - self.canvas.position.x,
+            y3: y3 - self.canvas.position.y,
+        }));
+        Ok(())
+    }
+
+    fn link(
+        &mut self,
+        url: String,
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Link(Link {
+            url,
+            text,
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            size,
+        }));
+        Ok(())
+    }
+
+    fn label(
+        &mut self,
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        color: String,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Label(Label {
+            text,
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            size,
+            color,
+            max_size: None,
+        }));
+        Ok(())
+    }
+
+    fn label_box(
+        &mut self,
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        color: String,
+        max_width: f32,
+        max_height: f32,
+    ) -> wasmtime::Result<()> {
+        let max_height = if max_height > 0. {
+            max_height
+        } else {
+            f32::INFINITY
+        };
+        self.queue.push(HostEvent::Label(Label {
+            text,
+            x: x - self.canvas.position.x,
+            y: y - self.canvas.position.y,
+            size,
+            color,
+            max_size: Some(Vec2::new(max_width.max(0.), max_height)),
+        }));
+        Ok(())
+    }
+
+    fn set_text_align(&mut self, align: TextAlign) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::TextAlign(align));
+        Ok(())
+    }
+
+    fn set_text_baseline(&mut self, baseline: TextBaseline) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::TextBaseline(baseline));
+        Ok(())
+    }
+
+    fn set_text_direction(&mut self, direction: TextDirection) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::TextDirection(direction));
+        Ok(())
+    }
+
+    fn set_font(&mut self, name: String) -> wasmtime::Result<()> {
+        self.font.name = (!name.is_empty()).then_some(name);
+        self.font.guest = None;
+        self.queue.push(HostEvent::Font(self.font.clone()));
+        Ok(())
+    }
+
+    fn load_font(&mut self, bytes: Vec<u8>) -> wasmtime::Result<Result<u32, String>> {
+        if self.fonts.len() >= MAX_FONTS {
+            wasmtime::bail!("guest loaded more than {MAX_FONTS} fonts");
+        }
+        let font = match Font::try_from_bytes(bytes) {
+            Ok(font) => font,
+            Err(e) => return Ok(Err(format!("not a TrueType or OpenType font: {e}"))),
+        };
+        let handle = self.fonts.len() as u32;
+        self.text_measurer.add_guest_font(handle, font.font.clone());
+        self.fonts.push(None);
+        self.pending_fonts.push((handle, font));
+        Ok(Ok(handle))
+    }
+
+    fn set_font_handle(&mut self, font: u32) -> wasmtime::Result<()> {
+        if font as usize >= self.fonts.len() {
+            eprintln!("ignoring unknown font {font}");
+            return Ok(());
+        }
+        self.font.guest = Some(font);
+        self.queue.push(HostEvent::Font(self.font.clone()));
+        Ok(())
+    }
+
+    fn set_font_weight(&mut self, weight: FontWeight) -> wasmtime::Result<()> {
+        self.font.bold = weight == FontWeight::Bold;
+        self.queue.push(HostEvent::Font(self.font.clone()));
+        Ok(())
+    }
+
+    fn set_font_style(&mut self, style: FontStyle) -> wasmtime::Result<()> {
+        self.font.italic = style == FontStyle::Italic;
+        self.queue.push(HostEvent::Font(self.font.clone()));
+        Ok(())
+    }
+
+    fn measure_text(
+        &mut self,
+        text: String,
+        size: f32,
+    ) -> wasmtime::Result<levo::portal::my_imports::Size> {
+        let measured = self.text_measurer.measure(&self.font, &text, size);
+        Ok(levo::portal::my_imports::Size {
+            width: measured.x,
+            height: measured.y,
+        })
+    }
+
+    fn load_image(&mut self, bytes: Vec<u8>) -> wasmtime::Result<Result<u32, String>> {
+        if self.images.len() >= MAX_IMAGES {
+            wasmtime::bail!("guest loaded more than {MAX_IMAGES} images");
+        }
+        let format = match bytes.as_slice() {
+            [0x89, b'P', b'N', b'G', ..] => ImageFormat::Png,
+            [0xff, 0xd8, 0xff, ..] => ImageFormat::Jpeg,
+            _ => return Ok(Err("not a PNG or JPEG image".to_string())),
+        };
+        // The header says how big the image decodes to, so a small file can't claim gigabytes
+        let dimensions = image::io::Reader::new(std::io::Cursor::new(&bytes))
+            .with_guessed_format()
+            .map_err(image::ImageError::from)
+            .and_then(|reader| reader.into_dimensions());
+        let (width, height) = match dimensions {
+            Ok(dimensions) => dimensions,
+            Err(e) => return Ok(Err(e.to_string())),
+        };
+        if width > MAX_LAYER_SIZE || height > MAX_LAYER_SIZE {
+            return Ok(Err(format!(
+                "images are at most {MAX_LAYER_SIZE} pixels across, not {width}x{height}"
+            )));
+        }
+        let pixels = width as u64 * height as u64;
+        if self.image_pixels + pixels > MAX_IMAGE_PIXELS {
+            return Ok(Err(format!(
+                "the guest's images would add up to over {MAX_IMAGE_PIXELS} pixels"
+            )));
+        }
+        let image = match Image::from_buffer(
+            &bytes,
+            ImageType::Format(format),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+        ) {
+            Ok(image) => image,
+            Err(e) => return Ok(Err(e.to_string())),
+        };
+        self.image_pixels += pixels;
+        let handle = self.images.len() as u32;
+        self.images.push(None);
+        self.pending_images.push((handle, image));
+        Ok(Ok(handle))
+    }
+
+    fn draw_image(
+        &mut self,
+        image: u32,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::DrawImage(DrawImage {
+            image,
+            rect: FillRect {
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+                width,
+                height,
+            },
+        }));
+        Ok(())
+    }
+
+    fn create_layer(&mut self, width: u32, height: u32) -> wasmtime::Result<Result<u32, String>> {
+        if self.layers.len() >= MAX_LAYERS {
+            return Ok(Err(format!("guests get at most {MAX_LAYERS} layers")));
+        }
+        if width == 0 || height == 0 || width > MAX_LAYER_SIZE || height > MAX_LAYER_SIZE {
+            return Ok(Err(format!(
+                "layers are 1 to {MAX_LAYER_SIZE} pixels across, not {width}x{height}"
+            )));
+        }
+        let mut image = Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Bgra8UnormSrgb,
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_DST
+            | TextureUsages::RENDER_ATTACHMENT;
+        let handle = self.images.len() as u32;
+        self.images.push(None);
+        self.pending_images.push((handle, image));
+        self.layers.push(GuestLayer {
+            image: handle,
+            size: Vec2::new(width as f32, height as f32),
+        });
+        Ok(Ok(handle))
+    }
+
+    fn set_target_layer(&mut self, layer: u32) -> wasmtime::Result<()> {
+        match self.layers.iter().position(|l| l.image == layer) {
+            Some(index) => self.queue.push(HostEvent::Target(Some(index))),
+            None => eprintln!("ignoring unknown layer {layer}"),
+        }
+        Ok(())
+    }
+
+    fn set_target_screen(&mut self) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::Target(None));
+        Ok(())
+    }
+
+    fn draw_layer(&mut self, layer: u32, x: f32, y: f32) -> wasmtime::Result<()> {
+        let Some(GuestLayer { size, .. }) = self.layers.iter().find(|l| l.image == layer) else {
+            eprintln!("ignoring unknown layer {layer}");
+            return Ok(());
+        };
+        self.queue.push(HostEvent::DrawImage(DrawImage {
+            image: layer,
+            rect: FillRect {
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+                width: size.x,
+                height: size.y,
+            },
+        }));
+        Ok(())
+    }
+
+    fn delta_seconds(&mut self) -> wasmtime::Result<f32> {
+        Ok(self.delta_seconds)
+    }
+
+    fn key_just_pressed(
+        &mut self,
+        key: levo::portal::my_imports::KeyCode,
+    ) -> wasmtime::Result<bool> {
+        Ok(self.inputs.keys_just_pressed.contains(&key.into()))
+    }
+
+    fn key_pressed(&mut self, key: levo::portal::my_imports::KeyCode) -> wasmtime::Result<bool> {
+        Ok(self.inputs.keys_pressed.contains(&key.into()))
+    }
+
+    fn key_just_released(
+        &mut self,
+        key: levo::portal::my_imports::KeyCode,
+    ) -> wasmtime::Result<bool> {
+        Ok(self.inputs.keys_just_released.contains(&key.into()))
+    }
+
+    fn mouse_button_just_pressed(
+        &mut self,
+        btn: levo::portal::my_imports::MouseButton,
+    ) -> wasmtime::Result<bool> {
+        Ok(self.inputs.mouse_buttons_just_pressed.contains(&btn.into()))
+    }
+
+    fn mouse_button_just_released(
+        &mut self,
+        btn: levo::portal::my_imports::MouseButton,
+    ) -> wasmtime::Result<bool> {
+        Ok(self
+            .inputs
+            .mouse_buttons_just_released
+            .contains(&btn.into()))
+    }
+
+    fn mouse_button_pressed(
+        &mut self,
+        btn: levo::portal::my_imports::MouseButton,
+    ) -> wasmtime::Result<bool> {
+        Ok(self.inputs.mouse_buttons_pressed.contains(&btn.into()))
+    }
+
+    fn cursor_position(&mut self) -> wasmtime::Result<Option<levo::portal::my_imports::Position>> {
+        Ok(self.inputs.cursor_position.map(Into::into))
+    }
+
+    fn mouse_position(&mut self) -> wasmtime::Result<levo::portal::my_imports::Position> {
+        Ok(self.inputs.last_cursor_position.into())
+    }
+
+    fn request_redraw(&mut self) -> wasmtime::Result<()> {
+        self.redraw_requested = true;
+        Ok(())
+    }
+
+    fn set_continuous_redraw(&mut self, enabled: bool) -> wasmtime::Result<()> {
+        self.continuous_redraw = enabled;
+        Ok(())
+    }
+
+    fn show_diagnostics(&mut self, show: bool) -> wasmtime::Result<()> {
+        self.show_diagnostics = Some(show);
+        Ok(())
+    }
+
+    fn canvas_size(&mut self) -> wasmtime::Result<levo::portal::my_imports::Size> {
+        let size = self.canvas_config.visible_size(self.canvas.size);
+        Ok(levo::portal::my_imports::Size {
+            width: size.x,
+            height: size.y,
+        })
+    }
+
+    fn viewport_size(&mut self) -> wasmtime::Result<levo::portal::my_imports::Size> {
+        let size = self.canvas_config.viewport_size(self.canvas.size);
+        Ok(levo::portal::my_imports::Size {
+            width: size.x,
+            height: size.y,
+        })
+    }
+
+    fn device_pixel_ratio(&mut self) -> wasmtime::Result<f32> {
+        Ok(self.scale_factor * self.canvas_config.scale(self.canvas.size))
+    }
+
+    fn storage_get(&mut self, key: String) -> wasmtime::Result<Option<String>> {
+        if !self.allowed(Permission::Storage, "storage-get")? {
+            return Ok(None);
+        }
+        Ok(self.storage.as_ref().and_then(|storage| storage.get(&key)))
+    }
+
+    fn storage_set(&mut self, key: String, value: String) -> wasmtime::Result<Result<(), String>> {
+        if !self.allowed(Permission::Storage, "storage-set")? {
+            return Ok(Err(ASKING_PERMISSION.to_string()));
+        }
+        Ok(match &mut self.storage {
+            Some(storage) => storage.set(key, value),
+            None => Err("guests without a host have no storage".to_string()),
+        })
+    }
+
+    fn define_link(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        host: String,
+    ) -> wasmtime::Result<()> {
+        if !self.allowed(Permission::Navigate, "define-link")? {
+            return Ok(());
+        }
+        let Some(host) = guest_navigation_url(&host) else {
+            eprintln!("ignoring link to {host}, which isn't an http(s) URL");
+            return Ok(());
+        };
+        self.queue.push(HostEvent::DefineLink(
+            FillRect {
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+                width,
+                height,
+            },
+            host,
+        ));
+        Ok(())
+    }
+
+    fn define_region(
+        &mut self,
+        id: u32,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> wasmtime::Result<()> {
+        self.queue.push(HostEvent::DefineRegion(
+            id,
+            FillRect {
+                x: x - self.canvas.position.x,
+                y: y - self.canvas.position.y,
+                width,
+                height,
+            },
+        ));
+        Ok(())
+    }
+
+    fn navigate(&mut self, url: String) -> wasmtime::Result<()> {
+        if !self.allowed(Permission::Navigate, "navigate")? {
+            return Ok(());
+        }
+        match guest_navigation_url(&url) {
+            Some(url) => self.navigation = Some(url),
+            None => eprintln!("ignoring navigation to {url}, which isn't an http(s) URL"),
+        }
+        Ok(())
+    }
+
+    fn play_sound(&mut self, bytes: Vec<u8>) -> wasmtime::Result<u32> {
+        Ok(self.audio.play(bytes, false, false))
+    }
+
+    fn stop_sound(&mut self, handle: u32) -> wasmtime::Result<()> {
+        self.audio.stop(handle, false);
+        Ok(())
+    }
+
+    fn play_music(&mut self, bytes: Vec<u8>, looping: bool) -> wasmtime::Result<u32> {
+        Ok(self.audio.play(bytes, true, looping))
+    }
+
+    fn set_music_volume(&mut self, handle: u32, volume: f32) -> wasmtime::Result<()> {
+        self.audio.set_volume(handle, true, volume);
+        Ok(())
+    }
+
+    fn stop_music(&mut self, handle: u32) -> wasmtime::Result<()> {
+        self.audio.stop(handle, true);
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, ms: u32) -> wasmtime::Result<u32> {
+        self.timers.set(ms, false)
+    }
+
+    fn set_interval(&mut self, ms: u32) -> wasmtime::Result<u32> {
+        self.timers.set(ms, true)
+    }
+
+    fn clear_timer(&mut self, handle: u32) -> wasmtime::Result<()> {
+        self.timers.clear(handle);
+        Ok(())
+    }
+
+    fn random_seed(&mut self) -> wasmtime::Result<u64> {
+        Ok(self.random.seed())
+    }
+
+    fn random_f32(&mut self) -> wasmtime::Result<f32> {
+        Ok(self.random.next_f32())
+    }
+
+    fn fetch_start(&mut self, url: String) -> wasmtime::Result<Result<u32, String>> {
+        if !self.allowed(Permission::Fetch, "fetch-start")? {
+            return Ok(Err(ASKING_PERMISSION.to_string()));
+        }
+        Ok(self.fetches.start(&url))
+    }
+
+    fn fetch_poll(&mut self, handle: u32) -> wasmtime::Result<FetchStatus> {
+        Ok(self.fetches.poll(handle))
+    }
+
+    fn fetch_take(&mut self, handle: u32) -> wasmtime::Result<Result<Vec<u8>, String>> {
+        Ok(self.fetches.take(handle))
+    }
+
+    fn get_image_data(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> wasmtime::Result<Result<u32, String>> {
+        self.capabilities.check("get-image-data")?;
+        let region = Rect::from_corners(Vec2::new(x, y), Vec2::new(x + width, y + height));
+        Ok(self.readbacks.request(region))
+    }
+
+    fn image_data_poll(&mut self, handle: u32) -> wasmtime::Result<FetchStatus> {
+        Ok(self.readbacks.poll(handle))
+    }
+
+    fn image_data_take(
+        &mut self,
+        handle: u32,
+    ) -> wasmtime::Result<Result<levo::portal::my_imports::ImageData, String>> {
+        Ok(self.readbacks.take(handle))
+    }
+
+    fn read_file(&mut self, path: String) -> wasmtime::Result<Result<Vec<u8>, ()>> {
+        if !self.allowed(Permission::ReadFile, "read-file")? {
+            eprintln!("read_file is waiting for permission");
+            return Ok(Err(()));
+        }
+        if let Some(allow_read) = self.allow_read.as_ref() {
+            let canonicalized_allow_read = match canonicalize_path(Path::new(allow_read)) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Error canonicalizing allow_read path: {}", e.to_string());
+                    return Ok(Err(()));
+                }
+            };
+
+            let full_path = canonicalized_allow_read.join(&path);
+            let canonicalized_full_path = match canonicalize_path(&full_path) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Error canonicalizing full path: {}", e.to_string());
+                    return Ok(Err(()));
+                }
+            };
+
+            if is_path_within_allowed_directory(&canonicalized_allow_read, &canonicalized_full_path)
+            {
+                match std::fs::read(&canonicalized_full_path) {
+                    Ok(content) => Ok(Ok(content)),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        Ok(Err(()))
+                    }
+                }
+            } else {
+                eprintln!(
+                    "Path is not within allowed directory. Allowed: {}. Path: {}",
+                    &canonicalized_allow_read.display(),
+                    &canonicalized_full_path.display()
+                );
+                Ok(Err(()))
+            }
+        } else {
+            eprintln!("read_file is not allowed");
+            Ok(Err(()))
+        }
+    }
+}
+
+impl From<KeyCode> for levo::portal::my_imports::KeyCode {
+    fn from(value: KeyCode) -> Self {
+        use KeyCode as Other;
+        match value {
+            Other::Key1 => Self::Key1,
+            Other::Key2 => Self::Key2,
+            Other::Key3 => Self::Key3,
+            Other::Key4 => Self::Key4,
+            Other::Key5 => Self::Key5,
+            Other::Key6 => Self::Key6,
+            Other::Key7 => Self::Key7,
+            Other::Key8 => Self::Key8,
+            Other::Key9 => Self::Key9,
+            Other::Key0 => Self::Key0,
+            Other::A => Self::A,
+            Other::B => Self::B,
+            Other::C => Self::C,
+            Other::D => Self::D,
+            Other::E => Self::E,
+            Other::F => Self::F,
+            Other::G => Self::G,
+            Other::H => Self::H,
+            Other::I => Self::I,
+            Other::J => Self::J,
+            Other::K => Self::K,
+            Other::L => Self::L,
+            Other::M => Self::M,
+            Other::N => Self::N,
+            Other::O => Self::O,
+            Other::P => Self::P,
+            Other::Q => Self::Q,
+            Other::R => Self::R,
+            Other::S => Self::S,
+            Other::T => Self::T,
+            Other::U => Self::U,
+            Other::V => Self::V,
+            Other::W => Self::W,
+            Other::X => Self::X,
+            Other::Y => Self::Y,
+            Other::Z => Self::Z,
+            Other::Escape => Self::Escape,
+            Other::F1 => Self::F1,
+            Other::F2 => Self::F2,
+            Other::F3 => Self::F3,
+            Other::F4 => Self::F4,
+            Other::F5 => Self::F5,
+            Other::F6 => Self::F6,
+            Other::F7 => Self::F7,
+            Other::F8 => Self::F8,
+            Other::F9 => Self::F9,
+            Other::F10 => Self::F10,
+            Other::F11 => Self::F11,
+            Other::F12 => Self::F12,
+            Other::F13 => Self::F13,
+            Other::F14 => Self::F14,
+            Other::F15 => Self::F15,
+            Other::F16 => Self::F16,
+            Other::F17 => Self::F17,
+            Other::F18 => Self::F18,
+            Other::F19 => Self::F19,
+            Other::F20 => Self::F20,
+            Other::F21 => Self::F21,
+            Other::F22 => Self::F22,
+            Other::F23 => Self::F23,
+            Other::F24 => Self::F24,
+            Other::Snapshot => Self::Snapshot,
+            Other::Scroll => Self::Scroll,
+            Other::Pause => Self::Pause,
+            Other::Insert => Self::Insert,
+            Other::Home => Self::Home,
+            Other::Delete => Self::Delete,
+            Other::End => Self::End,
+            Other::PageDown => Self::PageDown,
+            Other::PageUp => Self::PageUp,
+            Other::Left => Self::Left,
+            Other::Up => Self::Up,
+            Other::Right => Self::Right,
+            Other::Down => Self::Down,
+            Other::Back => Self::Back,
+            Other::Return => Self::Return,
+            Other::Space => Self::Space,
+            Other::Compose => Self::Compose,
+            Other::Caret => Self::Caret,
+            Other::Numlock => Self::Numlock,
+            Other::Numpad0 => Self::Numpad0,
+            Other::Numpad1 => Self::Numpad1,
+            Other::Numpad2 => Self::Numpad2,
+            Other::Numpad3 => Self::Numpad3,
+            Other::Numpad4 => Self::Numpad4,
+            Other::Numpad5 => Self::Numpad5,
+            Other::Numpad6 => Self::Numpad6,
+            Other::Numpad7 => Self::Numpad7,
+            Other::Numpad8 => Self::Numpad8,
+            Other::Numpad9 => Self::Numpad9,
+            Other::AbntC1 => Self::AbntC1,
+            Other::AbntC2 => Self::AbntC2,
+            Other::NumpadAdd => Self::NumpadAdd,
+            Other::Apostrophe => Self::Apostrophe,
+            Other::Apps => Self::Apps,
+            Other::Asterisk => Self::Asterisk,
+            Other::Plus => Self::Plus,
+            Other::At => Self::At,
+            Other::Ax => Self::Ax,
+            Other::Backslash => Self::Backslash,
+            Other::Calculator => Self::Calculator,
+            Other::Capital => Self::Capital,
+            Other::Colon => Self::Colon,
+            Other::Comma => Self::Comma,
+            Other::Convert => Self::Convert,
+            Other::NumpadDecimal => Self::NumpadDecimal,
+            Other::NumpadDivide => Self::NumpadDivide,
+            Other::Equals => Self::Equals,
+            Other::Grave => Self::Grave,
+            Other::Kana => Self::Kana,
+            Other::Kanji => Self::Kanji,
+            Other::AltLeft => Self::AltLeft,
+            Other::BracketLeft => Self::BracketLeft,
+            Other::ControlLeft => Self::ControlLeft,
+            Other::ShiftLeft => Self::ShiftLeft,
+            Other::SuperLeft => Self::SuperLeft,
+            Other::Mail => Self::Mail,
+            Other::MediaSelect => Self::MediaSelect,
+            Other::MediaStop => Self::MediaStop,
+            Other::Minus => Self::Minus,
+            Other::NumpadMultiply => Self::NumpadMultiply,
+            Other::Mute => Self::Mute,
+            Other::MyComputer => Self::MyComputer,
+            Other::NavigateForward => Self::NavigateForward,
+            Other::NavigateBackward => Self::NavigateBackward,
+            Other::NextTrack => Self::NextTrack,
+            Other::NoConvert => Self::NoConvert,
+            Other::NumpadComma => Self::NumpadComma,
+            Other::NumpadEnter => Self::NumpadEnter,
+            Other::NumpadEquals => Self::NumpadEquals,
+            Other::Oem102 => Self::Oem102,
+            Other::Period => Self::Period,
+            Other::PlayPause => Self::PlayPause,
+            Other::Power => Self::Power,
+            Other::PrevTrack => Self::PrevTrack,
+            Other::AltRight => Self::AltRight,
+            Other::BracketRight => Self::BracketRight,
+            Other::ControlRight => Self::ControlRight,
+            Other::ShiftRight => Self::ShiftRight,
+            Other::SuperRight => Self::SuperRight,
+            Other::Semicolon => Self::Semicolon,
+            Other::Slash => Self::Slash,
+            Other::Sleep => Self::Sleep,
+            Other::Stop => Self::Stop,
+            Other::NumpadSubtract => Self::NumpadSubtract,
+            Other::Sysrq => Self::Sysrq,
+            Other::Tab => Self::Tab,
+            Other::Underline => Self::Underline,
+            Other::Unlabeled => Self::Unlabeled,
+            Other::VolumeDown => Self::VolumeDown,
+            Other::VolumeUp => Self::VolumeUp,
+            Other::Wake => Self::Wake,
+            Other::WebBack => Self::WebBack,
+            Other::WebFavorites => Self::WebFavorites,
+            Other::WebForward => Self::WebForward,
+            Other::WebHome => Self::WebHome,
+            Other::WebRefresh => Self::WebRefresh,
+            Other::WebSearch => Self::WebSearch,
+            Other::WebStop => Self::WebStop,
+            Other::Yen => Self::Yen,
+            Other::Copy => Self::Copy,
+            Other::Paste => Self::Paste,
+            Other::Cut => Self::Cut,
+        }
+    }
+}
+
+impl From<levo::portal::my_imports::KeyCode> for KeyCode {
+    fn from(value: levo::portal::my_imports::KeyCode) -> Self {
+        use levo::portal::my_imports::KeyCode as Other;
+        match value {
+            Other::Key1 => Self::Key1,
+            Other::Key2 => Self::Key2,
+            Other::Key3 => Self::Key3,
+            Other::Key4 => Self::Key4,
+            Other::Key5 => Self::Key5,
+            Other::Key6 => Self::Key6,
+            Other::Key7 => Self::Key7,
+            Other::Key8 => Self::Key8,
+            Other::Key9 => Self::Key9,
+            Other::Key0 => Self::Key0,
+            Other::A => Self::A,
+            Other::B => Self::B,
+            Other::C => Self::C,
+            Other::D => Self::D,
+            Other::E => Self::E,
+            Other::F => Self::F,
+            Other::G => Self::G,
+            Other::H => Self::H,
+            Other::I => Self::I,
+            Other::J => Self::J,
+            Other::K => Self::K,
+            Other::L => Self::L,
+            Other::M => Self::M,
+            Other::N => Self::N,
+            Other::O => Self::O,
+            Other::P => Self::P,
+            Other::Q => Self::Q,
+            Other::R => Self::R,
+            Other::S => Self::S,
+            Other::T => Self::T,
+            Other::U => Self::U,
+            Other::V => Self::V,
+            Other::W => Self::W,
+            Other::X => Self::X,
+            Other::Y => Self::Y,
+            Other::Z => Self::Z,
+            Other::Escape => Self::Escape,
+            Other::F1 => Self::F1,
+            Other::F2 => Self::F2,
+            Other::F3 => Self::F3,
+            Other::F4 => Self::F4,
+            Other::F5 => Self::F5,
+            Other::F6 => Self::F6,
+            Other::F7 => Self::F7,
+            Other::F8 => Self::F8,
+            Other::F9 => Self::F9,
+            Other::F10 => Self::F10,
+            Other::F11 => Self::F11,
+            Other::F12 => Self::F12,
+            Other::F13 => Self::F13,
+            Other::F14 => Self::F14,
+            Other::F15 => Self::F15,
+            Other::F16 => Self::F16,
+            Other::F17 => Self::F17,
+            Other::F18 => Self::F18,
+            Other::F19 => Self::F19,
+            Other::F20 => Self::F20,
+            Other::F21 => Self::F21,
+            Other::F22 => Self::F22,
+            Other::F23 => Self::F23,
+            Other::F24 => Self::F24,
+            Other::Snapshot => Self::Snapshot,
+            Other::Scroll => Self::Scroll,
+            Other::Pause => Self::Pause,
+            Other::Insert => Self::Insert,
+            Other::Home => Self::Home,
+            Other::Delete => Self::Delete,
+            Other::End => Self::End,
+            Other::PageDown => Self::PageDown,
+            Other::PageUp => Self::PageUp,
+            Other::Left => Self::Left,
+            Other::Up => Self::Up,
+            Other::Right => Self::Right,
+            Other::Down => Self::Down,
+            Other::Back => Self::Back,
+            Other::Return => Self::Return,
+            Other::Space => Self::Space,
+            Other::Compose => Self::Compose,
+            Other::Caret => Self::Caret,
+            Other::Numlock => Self::Numlock,
+            Other::Numpad0 => Self::Numpad0,
+            Other::Numpad1 => Self::Numpad1,
+            Other::Numpad2 => Self::Numpad2,
+            Other::Numpad3 => Self::Numpad3,
+            Other::Numpad4 => Self::Numpad4,
+            Other::Numpad5 => Self::Numpad5,
+            Other::Numpad6 => Self::Numpad6,
+            Other::Numpad7 => Self::Numpad7,
+            Other::Numpad8 => Self::Numpad8,
+            Other::Numpad9 => Self::Numpad9,
+            Other::AbntC1 => Self::AbntC1,
+            Other::AbntC2 => Self::AbntC2,
+            Other::NumpadAdd => Self::NumpadAdd,
+            Other::Apostrophe => Self::Apostrophe,
+            Other::Apps => Self::Apps,
+            Other::Asterisk => Self::Asterisk,
+            Other::Plus => Self::Plus,
+            Other::At => Self::At,
+            Other::Ax => Self::Ax,
+            Other::Backslash => Self::Backslash,
+            Other::Calculator => Self::Calculator,
+            Other::Capital => Self::Capital,
+            Other::Colon => Self::Colon,
+            Other::Comma => Self::Comma,
+            Other::Convert => Self::Convert,
+            Other::NumpadDecimal => Self::NumpadDecimal,
+            Other::NumpadDivide => Self::NumpadDivide,
+            Other::Equals => Self::Equals,
+            Other::Grave => Self::Grave,
+            Other::Kana => Self::Kana,
+            Other::Kanji => Self::Kanji,
+            Other::AltLeft => Self::AltLeft,
+            Other::BracketLeft => Self::BracketLeft,
+            Other::ControlLeft => Self::ControlLeft,
+            Other::ShiftLeft => Self::ShiftLeft,
+            Other::SuperLeft => Self::SuperLeft,
+            Other::Mail => Self::Mail,
+            Other::MediaSelect => Self::MediaSelect,
+            Other::MediaStop => Self::MediaStop,
+            Other::Minus => Self::Minus,
+            Other::NumpadMultiply => Self::NumpadMultiply,
+            Other::Mute => Self::Mute,
+            Other::MyComputer => Self::MyComputer,
+            Other::NavigateForward => Self::NavigateForward,
+            Other::NavigateBackward => Self::NavigateBackward,
+            Other::NextTrack => Self::NextTrack,
+            Other::NoConvert => Self::NoConvert,
+            Other::NumpadComma => Self::NumpadComma,
+            Other::NumpadEnter => Self::NumpadEnter,
+            Other::NumpadEquals => Self::NumpadEquals,
+            Other::Oem102 => Self::Oem102,
+            Other::Period => Self::Period,
+            Other::PlayPause => Self::PlayPause,
+            Other::Power => Self::Power,
+            Other::PrevTrack => Self::PrevTrack,
+            Other::AltRight => Self::AltRight,
+            Other::BracketRight => Self::BracketRight,
+            Other::ControlRight => Self::ControlRight,
+            Other::ShiftRight => Self::ShiftRight,
+            Other::SuperRight => Self::SuperRight,
+            Other::Semicolon => Self::Semicolon,
+            Other::Slash => Self::Slash,
+            Other::Sleep => Self::Sleep,
+            Other::Stop => Self::Stop,
+            Other::NumpadSubtract => Self::NumpadSubtract,
+            Other::Sysrq => Self::Sysrq,
+            Other::Tab => Self::Tab,
+            Other::Underline => Self::Underline,
+            Other::Unlabeled => Self::Unlabeled,
+            Other::VolumeDown => Self::VolumeDown,
+            Other::VolumeUp => Self::VolumeUp,
+            Other::Wake => Self::Wake,
+            Other::WebBack => Self::WebBack,
+            Other::WebFavorites => Self::WebFavorites,
+            Other::WebForward => Self::WebForward,
+            Other::WebHome => Self::WebHome,
+            Other::WebRefresh => Self::WebRefresh,
+            Other::WebSearch => Self::WebSearch,
+            Other::WebStop => Self::WebStop,
+            Other::Yen => Self::Yen,
+            Other::Copy => Self::Copy,
+            Other::Paste => Self::Paste,
+            Other::Cut => Self::Cut,
+        }
+    }
+}
+
+impl From<levo::portal::my_imports::BlendMode> for BlendMode {
+    fn from(value: levo::portal::my_imports::BlendMode) -> Self {
+        use levo::portal::my_imports::BlendMode as Other;
+        match value {
+            Other::SrcOver => Self::SrcOver,
+            Other::Add => Self::Add,
+            Other::Multiply => Self::Multiply,
+            Other::Screen => Self::Screen,
+        }
+    }
+}
+
+impl From<levo::portal::my_imports::LogLevel> for LogLevel {
+    fn from(value: levo::portal::my_imports::LogLevel) -> Self {
+        use levo::portal::my_imports::LogLevel as Other;
+        match value {
+            Other::Debug => Self::Debug,
+            Other::Info => Self::Info,
+            Other::Warn => Self::Warn,
+            Other::Error => Self::Error,
+        }
+    }
+}
+
+impl From<levo::portal::my_imports::MouseButton> for MouseButton {
+    fn from(value: levo::portal::my_imports::MouseButton) -> Self {
+        use levo::portal::my_imports::MouseButton as Other;
+        match value {
+            Other::Left => Self::Left,
+            Other::Right => Self::Right,
+            Other::Middle => Self::Middle,
+            Other::Other(inner) => Self::Other(inner),
+        }
+    }
+}
+
+impl From<MouseButton> for levo::portal::my_imports::MouseButton {
+    fn from(value: MouseButton) -> Self {
+        use MouseButton as Other;
+        match value {
+            Other::Left => Self::Left,
+            Other::Right => Self::Right,
+            Other::Middle => Self::Middle,
+            Other::Other(inner) => Self::Other(inner),
+        }
+    }
+}
+
+impl From<levo::portal::my_imports::Position> for Vec2 {
+    fn from(p: levo::portal::my_imports::Position) -> Self {
+        Vec2::new(p.x, p.y)
+    }
+}
+
+impl From<Vec2> for levo::portal::my_imports::Position {
+    fn from(v: Vec2) -> Self {
+        levo::portal::my_imports::Position { x: v.x, y: v.y }
+    }
+}
+
+#[derive(Resource)]
+struct WasmStore {
+    store: Store<MyCtx>,
+    /// Stops ticking the store's engine once the store is replaced
+    _epoch_ticker: EpochTicker,
+}
+
+/// Increments an engine's epoch every millisecond until dropped, so epoch deadlines on its stores
+/// are counted in milliseconds.
+struct EpochTicker(tokio::task::JoinHandle<()>);
+
+impl EpochTicker {
+    fn spawn(engine: Engine) -> Self {
+        Self(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(1));
+            loop {
+                interval.tick().await;
+                engine.increment_epoch();
+            }
+        }))
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[derive(Resource)]
+struct WasmBindings {
+    bindings: MyWorld,
+    hooks: GuestHooks,
+    first_run: bool,
+    /// `Time::elapsed_seconds` at the guest's first update, `None` until then
+    started_at: Option<f32>,
+    /// `Time::elapsed_seconds` at the guest's latest update, `None` until the first one
+    last_update_at: Option<f32>,
+}
+
+/// Runs the portal as the command line asks.
+pub fn run() {
+    let args = Args::parse();
+    eprintln!("{:?}", &args);
+    if args.clear_cache {
+        let dir = args
+            .cache_dir
+            .clone()
+            .unwrap_or_else(cache::default_cache_dir);
+        if let Err(e) = cache::clear_cache(&dir) {
+            eprintln!("failed to clear the cache at {}: {e}", dir.display());
+        }
+    }
+    if args.reset_permissions {
+        let path = storage::default_permissions_path();
+        if let Err(e) = capabilities::clear_permissions(&path) {
+            eprintln!("failed to reset permissions at {}: {e}", path.display());
+        }
+    }
+
+    if let Some(frames) = args.headless {
+        if let Err(e) = run_headless(&args, frames) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let master_volume = MasterVolume::load(&storage::default_settings_path(), args.volume);
+    let config = PortalConfig::from(&args);
+
+    App::new()
+        .insert_resource(ClearColor(config.background))
+        .insert_resource(config)
+        .insert_resource(master_volume.global_volume())
+        .insert_resource(master_volume)
+        .insert_resource(args)
+        .add_plugins(DefaultPlugins)
+        .add_plugins(CosmicEditPlugin::default())
+        .add_plugins(ShapePlugin)
+        .add_plugins(GradientPlugin)
+        .add_plugins(PatternPlugin)
+        .add_plugins(BlendPlugin)
+        .add_plugins(FrameTimeDiagnosticsPlugin)
+        .init_resource::<GuestScene>()
+        .init_resource::<GuestFonts>()
+        .init_resource::<LoadStatus>()
+        .init_resource::<Console>()
+        .init_resource::<Diagnostics>()
+        .init_resource::<History>()
+        .init_resource::<Tabs>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, handle_get_wasm)
+        .add_systems(Update, handle_reload)
+        .add_systems(Update, handle_reader_mode)
+        .add_systems(Update, handle_permission_prompt)
+        .add_systems(Update, handle_history)
+        .add_systems(Update, handle_tabs)
+        .add_systems(Update, handle_abort)
+        // A failed `setup` unloads the guest through commands, which have to land before `update`
+        .add_systems(
+            Update,
+            (run_wasm_setup, apply_deferred, run_wasm_update).chain(),
+        )
+        .add_systems(Update, handle_guest_event.after(run_wasm_update))
+        .add_systems(Update, update_clip_cameras.after(handle_guest_event))
+        .add_systems(Update, update_layer_cameras.after(handle_guest_event))
+        .add_systems(Update, handle_refresh)
+        .add_systems(Update, handle_load_status)
+        .add_systems(Update, handle_guest_print.after(run_wasm_update))
+        .add_systems(Update, handle_guest_audio.after(run_wasm_update))
+        .add_systems(Update, handle_mute.after(handle_guest_audio))
+        .add_systems(
+            Update,
+            hide_notice.after(handle_mute).after(handle_reader_mode),
+        )
+        .add_systems(Update, handle_readbacks.after(run_wasm_update))
+        .add_systems(Update, handle_console.after(handle_guest_print))
+        .add_systems(Update, handle_guest_stats.after(handle_guest_event))
+        .add_systems(Update, handle_diagnostics.after(handle_guest_stats))
+        .add_systems(PostUpdate, handle_link)
+        .add_systems(PostUpdate, handle_link_areas.before(handle_navigation))
+        .add_systems(PostUpdate, handle_navigation)
+        .add_plugins(bevy_tokio_tasks::TokioTasksPlugin {
+            make_runtime: Box::new(|| {
+                let mut runtime = tokio::runtime::Builder::new_multi_thread();
+                runtime.enable_all();
+                runtime.build().unwrap()
+            }),
+            ..bevy_tokio_tasks::TokioTasksPlugin::default()
+        })
+        .run();
+}
+
+/// Prints the events a `--headless` guest queues, frame by frame.
+fn run_headless(args: &Args, frames: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args
+        .run
+        .as_ref()
+        .expect("clap requires --run with --headless");
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let script = match &args.input_script {
+        Some(script) => std::fs::read_to_string(script)
+            .map_err(|e| format!("failed to read {}: {e}", script.display()))
+            .and_then(|script| headless::parse_script(&script))?,
+        None => Vec::new(),
+    };
+    let path = path.canonicalize()?;
+    let url = Url::from_file_path(&path)
+        .map_err(|()| format!("{} can't be a file URL", path.display()))?;
+    let recorded = headless::run_headless(
+        &bytes,
+        url.as_str(),
+        frames,
+        &script,
+        &PortalConfig::from(args),
+    )?;
+    for (frame, events) in recorded.iter().enumerate() {
+        println!("frame {frame}");
+        for event in events {
+            println!("  {event:?}");
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum PathCommand {
+    MoveTo((f32, f32)),
+    LineTo((f32, f32)),
+    QuadraticBezierTo(QuadraticBezierTo),
+    CubicBezierTo(CubicBezierTo),
+    Arc(Arc),
+    ArcTo(ArcTo),
+    Ellipse(Ellipse),
+    Begin,
+    Close,
+}
+
+/// A piece of a guest frame with the draw state that was in effect baked into it.
+///
+/// Commands have no identity of their own: a command is identified by its position in the
+/// frame, so a guest that draws the same stream every frame keeps the same entities.
+#[derive(Clone, PartialEq, Debug)]
+enum DrawCommand {
+    Shape(GuestShape),
+    Image {
+        image: Handle<Image>,
+        /// Negative along an axis the image is mirrored in
+        size: Vec2,
+        alpha: f32,
+        blend: BlendMode,
+        transform: Transform,
+        clip: Option<Rect>,
+    },
+    Label {
+        /// The text in runs with the font each is drawn in, see `TextMeasurer::fallback_runs`
+        runs: Vec<(String, Handle<Font>)>,
+        size: f32,
+        color: Color,
+        align: TextAlign,
+        baseline: TextBaseline,
+        max_size: Option<Vec2>,
+        /// Puts the label's anchor on its position, see `label_anchor`
+        transform: Transform,
+        clip: Option<Rect>,
+    },
+    Link {
+        url: String,
+        text: String,
+        size: f32,
+        /// Viewport position of the link's top left corner
+        position: Vec2,
+    },
+    /// A rectangle from `define_link` that navigates when clicked, with nothing to draw
+    LinkArea {
+        url: String,
+        /// World space, already clipped
+        bounds: Rect,
+    },
+    /// A rectangle from `define_region` the guest hears about the cursor entering and leaving
+    Region {
+        id: u32,
+        /// World space, already clipped
+        bounds: Rect,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct GuestShape {
+    geometry: Geometry,
+    fill: Option<Paint>,
+    fill_rule: FillRule,
+    stroke: Option<StrokePaint>,
+    transform: Transform,
+    /// World space rectangle the shape is clipped to
+    clip: Option<Rect>,
+    shadow: Option<Shadow>,
+    blend: BlendMode,
+}
+
+/// A copy of a shape in a solid color drawn just behind it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Shadow {
+    color: Color,
+    /// World space
+    offset: Vec2,
+}
+
+impl Shadow {
+    /// The shadow's offset in the coordinates of a shape with `transform`, `None` if the shape
+    /// is squashed flat and can't have one.
+    fn local_offset(&self, transform: &Transform) -> Option<Vec2> {
+        if transform.scale.x == 0. || transform.scale.y == 0. {
+            return None;
+        }
+        let offset = transform.rotation.inverse() * self.offset.extend(0.);
+        Some(offset.truncate() / transform.scale.truncate())
+    }
+}
+
+impl GuestShape {
+    /// Spawns the entity for the shape's shadow, if it has one, to be added to the shape's
+    /// children. `stroke_path` is the path the shape's stroke is drawn from when it isn't `path`.
+    fn spawn_shadow(
+        &self,
+        commands: &mut Commands,
+        path: &bevy_prototype_lyon::prelude::Path,
+        stroke_path: Option<&bevy_prototype_lyon::prelude::Path>,
+        layers: RenderLayers,
+    ) -> Option<Entity> {
+        let shadow = self.shadow?;
+        let offset = shadow.local_offset(&self.transform)?;
+        // Behind the shape, and in front of whatever was drawn before it
+        let mut entity = commands.spawn((
+            ShapeBundle {
+                path: path.clone(),
+                spatial: SpatialBundle {
+                    transform: Transform::from_translation(offset.extend(-DRAW_ORDER_STEP / 2.)),
+                    ..default()
+                },
+                ..default()
+            },
+            layers,
+        ));
+        // Gradients cast a shadow in the shadow color too
+        if self.fill.is_some() {
+            entity.insert(self.lyon_fill(shadow.color));
+        }
+        let id = entity.id();
+        self.insert_blend(commands, id);
+        if let Some(stroke) = &self.stroke {
+            let stroke = StrokePaint {
+                color: shadow.color,
+                ..stroke.clone()
+            };
+            match stroke_path.filter(|_| stroke.dash.is_some()) {
+                Some(stroke_path) => {
+                    let child = commands
+                        .spawn((
+                            ShapeBundle {
+                                path: stroke_path.clone(),
+                                ..default()
+                            },
+                            stroke.stroke(),
+                            layers,
+                        ))
+                        .id();
+                    self.insert_blend(commands, child);
+                    commands.entity(id).add_child(child);
+                }
+                None => {
+                    commands.entity(id).insert(stroke.stroke());
+                }
+            }
+        }
+        Some(id)
+    }
+
+    /// Marks one of the shape's entities to be drawn with the shape's blend mode, unless that's
+    /// the usual one.
+    fn insert_blend(&self, commands: &mut Commands, entity: Entity) {
+        if self.blend != BlendMode::SrcOver {
+            commands.entity(entity).insert(Blend(self.blend));
+        }
+    }
+
+    fn lyon_fill(&self, color: Color) -> Fill {
+        let rule = match self.fill_rule {
+            FillRule::NonZero => bevy_prototype_lyon::prelude::FillRule::NonZero,
+            FillRule::EvenOdd => bevy_prototype_lyon::prelude::FillRule::EvenOdd,
+        };
+        Fill {
+            color,
+            options: FillOptions::default().with_fill_rule(rule),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Geometry {
+    /// A rectangle of this size centered on the shape's origin
+    Rect(Vec2),
+    /// Like `Rect`, with the corner radii of a `RoundRect`
+    RoundRect(Vec2, [f32; 4]),
+    /// A closed polygon through these points, laid out like a path
+    Polygon(Vec<Vec2>),
+    /// A circle of this radius centered on the shape's origin
+    Circle(f32),
+    /// The commands that followed `begin_path`
+    Path(Vec<PathCommand>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Paint {
+    Color(Color),
+    /// In the coordinates of the shape's mesh
+    Gradient(Gradient),
+    /// In the coordinates of the shape's mesh, like gradients
+    Pattern(Pattern),
+}
+
+impl Paint {
+    fn with_alpha(self, alpha: f32) -> Self {
+        match self {
+            Paint::Color(color) => Paint::Color(with_alpha(color, alpha)),
+            Paint::Gradient(gradient) => Paint::Gradient(gradient.with_alpha(alpha)),
+            Paint::Pattern(pattern) => Paint::Pattern(pattern.with_alpha(alpha)),
+        }
+    }
+
+    fn translated(self, offset: Vec2) -> Self {
+        match self {
+            Paint::Color(color) => Paint::Color(color),
+            Paint::Gradient(gradient) => Paint::Gradient(gradient.translated(offset)),
+            Paint::Pattern(pattern) => Paint::Pattern(pattern.translated(offset)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct StrokePaint {
+    color: Color,
+    line_width: f32,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+    /// `None` for solid lines
+    dash: Option<LineDash>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct LineDash {
+    /// Alternating dash and gap lengths, an even number of them adding up to more than zero
+    segments: Vec<f32>,
+    /// How far into the pattern each subpath starts
+    offset: f32,
+}
+
+impl StrokePaint {
+    fn stroke(&self) -> Stroke {
+        use bevy_prototype_lyon::prelude::{LineCap as Cap, LineJoin as Join};
+        let cap = match self.cap {
+            LineCap::Butt => Cap::Butt,
+            LineCap::Round => Cap::Round,
+            LineCap::Square => Cap::Square,
+        };
+        let join = match self.join {
+            LineJoin::Miter => Join::Miter,
+            LineJoin::Round => Join::Round,
+            LineJoin::Bevel => Join::Bevel,
+        };
+        Stroke {
+            color: self.color,
+            options: StrokeOptions::default()
+                .with_line_width(self.line_width)
+                .with_line_cap(cap)
+                .with_line_join(join)
+                .with_miter_limit(self.miter_limit),
+        }
+    }
+}
+
+impl DrawCommand {
+    /// Spawns the command's entities at depth `z`, shown on `layers` unless it's a link.
+    fn spawn(&self, commands: &mut Commands, layers: RenderLayers, z: f32) -> Entity {
+        let at_depth = |transform: &Transform| {
+            transform.with_translation(transform.translation.truncate().extend(z))
+        };
+        match self {
+            DrawCommand::Shape(shape) => {
+                let path = match &shape.geometry {
+                    Geometry::Rect(extents) => GeometryBuilder::build_as(&Rectangle {
+                        extents: *extents,
+                        origin: RectangleOrigin::Center,
+                    }),
+                    Geometry::RoundRect(extents, radii) => round_rect_path(*extents, *radii),
+                    Geometry::Polygon(points) => GeometryBuilder::build_as(&Polygon {
+                        points: points.clone(),
+                        closed: true,
+                    }),
+                    Geometry::Circle(radius) => GeometryBuilder::build_as(&Circle {
+                        radius: *radius,
+                        center: Vec2::ZERO,
+                    }),
+                    Geometry::Path(path) => build_path(path),
+                };
+                // The gradient and pattern materials would paint a stroke in the same mesh with
+                // the fill too, so those shapes have their stroke drawn by a child, as does a
+                // dashed stroke, which strokes a path of its own
+                let stroke_path = match &shape.stroke {
+                    Some(StrokePaint {
+                        dash: Some(dash), ..
+                    }) => Some(dashed_path(&path, dash)),
+                    Some(_)
+                        if matches!(shape.fill, Some(Paint::Gradient(_) | Paint::Pattern(_))) =>
+                    {
+                        Some(path.clone())
+                    }
+                    _ => None,
+                };
+                let shadow = shape.spawn_shadow(commands, &path, stroke_path.as_ref(), layers);
+                let mut entity = commands.spawn((
+                    ShapeBundle {
+                        path,
+                        spatial: SpatialBundle {
+                            transform: at_depth(&shape.transform),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    layers,
+                    GuestEntity,
+                ));
+                match &shape.fill {
+                    Some(Paint::Color(color)) => {
+                        entity.insert(shape.lyon_fill(*color));
+                    }
+                    Some(Paint::Gradient(gradient)) => {
+                        // Tessellated white for `GradientMaterial` to paint over
+                        entity
+                            .insert((
+                                shape.lyon_fill(Color::WHITE),
+                                GradientFill(gradient.clone()),
+                            ))
+                            .remove::<Handle<ColorMaterial>>();
+                    }
+                    Some(Paint::Pattern(pattern)) => {
+                        // Tessellated white for `PatternMaterial` to paint over
+                        entity
+                            .insert((shape.lyon_fill(Color::WHITE), PatternFill(pattern.clone())))
+                            .remove::<Handle<ColorMaterial>>();
+                    }
+                    None => {}
+                }
+                let id = entity.id();
+                shape.insert_blend(commands, id);
+                if let Some(shadow) = shadow {
+                    commands.entity(id).add_child(shadow);
+                }
+                if let Some(stroke) = &shape.stroke {
+                    match stroke_path {
+                        Some(path) => {
+                            let child = commands
+                                .spawn((
+                                    ShapeBundle {
+                                        path,
+                                        spatial: SpatialBundle {
+                                            transform: Transform::from_xyz(
+                                                0.,
+                                                0.,
+                                                DRAW_ORDER_STEP / 2.,
+                                            ),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                    stroke.stroke(),
+                                    layers,
+                                ))
+                                .id();
+                            shape.insert_blend(commands, child);
+                            commands.entity(id).add_child(child);
+                        }
+                        None => {
+                            commands.entity(id).insert(stroke.stroke());
+                        }
+                    }
+                }
+                id
+            }
+            DrawCommand::Image {
+                image,
+                size,
+                alpha,
+                blend,
+                transform,
+                ..
+            } => {
+                let mut entity = commands.spawn((
+                    SpriteBundle {
+                        texture: image.clone(),
+                        sprite: Sprite {
+                            custom_size: Some(size.abs()),
+                            flip_x: size.x < 0.,
+                            flip_y: size.y < 0.,
+                            color: Color::WHITE.with_a(*alpha),
+                            ..default()
+                        },
+                        transform: at_depth(transform),
+                        ..default()
+                    },
+                    layers,
+                    GuestEntity,
+                ));
+                if *blend != BlendMode::SrcOver {
+                    entity.insert(Blend(*blend));
+                }
+                entity.id()
+            }
+            DrawCommand::Label {
+                runs,
+                size,
+                color,
+                align,
+                baseline,
+                max_size,
+                transform,
+                ..
+            } => commands
+                .spawn((
+                    Text2dBundle {
+                        text: Text {
+                            sections: runs
+                                .iter()
+                                .map(|(text, font)| {
+                                    TextSection::new(
+                                        text.clone(),
+                                        TextStyle {
+                                            font: font.clone(),
+                                            font_size: *size,
+                                            color: *color,
+                                        },
+                                    )
+                                })
+                                .collect(),
+                            alignment: match align {
+                                TextAlign::Left => TextAlignment::Left,
+                                TextAlign::Center => TextAlignment::Center,
+                                TextAlign::Right => TextAlignment::Right,
+                            },
+                            ..default()
+                        },
+                        text_anchor: label_anchor(*align, *baseline),
+                        text_2d_bounds: match max_size {
+                            Some(size) => Text2dBounds { size: *size },
+                            None => Text2dBounds::UNBOUNDED,
+                        },
+                        transform: at_depth(transform),
+                        ..default()
+                    },
+                    layers,
+                    GuestEntity,
+                ))
+                .id(),
+            DrawCommand::Link {
+                url,
+                text,
+                size,
+                position,
+            } => {
+                let button = commands
+                    .spawn((
+                        ButtonBundle {
+                            focus_policy: bevy::ui::FocusPolicy::Pass,
+                            background_color: Color::NONE.into(),
+                            style: Style {
+                                position_type: bevy::ui::PositionType::Absolute,
+                                left: bevy::prelude::Val::Px(position.x),
+                                top: bevy::prelude::Val::Px(position.y),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        GuestUrl(url.clone()),
+                        GuestEntity,
+                    ))
+                    .id();
+                let text = commands
+                    .spawn((TextBundle {
+                        text: Text {
+                            sections: vec![TextSection::new(
+                                text.clone(),
+                                TextStyle {
+                                    font_size: *size,
+                                    color: Color::BLUE,
+                                    ..default()
+                                },
+                            )],
+                            ..default()
+                        },
+                        ..default()
+                    },))
+                    .id();
+                commands.entity(button).add_child(text);
+                button
+            }
+            DrawCommand::LinkArea { .. } | DrawCommand::Region { .. } => {
+                commands.spawn(GuestEntity).id()
+            }
+        }
+    }
+
+    /// The world space rectangle the command is clipped to. Links are never clipped, and link
+    /// areas and regions are clipped when they're defined.
+    fn clip(&self) -> Option<Rect> {
+        match self {
+            DrawCommand::Shape(shape) => shape.clip,
+            DrawCommand::Image { clip, .. } | DrawCommand::Label { clip, .. } => *clip,
+            DrawCommand::Link { .. }
+            | DrawCommand::LinkArea { .. }
+            | DrawCommand::Region { .. } => None,
+        }
+    }
+
+    /// World space bounds used by `clear_rect`, or `None` for things it can't clear.
+    fn bounds(&self) -> Option<Rect> {
+        match self {
+            DrawCommand::Shape(shape) => {
+                let bounds = match &shape.geometry {
+                    Geometry::Rect(extents) | Geometry::RoundRect(extents, _) => {
+                        Rect::from_center_size(Vec2::ZERO, *extents)
+                    }
+                    Geometry::Circle(radius) => {
+                        Rect::from_center_size(Vec2::ZERO, Vec2::splat(radius * 2.))
+                    }
+                    Geometry::Path(path) => path_bounds(&build_path(path)),
+                    Geometry::Polygon(points) => points.iter().fold(
+                        Rect {
+                            min: Vec2::INFINITY,
+                            max: Vec2::NEG_INFINITY,
+                        },
+                        |bounds, p| bounds.union_point(*p),
+                    ),
+                };
+                Some(transformed_bounds(bounds, &shape.transform))
+            }
+            DrawCommand::Image {
+                size, transform, ..
+            } => Some(transformed_bounds(
+                Rect::from_center_size(Vec2::ZERO, size.abs()),
+                transform,
+            )),
+            // Text extents aren't known until layout, so a label is cleared by its anchor.
+            DrawCommand::Label { transform, .. } => Some(Rect::from_center_size(
+                transform.translation.truncate(),
+                Vec2::ZERO,
+            )),
+            DrawCommand::Link { .. } => None,
+            DrawCommand::LinkArea { bounds, .. } | DrawCommand::Region { bounds, .. } => {
+                Some(*bounds)
+            }
+        }
+    }
+}
+
+/// How far below the alphabetic baseline the bottom of a line of text is, as a fraction of the
+/// font size. Taken from the default font's descender, so it's approximate for other fonts.
+const TEXT_DESCENT: f32 = 0.265;
+
+/// The point of a label's text that goes on its position. An alphabetic baseline anchors the
+/// bottom of the text, and `resolve_draw_commands` moves the anchor down by `TEXT_DESCENT`.
+fn label_anchor(align: TextAlign, baseline: TextBaseline) -> Anchor {
+    match (baseline, align) {
+        (TextBaseline::Top, TextAlign::Left) => Anchor::TopLeft,
+        (TextBaseline::Top, TextAlign::Center) => Anchor::TopCenter,
+        (TextBaseline::Top, TextAlign::Right) => Anchor::TopRight,
+        (TextBaseline::Middle, TextAlign::Left) => Anchor::CenterLeft,
+        (TextBaseline::Middle, TextAlign::Center) => Anchor::Center,
+        (TextBaseline::Middle, TextAlign::Right) => Anchor::CenterRight,
+        (TextBaseline::Bottom | TextBaseline::Alphabetic, TextAlign::Left) => Anchor::BottomLeft,
+        (TextBaseline::Bottom | TextBaseline::Alphabetic, TextAlign::Center) => {
+            Anchor::BottomCenter
+        }
+        (TextBaseline::Bottom | TextBaseline::Alphabetic, TextAlign::Right) => Anchor::BottomRight,
+    }
+}
+
+#[derive(bevy::prelude::Component)]
+struct GuestEntity;
+
+#[derive(bevy::prelude::Component)]
+struct GuestUrl(String);
+
+/// Guest entities kept alive across frames, in the order their commands were drawn.
+#[derive(Resource, Default)]
+struct GuestScene {
+    items: Vec<(DrawCommand, RenderLayers, Entity)>,
+    /// The clip of the run of drawing on render layer `i + 1` for each `i`, `None` for unclipped
+    /// drawing after a clipped run, see `assign_run_layers`
+    run_clips: Vec<Option<Rect>>,
+    /// What's drawn on each of the guest's layers, `None` for layers nothing was drawn on yet
+    offscreen: Vec<Option<OffscreenLayer>>,
+    /// The `define_region` rectangles on the screen, by id
+    regions: HitGrid<u32>,
+    /// The `define_link` rectangles on the screen, by URL
+    link_areas: HitGrid<String>,
+}
+
+/// A guest's offscreen layer from `create_layer`.
+struct GuestLayer {
+    /// The layer's image handle
+    image: u32,
+    size: Vec2,
+}
+
+/// Drawing a layer holds, rendered into its image by a `LayerCamera`.
+struct OffscreenLayer {
+    image: Handle<Image>,
+    /// The world space rectangle the drawing is in, where it would be if it were on the screen
+    bounds: Rect,
+    items: Vec<(DrawCommand, RenderLayers, Entity)>,
+}
+
+/// What a frame drew on the screen and on each layer it drew into, see `resolve_draw_commands`.
+struct ResolvedFrame {
+    screen: Vec<DrawCommand>,
+    /// By layer index
+    layers: Vec<(usize, OffscreenFrame)>,
+}
+
+struct OffscreenFrame {
+    image: Handle<Image>,
+    bounds: Rect,
+    commands: Vec<DrawCommand>,
+}
+
+/// How much closer to the camera each command in a frame is than the one before, so that later
+/// draws show on top. The 2D camera sees depths up to 1000, which is room for a million commands.
+const DRAW_ORDER_STEP: f32 = 0.001;
+
+/// The depth of the `i`th command of a frame.
+fn draw_depth(i: usize) -> f32 {
+    i as f32 * DRAW_ORDER_STEP
+}
+
+/// Past this many layers `create_layer` fails, each one takes a render layer.
+const MAX_LAYERS: usize = 8;
+/// Layers are at most this many pixels wide and high.
+const MAX_LAYER_SIZE: u32 = 4096;
+
+/// Render layer 0 is the main camera's and the last `MAX_LAYERS` are the guest's layers', which
+/// leaves the rest for the runs of drawing that start wherever the clip changes.
+const MAX_CLIPS: usize = RenderLayers::TOTAL_LAYERS - 1 - MAX_LAYERS;
+
+/// The render layer the drawing on the guest's `i`th layer is on.
+fn offscreen_render_layer(i: usize) -> RenderLayers {
+    RenderLayers::layer((RenderLayers::TOTAL_LAYERS - MAX_LAYERS + i) as u8)
+}
+
+impl GuestScene {
+    /// Brings the scene in line with `frame`, respawning only the commands that changed. Layers the
+    /// frame didn't draw into keep what they had.
+    fn reconcile(&mut self, commands: &mut Commands, frame: ResolvedFrame) {
+        let screen_layers = self.assign_run_layers(&frame.screen);
+        let mut regions = Vec::new();
+        let mut link_areas = Vec::new();
+        for command in &frame.screen {
+            match command {
+                DrawCommand::Region { id, bounds } => regions.push((*bounds, *id)),
+                DrawCommand::LinkArea { url, bounds } => link_areas.push((*bounds, url.clone())),
+                _ => {}
+            }
+        }
+        self.regions.set(regions);
+        self.link_areas.set(link_areas);
+        reconcile_items(&mut self.items, commands, frame.screen, |i| {
+            screen_layers[i]
+        });
+        for (i, layer) in frame.layers {
+            if self.offscreen.len() <= i {
+                self.offscreen.resize_with(i + 1, || None);
+            }
+            let offscreen = self.offscreen[i].get_or_insert_with(|| OffscreenLayer {
+                image: layer.image.clone(),
+                bounds: layer.bounds,
+                items: Vec::new(),
+            });
+            offscreen.bounds = layer.bounds;
+            // Links are UI and regions are for the cursor over the screen, neither makes sense
+            // in an image
+            let drawn = layer
+                .commands
+                .into_iter()
+                .filter(|command| {
+                    !matches!(
+                        command,
+                        DrawCommand::Link { .. }
+                            | DrawCommand::LinkArea { .. }
+                            | DrawCommand::Region { .. }
+                    )
+                })
+                .collect();
+            reconcile_items(&mut offscreen.items, commands, drawn, |_| {
+                offscreen_render_layer(i)
+            });
+        }
+    }
+
+    /// Every entity in the scene, on the screen and on layers.
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        let offscreen = self.offscreen.iter().flatten().flat_map(|l| &l.items);
+        self.items
+            .iter()
+            .chain(offscreen)
+            .map(|(_, _, entity)| *entity)
+    }
+
+    /// The hit region from `define_region` at the world space `point`, the last defined if
+    /// several are there.
+    fn region_at(&self, point: Vec2) -> Option<u32> {
+        self.regions.find(point).copied()
+    }
+
+    /// The URL of the link area from `define_link` at the world space `point`, the last defined
+    /// if several are there.
+    fn link_area_at(&self, point: Vec2) -> Option<&String> {
+        self.link_areas.find(point)
+    }
+
+    /// Splits `frame` into runs of drawing with the same clip, giving each run after the first
+    /// its own render layer, and returns the render layer of each command. A run's camera draws
+    /// after the one before's, so drawing stays in order whichever clips it's in.
+    fn assign_run_layers(&mut self, frame: &[DrawCommand]) -> Vec<RenderLayers> {
+        self.run_clips.clear();
+        let mut clip = None;
+        let mut overflowed = false;
+        frame
+            .iter()
+            .map(|command| {
+                // Links, link areas and regions aren't drawn by cameras, so they don't end a run
+                let drawn = !matches!(
+                    command,
+                    DrawCommand::Link { .. }
+                        | DrawCommand::LinkArea { .. }
+                        | DrawCommand::Region { .. }
+                );
+                if overflowed {
+                    return RenderLayers::default();
+                }
+                if drawn && command.clip() != clip {
+                    if self.run_clips.len() == MAX_CLIPS {
+                        eprintln!(
+                            "the clip changed more than {MAX_CLIPS} times in a frame, drawing \
+                             the rest unclipped and underneath"
+                        );
+                        overflowed = true;
+                        return RenderLayers::default();
+                    }
+                    clip = command.clip();
+                    self.run_clips.push(clip);
+                }
+                RenderLayers::layer(self.run_clips.len() as u8)
+            })
+            .collect()
+    }
+}
+
+/// Brings `items` in line with `frame`, spawning the `i`th command on the render layers `layers`
+/// gives for `i`.
+///
+/// Commands are matched by position, so unchanged frames cause no entity churn while a command
+/// inserted early in the stream respawns everything after it. So does one that moved to another
+/// render layer.
+fn reconcile_items(
+    items: &mut Vec<(DrawCommand, RenderLayers, Entity)>,
+    commands: &mut Commands,
+    frame: Vec<DrawCommand>,
+    layers: impl Fn(usize) -> RenderLayers,
+) {
+    let len = frame.len();
+    for (i, command) in frame.into_iter().enumerate() {
+        let layer = layers(i);
+        match items.get_mut(i) {
+            Some((kept, kept_layer, _)) if *kept == command && *kept_layer == layer => {}
+            Some((kept, kept_layer, entity)) => {
+                commands.entity(*entity).despawn_recursive();
+                *entity = command.spawn(commands, layer, draw_depth(i));
+                *kept = command;
+                *kept_layer = layer;
+            }
+            None => {
+                let entity = command.spawn(commands, layer, draw_depth(i));
+                items.push((command, layer, entity));
+            }
+        }
+    }
+    if items.len() > len {
+        for (_, _, entity) in items.drain(len..) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Shows the render layer it holds, through a viewport over that layer's clip, or over the
+/// whole canvas for an unclipped run.
+#[derive(bevy::prelude::Component)]
+struct ClipCamera(u8);
+
+/// Keeps a camera for each run of drawing after the clip first changes. A run's things are only
+/// on its render layer, so they're only drawn by its camera, whose viewport is the run's clip.
+///
+/// Each camera's order is its render layer, so runs draw after the main camera and the runs
+/// before them, in the order the guest drew them.
+fn update_clip_cameras(
+    mut commands: Commands,
+    scene: Res<GuestScene>,
+    main_camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut clip_camera_q: Query<
+        (Entity, &ClipCamera, &mut Camera, &mut Transform),
+        Without<MainCamera>,
+    >,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let (Ok((main_camera, main_transform)), Ok(window), Ok((node_transform, node))) = (
+        main_camera_q.get_single(),
+        windows.get_single(),
+        canvas_q.get_single(),
+    ) else {
+        return;
+    };
+    // Guests only draw on the canvas, so clips never show past it and over the browser's UI
+    let canvas = Rect::from_center_size(node_transform.translation().truncate(), node.size());
+    let viewport = |clip: Option<Rect>| {
+        let visible = match clip {
+            Some(clip) => {
+                let top_left = main_camera.world_to_viewport(
+                    main_transform,
+                    Vec2::new(clip.min.x, clip.max.y).extend(0.),
+                )?;
+                let bottom_right = main_camera.world_to_viewport(
+                    main_transform,
+                    Vec2::new(clip.max.x, clip.min.y).extend(0.),
+                )?;
+                Rect::from_corners(top_left, bottom_right).intersect(canvas)
+            }
+            None => canvas,
+        };
+        if visible.is_empty() {
+            return None;
+        }
+        let scale = window.scale_factor() as f32;
+        let position = (visible.min * scale).round().as_uvec2();
+        let size = (visible.max * scale).round().as_uvec2() - position;
+        if size.x == 0 || size.y == 0 {
+            return None;
+        }
+        let center = main_camera.viewport_to_world_2d(main_transform, visible.center())?;
+        let viewport = Viewport {
+            physical_position: position,
+            physical_size: size,
+            ..default()
+        };
+        Some((viewport, center))
+    };
+    let mut has_camera = vec![false; scene.run_clips.len()];
+    for (entity, ClipCamera(layer), mut camera, mut transform) in clip_camera_q.iter_mut() {
+        let clip = scene.run_clips.get(*layer as usize - 1).copied();
+        match clip.and_then(&viewport) {
+            Some((viewport, center)) => {
+                camera.viewport = Some(viewport);
+                camera.is_active = true;
+                transform.translation = center.extend(transform.translation.z);
+            }
+            // An empty clip hides everything clipped to it
+            None if clip.is_some() => camera.is_active = false,
+            None => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        }
+        has_camera[*layer as usize - 1] = true;
+    }
+    for (i, clip) in scene.run_clips.iter().enumerate() {
+        if has_camera[i] {
+            continue;
+        }
+        let Some((viewport, center)) = viewport(*clip) else {
+            continue;
+        };
+        let layer = i as u8 + 1;
+        commands.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    order: layer as isize,
+                    viewport: Some(viewport),
+                    ..default()
+                },
+                camera_2d: Camera2d {
+                    clear_color: ClearColorConfig::None,
+                },
+                transform: Transform::from_translation(
+                    center.extend(main_transform.translation().z),
+                ),
+                ..default()
+            },
+            UiCameraConfig { show_ui: false },
+            RenderLayers::layer(layer),
+            ClipCamera(layer),
+        ));
+    }
+}
+
+/// Renders the drawing on the guest's `i`th layer into the layer's image.
+#[derive(bevy::prelude::Component)]
+struct LayerCamera(usize);
+
+/// Keeps a camera for each layer with something drawn on it, looking at where the drawing is.
+///
+/// Layer cameras draw before the main camera, and before the cameras of layers created after
+/// theirs, so a layer drawn into a layer created after it is up to date.
+fn update_layer_cameras(
+    mut commands: Commands,
+    scene: Res<GuestScene>,
+    mut layer_camera_q: Query<(
+        Entity,
+        &LayerCamera,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+) {
+    let bounds_scaling = |bounds: Rect| ScalingMode::Fixed {
+        width: bounds.width(),
+        height: bounds.height(),
+    };
+    let mut has_camera = vec![false; scene.offscreen.len()];
+    for (entity, LayerCamera(i), mut transform, mut projection) in layer_camera_q.iter_mut() {
+        let Some(Some(layer)) = scene.offscreen.get(*i) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        transform.translation = layer.bounds.center().extend(transform.translation.z);
+        projection.scaling_mode = bounds_scaling(layer.bounds);
+        has_camera[*i] = true;
+    }
+    for (i, layer) in scene.offscreen.iter().enumerate() {
+        let Some(layer) = layer.as_ref().filter(|_| !has_camera[i]) else {
+            continue;
+        };
+        let mut camera = Camera2dBundle {
+            camera: Camera {
+                order: i as isize - MAX_LAYERS as isize,
+                target: RenderTarget::Image(layer.image.clone()),
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::NONE),
+            },
+            ..default()
+        };
+        camera.projection.scaling_mode = bounds_scaling(layer.bounds);
+        camera.transform.translation = layer.bounds.center().extend(camera.transform.translation.z);
+        commands.spawn((
+            camera,
+            UiCameraConfig { show_ui: false },
+            offscreen_render_layer(i),
+            LayerCamera(i),
+        ));
+    }
+}
+
+fn handle_guest_event(
+    mut commands: Commands,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    wasm_store: Option<ResMut<WasmStore>>,
+    mut scene: ResMut<GuestScene>,
+    mut images: ResMut<Assets<Image>>,
+    mut font_assets: ResMut<Assets<Font>>,
+    mut fonts: ResMut<GuestFonts>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(mut wasm_store) = wasm_store else {
+        return;
+    };
+    let data = wasm_store.store.data_mut();
+    // Only a finished frame is diffed, otherwise a half drawn one would despawn the rest
+    if !data.frame_ready {
+        return;
+    }
+    data.frame_ready = false;
+    for (handle, image) in data.pending_images.drain(..) {
+        data.images[handle as usize] = Some(images.add(image));
+    }
+    for (handle, font) in data.pending_fonts.drain(..) {
+        data.fonts[handle as usize] = Some(font_assets.add(font));
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let frame = resolve_draw_commands(data, camera, camera_transform, &mut fonts, &asset_server);
+    scene.reconcile(&mut commands, frame);
+}
+
+/// Replays the queued host events against the draw state, producing the frame's draw commands
+/// for the screen and for the layers it drew into.
+fn resolve_draw_commands(
+    data: &mut MyCtx,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    fonts: &mut GuestFonts,
+    asset_server: &AssetServer,
+) -> ResolvedFrame {
+    let mut frame = Vec::new();
+    // The commands for the targets drawing switched away from, by layer index
+    let mut targets: HashMap<Option<usize>, Vec<DrawCommand>> = HashMap::new();
+    let mut target = None;
+    let mut current_path = Vec::new();
+    let mut current_shape = None;
+    let origin = data.canvas_config.transform(data.canvas.size);
+    data.draw_state.origin = origin;
+    for r in data.queue.drain(..) {
+        match r {
+            HostEvent::FillStyle(c_str) => {
+                data.draw_state.fill = Paint::Color(data.colors.parse(c_str));
+            }
+            HostEvent::FillRule(rule) => {
+                data.draw_state.fill_rule = rule;
+            }
+            HostEvent::FillGradient(gradient) => {
+                data.draw_state.fill = Paint::Gradient(gradient);
+            }
+            HostEvent::FillPattern(image, repeat) => {
+                let Some(Some(image)) = data.images.get(image as usize) else {
+                    eprintln!("ignoring pattern of unknown image {image}");
+                    continue;
+                };
+                let flip_y = data.draw_state.upright() > 0.;
+                data.draw_state.fill = Paint::Pattern(Pattern::new(image.clone(), repeat, flip_y));
+            }
+            HostEvent::StrokeStyle(c_str) => {
+                data.draw_state.stroke = data.colors.parse(c_str);
+            }
+            HostEvent::LineWidth(width) => {
+                data.draw_state.line_width = width;
+            }
+            HostEvent::LineCap(cap) => {
+                data.draw_state.line_cap = cap;
+            }
+            HostEvent::LineJoin(join) => {
+                data.draw_state.line_join = join;
+            }
+            HostEvent::MiterLimit(limit) => {
+                data.draw_state.miter_limit = limit;
+            }
+            HostEvent::LineDash(segments) => {
+                data.draw_state.line_dash = segments;
+            }
+            HostEvent::LineDashOffset(offset) => {
+                data.draw_state.line_dash_offset = offset;
+            }
+            HostEvent::TextAlign(align) => {
+                data.draw_state.text_align = align;
+            }
+            HostEvent::TextBaseline(baseline) => {
+                data.draw_state.text_baseline = baseline;
+            }
+            HostEvent::TextDirection(direction) => {
+                data.draw_state.text_direction = direction;
+            }
+            HostEvent::Font(font) => {
+                data.draw_state.font = font;
+            }
+            HostEvent::GlobalAlpha(alpha) => {
+                data.draw_state.global_alpha = alpha.clamp(0., 1.);
+            }
+            HostEvent::BlendMode(mode) => {
+                data.draw_state.blend_mode = mode;
+            }
+            HostEvent::ShadowColor(c_str) => {
+                data.draw_state.shadow_color = data.colors.parse(c_str);
+            }
+            HostEvent::ShadowBlur(blur) => {
+                data.draw_state.shadow_blur = blur;
+            }
+            HostEvent::ShadowOffset(offset) => {
+                data.draw_state.shadow_offset = offset;
+            }
+            HostEvent::Translate((x, y)) => {
+                data.draw_state.transform =
+                    data.draw_state.transform * Affine2::from_translation(Vec2::new(x, y));
+            }
+            HostEvent::Rotate(radians) => {
+                data.draw_state.transform =
+                    data.draw_state.transform * Affine2::from_angle(radians);
+            }
+            HostEvent::Scale((sx, sy)) => {
+                data.draw_state.transform =
+                    data.draw_state.transform * Affine2::from_scale(Vec2::new(sx, sy));
+            }
+            HostEvent::SetTransform(transform) => {
+                // A singular matrix is fine and collapses shapes to nothing, as on a canvas, but
+                // NaN or infinite entries would poison every entity drawn with it.
+                if transform.is_finite() {
+                    data.draw_state.transform = transform;
+                } else {
+                    eprintln!("ignoring non-finite transform {transform:?}");
+                }
+            }
+            HostEvent::ResetTransform => {
+                data.draw_state.transform = Affine2::IDENTITY;
+            }
+            HostEvent::Save => {
+                data.saved_draw_states.push(data.draw_state.clone());
+            }
+            HostEvent::Restore => {
+                // A `restore` without a matching `save` is a no-op
+                if let Some(state) = data.saved_draw_states.pop() {
+                    data.draw_state = DrawState { origin, ..state };
+                }
+            }
+            HostEvent::FillRect(fill_rect) => {
+                let bounds = fill_rect.bounds();
+                // The rectangle's mesh is centered on its origin, rather than laid out in guest
+                // coordinates like a path's
+                let fill = data
+                    .draw_state
+                    .fill()
+                    .translated(-(data.canvas.position + bounds.center()));
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Rect(bounds.size()),
+                    fill: Some(fill),
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: None,
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_translation(bounds.center().extend(0.)),
+                    ),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::FillRoundRect(RoundRect { rect, radii }) => {
+                let bounds = rect.bounds();
+                let limit = bounds.size().min_element() / 2.;
+                let fill = data
+                    .draw_state
+                    .fill()
+                    .translated(-(data.canvas.position + bounds.center()));
+                // The path puts the top corners towards positive y, which is the bottom when y
+                // goes down
+                let [top_left, top_right, bottom_right, bottom_left] = radii;
+                let radii = if data.draw_state.upright() < 0. {
+                    [bottom_left, bottom_right, top_right, top_left]
+                } else {
+                    radii
+                };
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::RoundRect(
+                        bounds.size(),
+                        radii.map(|radius| radius.max(0.).min(limit)),
+                    ),
+                    fill: Some(fill),
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: None,
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_translation(bounds.center().extend(0.)),
+                    ),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::FillPolygon(points) => {
+                // Fewer than three points have nothing inside to fill
+                if points.len() < 3 {
+                    continue;
+                }
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Polygon(points),
+                    fill: Some(data.draw_state.fill()),
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: None,
+                    transform: data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::FillEllipse(ellipse) => {
+                // Drawn on its own rather than added to the current path, like `fill_rect`
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Path(vec![
+                        PathCommand::Ellipse(ellipse),
+                        PathCommand::Close,
+                    ]),
+                    fill: Some(data.draw_state.fill()),
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: None,
+                    transform: data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::FillCircle(center, radius) => {
+                // Centered on its origin like `fill_rect`'s mesh
+                let fill = data
+                    .draw_state
+                    .fill()
+                    .translated(-(data.canvas.position + center));
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Circle(radius),
+                    fill: Some(fill),
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: None,
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_translation(center.extend(0.)),
+                    ),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::StrokeCircle(center, radius) => {
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Circle(radius),
+                    fill: None,
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: Some(data.draw_state.stroke()),
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_translation(center.extend(0.)),
+                    ),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::Line(from, to) => {
+                // Drawn on its own rather than added to the current path, like `fill_rect`
+                frame.push(DrawCommand::Shape(GuestShape {
+                    geometry: Geometry::Path(vec![
+                        PathCommand::MoveTo(from.into()),
+                        PathCommand::LineTo(to.into()),
+                    ]),
+                    fill: None,
+                    fill_rule: data.draw_state.fill_rule,
+                    stroke: Some(data.draw_state.stroke()),
+                    transform: data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                    clip: data.draw_state.clip,
+                    shadow: data.draw_state.shadow(),
+                    blend: data.draw_state.blend_mode,
+                }));
+            }
+            HostEvent::DefineLink(rect, url) => {
+                let bounds = transformed_bounds(
+                    rect.bounds(),
+                    &data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                );
+                let bounds = match data.draw_state.clip {
+                    Some(clip) => bounds.intersect(clip),
+                    None => bounds,
+                };
+                if !bounds.is_empty() {
+                    frame.push(DrawCommand::LinkArea { url, bounds });
+                }
+            }
+            HostEvent::DefineRegion(id, rect) => {
+                let bounds = transformed_bounds(
+                    rect.bounds(),
+                    &data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                );
+                let bounds = match data.draw_state.clip {
+                    Some(clip) => bounds.intersect(clip),
+                    None => bounds,
+                };
+                if !bounds.is_empty() {
+                    frame.push(DrawCommand::Region { id, bounds });
+                }
+            }
+            HostEvent::ClearRect(clear_rect) => {
+                // Guest shapes are entities rather than pixels, so clearing removes every shape
+                // drawn so far in this frame whose bounding box lies entirely inside the
+                // rectangle. Shapes that only overlap it are left untouched, and nothing is
+                // painted in their place: where no shape is left, the `--background` clear color
+                // shows through, but painting it over the rectangle would also hide what's drawn
+                // into layers, which start out transparent.
+                let area = transformed_bounds(
+                    clear_rect.bounds(),
+                    &data
+                        .draw_state
+                        .entity_transform(data.canvas.position, Transform::IDENTITY),
+                );
+                frame.retain(|command| {
+                    !command.bounds().is_some_and(|bounds| {
+                        area.contains(bounds.min) && area.contains(bounds.max)
+                    })
+                });
+                current_shape = None;
+            }
+            HostEvent::BeginPath => {
+                current_path.clear();
+                current_path.push(PathCommand::Begin);
+                current_shape = None;
+            }
+            HostEvent::Arc(arc) => {
+                current_path.push(PathCommand::Arc(arc));
+                current_shape = None;
+            }
+            HostEvent::ArcTo(arc_to) => {
+                current_path.push(PathCommand::ArcTo(arc_to));
+                current_shape = None;
+            }
+            HostEvent::Clip => {
+                let Some((PathCommand::Begin, path)) = current_path.split_first() else {
+                    eprintln!("path should start from begin");
+                    continue;
+                };
+                let transform = data
+                    .draw_state
+                    .entity_transform(data.canvas.position, Transform::IDENTITY);
+                let bounds = transformed_bounds(path_bounds(&build_path(path)), &transform);
+                data.draw_state.clip = Some(match data.draw_state.clip {
+                    Some(clip) => clip.intersect(bounds),
+                    None => bounds,
+                });
+                // The path's shape, if it was already filled, isn't clipped
+                current_shape = None;
+            }
+            HostEvent::ClosePath => {
+                current_path.push(PathCommand::Close);
+                current_shape = None;
+            }
+            HostEvent::Fill => {
+                let transform = data
+                    .draw_state
+                    .entity_transform(data.canvas.position, Transform::IDENTITY);
+                if let Some(shape) = path_shape(
+                    &mut frame,
+                    &mut current_shape,
+                    &current_path,
+                    transform,
+                    data.draw_state.clip,
+                ) {
+                    shape.fill = Some(data.draw_state.fill());
+                    shape.fill_rule = data.draw_state.fill_rule;
+                    shape.shadow = data.draw_state.shadow();
+                    shape.blend = data.draw_state.blend_mode;
+                }
+            }
+            HostEvent::Stroke => {
+                let transform = data
+                    .draw_state
+                    .entity_transform(data.canvas.position, Transform::IDENTITY);
+                if let Some(shape) = path_shape(
+                    &mut frame,
+                    &mut current_shape,
+                    &current_path,
+                    transform,
+                    data.draw_state.clip,
+                ) {
+                    shape.stroke = Some(data.draw_state.stroke());
+                    shape.shadow = data.draw_state.shadow();
+                    shape.blend = data.draw_state.blend_mode;
+                }
+            }
+            HostEvent::MoveTo((x, y)) => {
+                current_path.push(PathCommand::MoveTo((x, y)));
+                current_shape = None;
+            }
+            HostEvent::LineTo((x, y)) => {
+                current_path.push(PathCommand::LineTo((x, y)));
+                current_shape = None;
+            }
+            HostEvent::QuadraticBezierTo(qbt) => {
+                current_path.push(PathCommand::QuadraticBezierTo(qbt));
+                current_shape = None;
+            }
+            HostEvent::CubicBezierTo(cbt) => {
+                current_path.push(PathCommand::CubicBezierTo(cbt));
+                current_shape = None;
+            }
+            HostEvent::Target(layer) => {
+                if layer != target {
+                    targets.insert(target, std::mem::take(&mut frame));
+                    frame = targets.remove(&layer).unwrap_or_default();
+                    target = layer;
+                    current_shape = None;
+                }
+            }
+            HostEvent::DrawImage(DrawImage { image, rect }) => {
+                if target.is_some_and(|i| data.layers[i].image == image) {
+                    eprintln!("ignoring layer {image} being drawn into itself");
+                    continue;
+                }
+                let Some(Some(image)) = data.images.get(image as usize) else {
+                    eprintln!("ignoring unknown image {image}");
+                    continue;
+                };
+                let bounds = rect.bounds();
+                frame.push(DrawCommand::Image {
+                    image: image.clone(),
+                    size: Vec2::new(rect.width, rect.height * data.draw_state.upright()),
+                    alpha: data.draw_state.global_alpha,
+                    blend: data.draw_state.blend_mode,
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_translation(bounds.center().extend(0.)),
+                    ),
+                    clip: data.draw_state.clip,
+                });
+            }
+            HostEvent::Label(Label {
+                text,
+                x,
+                y,
+                size,
+                color,
+                max_size,
+            }) => {
+                let upright = data.draw_state.upright();
+                let y = match data.draw_state.text_baseline {
+                    TextBaseline::Alphabetic => y - size * TEXT_DESCENT * upright,
+                    _ => y,
+                };
+                let text = bidi::visual_order(&text, data.draw_state.text_direction);
+                let font = match data.draw_state.font.guest {
+                    Some(font) => data.fonts[font as usize].clone().unwrap_or_default(),
+                    None => fonts.get(&data.draw_state.font, asset_server),
+                };
+                let runs = match fonts.emoji(asset_server) {
+                    Some(emoji) => data
+                        .text_measurer
+                        .fallback_runs(&data.draw_state.font, &text)
+                        .into_iter()
+                        .map(|(range, fallback)| {
+                            let font = if fallback { &emoji } else { &font };
+                            (text[range].to_string(), font.clone())
+                        })
+                        .collect(),
+                    None => vec![(text, font)],
+                };
+                frame.push(DrawCommand::Label {
+                    runs,
+                    size,
+                    color: with_alpha(data.colors.parse(color), data.draw_state.global_alpha),
+                    align: data.draw_state.text_align,
+                    baseline: data.draw_state.text_baseline,
+                    max_size,
+                    transform: data.draw_state.entity_transform(
+                        data.canvas.position,
+                        Transform::from_xyz(x, y, 0.).with_scale(Vec3::new(1., upright, 1.)),
+                    ),
+                    clip: data.draw_state.clip,
+                });
+            }
+            HostEvent::Link(Link {
+                url,
+                text,
+                x,
+                y,
+                size,
+            }) => {
+                let transform = data
+                    .draw_state
+                    .entity_transform(data.canvas.position, Transform::from_xyz(x, y, 0.));
+                if let Some(position) =
+                    camera.world_to_viewport(camera_transform, transform.translation)
+                {
+                    frame.push(DrawCommand::Link {
+                        url,
+                        text,
+                        size,
+                        position,
+                    });
+                }
+            }
+        }
+    }
+    targets.insert(target, frame);
+    let screen = targets.remove(&None).unwrap_or_default();
+    let to_world = |point: Vec2| origin.transform_point2(point) - data.canvas.position;
+    let layers = targets
+        .into_iter()
+        .filter_map(|(layer, commands)| {
+            let i = layer?;
+            let GuestLayer { image, size } = &data.layers[i];
+            let frame = OffscreenFrame {
+                image: data.images[*image as usize].clone()?,
+                bounds: Rect::from_corners(to_world(Vec2::ZERO), to_world(*size)),
+                commands,
+            };
+            Some((i, frame))
+        })
+        .collect();
+    ResolvedFrame { screen, layers }
+}
+
+fn stop_guest_sounds(world: &mut World) {
+    let sounds: Vec<Entity> = world
+        .query_filtered::<Entity, With<GuestSound>>()
+        .iter(world)
+        .collect();
+    for entity in sounds {
+        world.entity_mut(entity).despawn_recursive();
+    }
+}
+
+/// Plays the sounds and music the guest started, changes and stops the ones it asked to, and
+/// silences a guest once it's unloaded or navigated away from.
+fn handle_guest_audio(
+    mut commands: Commands,
+    wasm_store: Option<ResMut<WasmStore>>,
+    mut sources: ResMut<Assets<bevy::audio::AudioSource>>,
+    global_volume: Res<bevy::audio::GlobalVolume>,
+    mut sounds_q: GuestSoundQuery,
+) {
+    let Some(mut wasm_store) = wasm_store else {
+        for (entity, ..) in sounds_q.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    wasm_store.store.data_mut().audio.apply(
+        &mut commands,
+        &mut sources,
+        &global_volume,
+        &mut sounds_q,
+    );
+}
+
+/// How long a notice shows after the shortcut that changed something
+const NOTICE_SECONDS: f32 = 1.5;
+
+type NoticeQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Text,
+        &'static mut Style,
+        &'static mut NoticeText,
+    ),
+>;
+
+/// Shows `message` in the notice at the bottom of the window for a moment.
+fn show_notice(notice_q: &mut NoticeQuery, message: &str, now: f32) {
+    for (mut text, mut style, mut notice) in notice_q.iter_mut() {
+        text.sections[0].value = message.to_string();
+        style.display = Display::Flex;
+        notice.hide_at = now + NOTICE_SECONDS;
+    }
+}
+
+/// Hides the notice once it's shown for long enough.
+fn hide_notice(time: Res<Time>, mut notice_q: NoticeQuery) {
+    let now = time.elapsed_seconds();
+    for (_, mut style, notice) in notice_q.iter_mut() {
+        if style.display != Display::None && now >= notice.hide_at {
+            style.display = Display::None;
+        }
+    }
+}
+
+/// Mutes and unmutes guest sounds with Cmd/Ctrl+M, saying which for a moment.
+fn handle_mute(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut master_volume: ResMut<MasterVolume>,
+    mut global_volume: ResMut<bevy::audio::GlobalVolume>,
+    sounds_q: GuestSoundQuery,
+    mut notice_q: NoticeQuery,
+) {
+    if !command_pressed(&keys) || !keys.just_pressed(KeyCode::M) {
+        return;
+    }
+    master_volume.toggle_mute();
+    *global_volume = master_volume.global_volume();
+    audio::apply_global_volume(&global_volume, &sounds_q);
+    let message = if master_volume.muted {
+        "Muted".to_string()
+    } else {
+        format!("Volume {:.0}%", master_volume.volume * 100.)
+    };
+    show_notice(&mut notice_q, &message, time.elapsed_seconds());
+}
+
+/// Moves what the guest printed, and what it wrote to stdout and stderr, to the on-screen
+/// console.
+fn handle_guest_print(
+    wasm_store: Option<ResMut<WasmStore>>,
+    time: Res<Time>,
+    mut console: ResMut<Console>,
+) {
+    let Some(mut wasm_store) = wasm_store else {
+        return;
+    };
+    print_guest_output(
+        wasm_store.store.data_mut(),
+        time.elapsed_seconds(),
+        &mut console,
+    );
+}
+
+fn print_guest_output(data: &mut MyCtx, now: f32, console: &mut Console) {
+    for (level, text) in data.printed.drain(..) {
+        console.push(now, ConsoleSource::Log(level), &text);
+    }
+    for line in data.stdout.take_lines() {
+        console.push(now, ConsoleSource::Stdout, &line);
+    }
+    for line in data.stderr.take_lines() {
+        console.push(now, ConsoleSource::Stderr, &line);
+    }
+}
+
+/// Takes a screenshot of the window for the pixels the guest asked for with `get_image_data`,
+/// which fills them in once this frame has been rendered.
+fn handle_readbacks(
+    wasm_store: Option<ResMut<WasmStore>>,
+    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+) {
+    let Some(mut wasm_store) = wasm_store else {
+        return;
+    };
+    let (Ok((window_entity, window)), Ok((node_transform, node))) =
+        (windows.get_single(), canvas_q.get_single())
+    else {
+        return;
+    };
+    let data = wasm_store.store.data_mut();
+    let requested = data.readbacks.take_requested();
+    if requested.is_empty() {
+        return;
+    }
+    let (camera, camera_transform) = camera_q.single();
+    let origin = data.canvas_config.transform(data.canvas.size);
+    let scale_factor = window.scale_factor() as f32;
+    // Only the canvas is the guest's to read, not the browser's UI around it
+    let canvas = Rect::from_center_size(node_transform.translation().truncate(), node.size());
+    let canvas = Rect::from_corners(canvas.min * scale_factor, canvas.max * scale_factor);
+    // Guest coordinates go to the world the way drawing does, and from there to the window's
+    // physical pixels, which the screenshot is in
+    let to_pixels = |p: Vec2| {
+        let world = origin.transform_point2(p) - data.canvas.position;
+        camera
+            .world_to_viewport(camera_transform, world.extend(0.))
+            .map(|p| p * scale_factor)
+    };
+    let regions: Vec<_> = requested
+        .into_iter()
+        .map(|(region, result)| {
+            let bounds = to_pixels(region.min)
+                .zip(to_pixels(region.max))
+                .map(|(a, b)| Rect::from_corners(a, b));
+            (bounds, result)
+        })
+        .collect();
+    let results: Vec<_> = regions.iter().map(|(_, result)| result.clone()).collect();
+    let taken = screenshots.take_screenshot(window_entity, move |screenshot: Image| {
+        for (bounds, result) in regions {
+            let pixels = bounds
+                .ok_or_else(|| "the region isn't on screen".to_string())
+                .and_then(|bounds| readback::crop(&screenshot, bounds, canvas));
+            *result.lock().unwrap() = Some(pixels);
+        }
+    });
+    if let Err(e) = taken {
+        for result in results {
+            *result.lock().unwrap() = Some(Err(format!("couldn't read back the window: {e}")));
+        }
+    }
+}
+
+/// Counts what the active guest has on screen for the diagnostics overlay, and shows or hides
+/// the overlay when the guest asks.
+fn handle_guest_stats(
+    wasm_store: Option<ResMut<WasmStore>>,
+    scene: Res<GuestScene>,
+    children_q: Query<&Children>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    if let Some(show) =
+        wasm_store.and_then(|mut store| store.store.data_mut().show_diagnostics.take())
+    {
+        diagnostics.visible = show;
+    }
+    if !diagnostics.visible {
+        return;
+    }
+    diagnostics.draw_commands = scene.entities().count();
+    diagnostics.entities = scene
+        .entities()
+        .map(|entity| 1 + children_q.iter_descendants(entity).count())
+        .sum();
+}
+
+fn with_alpha(color: Color, alpha: f32) -> Color {
+    color.with_a(color.a() * alpha)
+}
+
+/// The shape built from the current path, which `fill` and `stroke` paint.
+///
+/// The shape is added to the frame on first use and reused until the path changes, so a path
+/// that is both filled and stroked ends up as a single entity carrying both.
+fn path_shape<'a>(
+    frame: &'a mut Vec<DrawCommand>,
+    current_shape: &mut Option<usize>,
+    current_path: &[PathCommand],
+    transform: Transform,
+    clip: Option<Rect>,
+) -> Option<&'a mut GuestShape> {
+    let index = match *current_shape {
+        Some(index) => index,
+        None => {
+            let Some((PathCommand::Begin, path)) = current_path.split_first() else {
+                eprintln!("path should start from begin");
+                return None;
+            };
+            frame.push(DrawCommand::Shape(GuestShape {
+                geometry: Geometry::Path(path.to_vec()),
+                fill: None,
+                fill_rule: FillRule::NonZero,
+                stroke: None,
+                transform,
+                clip,
+                shadow: None,
+                blend: BlendMode::SrcOver,
+            }));
+            *current_shape = Some(frame.len() - 1);
+            frame.len() - 1
+        }
+    };
+    match &mut frame[index] {
+        DrawCommand::Shape(shape) => Some(shape),
+        _ => None,
+    }
+}
+
+/// Bounds of `bounds` after `transform`, which for rotations is the box around the rotated one.
+fn transformed_bounds(bounds: Rect, transform: &Transform) -> Rect {
+    [
+        bounds.min,
+        Vec2::new(bounds.min.x, bounds.max.y),
+        bounds.max,
+        Vec2::new(bounds.max.x, bounds.min.y),
+    ]
+    .map(|corner| transform.transform_point(corner.extend(0.)).truncate())
+    .into_iter()
+    .fold(
+        Rect {
+            min: Vec2::INFINITY,
+            max: Vec2::NEG_INFINITY,
+        },
+        |bounds, p| bounds.union_point(p),
+    )
+}
+
+/// Conservative bounds of a path: the box around all of its end and control points.
+fn path_bounds(path: &bevy_prototype_lyon::prelude::Path) -> Rect {
+    let mut points = path.0.points().iter().map(|p| Vec2::new(p.x, p.y));
+    let Some(first) = points.next() else {
+        return Rect::default();
+    };
+    points.fold(Rect::from_corners(first, first), |bounds, p| {
+        bounds.union_point(p)
+    })
+}
+
+/// Past this many dashes a stroke stops, so that tiny dashes along a long path can't stall the
+/// frame.
+const MAX_DASHES: usize = 100_000;
+
+/// The dashes along `path`, as a path of their own for the stroke to draw.
+///
+/// Curves are flattened into lines first, and each subpath starts `dash.offset` into the
+/// pattern, as on a canvas.
+fn dashed_path(
+    path: &bevy_prototype_lyon::prelude::Path,
+    dash: &LineDash,
+) -> bevy_prototype_lyon::prelude::Path {
+    use bevy_prototype_lyon::prelude::tess::path::{iterator::PathIterator, PathEvent};
+    let mut dasher = Dasher {
+        dash,
+        path_builder: PathBuilder::new(),
+        index: 0,
+        left: 0.,
+        drawing: false,
+        dashes: 0,
+    };
+    for event in path.0.iter().flattened(StrokeOptions::DEFAULT_TOLERANCE) {
+        match event {
+            PathEvent::Begin { .. } => dasher.start_subpath(),
+            PathEvent::Line { from, to } => {
+                dasher.line(Vec2::new(from.x, from.y), Vec2::new(to.x, to.y));
+            }
+            PathEvent::End {
+                last,
+                first,
+                close: true,
+            } => {
+                dasher.line(Vec2::new(last.x, last.y), Vec2::new(first.x, first.y));
+            }
+            _ => {}
+        }
+    }
+    dasher.path_builder.build()
+}
+
+/// Walks a path's lines, drawing the parts of them that fall on dashes.
+struct Dasher<'a> {
+    dash: &'a LineDash,
+    path_builder: PathBuilder,
+    /// The pattern segment the walk is in, even for dashes and odd for gaps
+    index: usize,
+    /// How much of that segment is left
+    left: f32,
+    /// Whether a dash is being drawn up to the current point
+    drawing: bool,
+    dashes: usize,
+}
+
+impl Dasher<'_> {
+    fn start_subpath(&mut self) {
+        let segments = &self.dash.segments;
+        let mut phase = self.dash.offset.rem_euclid(segments.iter().sum());
+        self.index = 0;
+        // Bounded in case rounding leaves `phase` at the full period
+        for _ in 0..segments.len() {
+            if phase < segments[self.index] {
+                break;
+            }
+            phase -= segments[self.index];
+            self.index = (self.index + 1) % segments.len();
+        }
+        self.left = segments[self.index] - phase;
+        self.drawing = false;
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2) {
+        let length = from.distance(to);
+        let mut travelled = 0.;
+        while travelled < length && self.dashes < MAX_DASHES {
+            let step = f32::min(self.left, length - travelled);
+            let on = self.index % 2 == 0;
+            if on && step > 0. {
+                if !self.drawing {
+                    self.path_builder.move_to(from.lerp(to, travelled / length));
+                    self.drawing = true;
+                }
+                self.path_builder
+                    .line_to(from.lerp(to, (travelled + step) / length));
+            }
+            travelled += step;
+            self.left -= step;
+            if self.left <= 0. {
+                self.drawing = false;
+                self.dashes += on as usize;
+                self.index = (self.index + 1) % self.dash.segments.len();
+                self.left = self.dash.segments[self.index];
+            }
+        }
+    }
+}
+
+/// A rectangle of `size` centered on the origin, with its corners rounded by `radii` in the order
+/// of `RoundRect`, which are at most half its smaller side.
+fn round_rect_path(size: Vec2, radii: [f32; 4]) -> bevy_prototype_lyon::prelude::Path {
+    let [top_left, top_right, bottom_right, bottom_left] = radii;
+    let half = size / 2.;
+    // Clockwise from the top left corner: where each side meets the next corner's arc, and the
+    // arc's center
+    let corners = [
+        (
+            Vec2::new(half.x - top_right, half.y),
+            Vec2::new(half.x - top_right, half.y - top_right),
+            top_right,
+        ),
+        (
+            Vec2::new(half.x, -half.y + bottom_right),
+            Vec2::new(half.x - bottom_right, -half.y + bottom_right),
+            bottom_right,
+        ),
+        (
+            Vec2::new(-half.x + bottom_left, -half.y),
+            Vec2::new(-half.x + bottom_left, -half.y + bottom_left),
+            bottom_left,
+        ),
+        (
+            Vec2::new(-half.x, half.y - top_left),
+            Vec2::new(-half.x + top_left, half.y - top_left),
+            top_left,
+        ),
+    ];
+    let mut path_builder = PathBuilder::new();
+    path_builder.move_to(Vec2::new(-half.x + top_left, half.y));
+    for (arc_start, center, radius) in corners {
+        path_builder.line_to(arc_start);
+        if radius > 0. {
+            path_builder.arc(
+                center,
+                Vec2::splat(radius),
+                -std::f32::consts::FRAC_PI_2,
+                0.,
+            );
+        }
+    }
+    path_builder.close();
+    path_builder.build()
+}
+
+/// Starts a subpath along `ellipse` from its start angle to its end angle.
+///
+/// Lyon's arcs start wherever the current point is, which is only on an ellipse's outline at the
+/// right angle for circles, so the ellipse is built from cubic curves instead: one for every
+/// quarter turn or less, each drawn on the unit circle and then stretched onto the ellipse.
+fn ellipse_path(path_builder: &mut PathBuilder, ellipse: &Ellipse) {
+    use std::f32::consts::{FRAC_PI_2, TAU};
+    let sweep = (ellipse.end_angle - ellipse.start_angle).clamp(-TAU, TAU);
+    let to_ellipse =
+        Affine2::from_angle_translation(ellipse.rotation, Vec2::new(ellipse.x, ellipse.y))
+            * Affine2::from_scale(Vec2::new(ellipse.radius_x, ellipse.radius_y));
+    let segments = (sweep.abs() / FRAC_PI_2).ceil().max(1.);
+    let step = sweep / segments;
+    // How far along the tangents the control points are for a curve that follows the circle
+    let handle = 4. / 3. * (step / 4.).tan();
+    let mut angle = ellipse.start_angle;
+    path_builder.move_to(to_ellipse.transform_point2(Vec2::from_angle(angle)));
+    for _ in 0..segments as usize {
+        let next = angle + step;
+        let control1 = Vec2::from_angle(angle) + Vec2::from_angle(angle).perp() * handle;
+        let control2 = Vec2::from_angle(next) - Vec2::from_angle(next).perp() * handle;
+        path_builder.cubic_bezier_to(
+            to_ellipse.transform_point2(control1),
+            to_ellipse.transform_point2(control2),
+            to_ellipse.transform_point2(Vec2::from_angle(next)),
+        );
+        angle = next;
+    }
+}
+
+fn build_path(commands: &[PathCommand]) -> bevy_prototype_lyon::prelude::Path {
+    let mut path_builder = PathBuilder::new();
+    // Whether there's a current point for `arc` and `arc_to` to join
+    let mut has_point = false;
+    for command in commands {
+        match command {
+            PathCommand::Arc(Arc {
+                x,
+                y,
+                radius,
+                sweep_angle,
+                x_rotation,
+            }) => {
+                // The arc starts on the right of the circle around `(x, y)`, joined by a line to
+                // the path so far like on a canvas
+                let center = Vec2::new(*x, *y);
+                let start = center + Vec2::new(*radius, 0.);
+                if has_point {
+                    path_builder.line_to(start);
+                } else {
+                    path_builder.move_to(start);
+                }
+                path_builder.arc(
+                    center,
+                    Vec2::new(*radius, *radius),
+                    *sweep_angle,
+                    *x_rotation,
+                );
+            }
+            PathCommand::ArcTo(ArcTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                radius,
+            }) => {
+                let corner = Vec2::new(*x1, *y1);
+                if !has_point {
+                    path_builder.move_to(corner);
+                }
+                arc_to(&mut path_builder, corner, Vec2::new(*x2, *y2), *radius);
+            }
+            PathCommand::Ellipse(ellipse) => ellipse_path(&mut path_builder, ellipse),
+            PathCommand::Begin => {
+                dbg!("path already created");
+            }
+            PathCommand::Close => {
+                path_builder.close();
+            }
+            PathCommand::MoveTo((x, y)) => {
+                path_builder.move_to(Vec2::new(*x, *y));
+            }
+            PathCommand::LineTo((x, y)) => {
+                path_builder.line_to(Vec2::new(*x, *y));
+            }
+            PathCommand::QuadraticBezierTo(QuadraticBezierTo { cx, cy, x, y }) => {
+                path_builder.quadratic_bezier_to(Vec2::new(*cx, *cy), Vec2::new(*x, *y));
+            }
+            PathCommand::CubicBezierTo(CubicBezierTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x3,
+                y3,
+            }) => {
+                path_builder.cubic_bezier_to(
+                    Vec2::new(*x1, *y1),
+                    Vec2::new(*x2, *y2),
+                    Vec2::new(*x3, *y3),
+                );
+            }
+        }
+        has_point = !matches!(command, PathCommand::Begin);
+    }
+    path_builder.build()
+}
+
+/// Rounds the corner at `corner` between the current point and `end` with an arc of `radius`,
+/// reaching it with a line from the current point. `end` is only a direction: the path stops
+/// where the arc meets the line to it.
+fn arc_to(path_builder: &mut PathBuilder, corner: Vec2, end: Vec2, radius: f32) {
+    let start = path_builder.current_position();
+    let to_start = (start - corner).normalize_or_zero();
+    let to_end = (end - corner).normalize_or_zero();
+    let cos = to_start.dot(to_end);
+    // Without a corner to round, as when the three points are in a line, a canvas draws a
+    // straight line to the corner
+    if radius == 0. || to_start == Vec2::ZERO || to_end == Vec2::ZERO || cos.abs() > 1. - 1e-6 {
+        path_builder.line_to(corner);
+        return;
+    }
+    // The arc touches both lines where they're this far from the corner
+    let half_angle = cos.acos() / 2.;
+    let tangent_distance = radius / half_angle.tan();
+    let center = corner + (to_start + to_end).normalize() * (radius / half_angle.sin());
+    let arc_start = corner + to_start * tangent_distance;
+    let arc_end = corner + to_end * tangent_distance;
+    path_builder.line_to(arc_start);
+    path_builder.arc(
+        center,
+        Vec2::splat(radius),
+        (arc_start - center).angle_between(arc_end - center),
+        0.,
+    );
+}
+
+fn handle_refresh(
+    text_input_q: Query<&CosmicEditor, With<AddressBar>>,
+    mut refresh_q: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RefreshButton>),
+    >,
+    runtime: ResMut<TokioTasksRuntime>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+) {
+    if windows.iter().len() == 0 {
+        return;
+    }
+    let primary_window = windows.single();
+    for (interaction, mut background_color) in refresh_q.iter_mut() {
+        match interaction {
+            Interaction::Pressed => {
+                *background_color = Color::GRAY.with_a(0.3).into();
+                let text = text_input_q.single().get_text();
+                let (canvas_global_transform, canvas_node) = canvas_q.single();
+                let (camera, camera_transform) = camera_q.single();
+                let canvas_position = get_position(
+                    canvas_global_transform,
+                    &primary_window,
+                    camera,
+                    camera_transform,
+                );
+                if canvas_position.is_none() {
+                    return;
+                }
+                let canvas = Canvas {
+                    size: canvas_node.size(),
+                    position: canvas_position.unwrap(),
+                };
+                spawn_get_wasm(&runtime, text, canvas, config.clone(), LoadSource::User);
+            }
+            Interaction::Hovered => {
+                *background_color = Color::GRAY.with_a(0.3).into();
+            }
+            Interaction::None => *background_color = Color::NONE.into(),
+        }
+    }
+}
+
+fn handle_link(
+    mut text_input_q: Query<(&CosmicEditor, &mut CosmicText), With<AddressBar>>,
+    links_q: Query<(&Interaction, &GuestUrl), (Changed<Interaction>, With<GuestUrl>)>,
+    runtime: ResMut<TokioTasksRuntime>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+) {
+    if windows.iter().len() == 0 {
+        return;
+    }
+    let mut primary_window = windows.single_mut();
+    for (interaction, url) in links_q.iter() {
+        match interaction {
+            Interaction::Pressed => {
+                primary_window.cursor.icon = CursorIcon::Hand;
+                let mut text = url.0.clone();
+                let (editor, mut text_setter) = text_input_q.single_mut();
+                if let Ok(previous_url_value) = Url::parse(&make_url_valid(editor.get_text())) {
+                    if let Ok(new_url) = previous_url_value.join(text.as_str()) {
+                        text = new_url.to_string().replace("https://", "");
+                    }
+                }
+                *text_setter = CosmicText::OneStyle(text.clone());
+                let (canvas_global_transform, canvas_node) = canvas_q.single();
+                let (camera, camera_transform) = camera_q.single();
+                let canvas_position = get_position(
+                    canvas_global_transform,
+                    &primary_window,
+                    camera,
+                    camera_transform,
+                );
+                if canvas_position.is_none() {
+                    return;
+                }
+                let canvas = Canvas {
+                    size: canvas_node.size(),
+                    position: canvas_position.unwrap(),
+                };
+                spawn_get_wasm(&runtime, text, canvas, config.clone(), LoadSource::Guest);
+            }
+            Interaction::Hovered => {
+                primary_window.cursor.icon = CursorIcon::Hand;
+            }
+            Interaction::None => {
+                primary_window.cursor.icon = CursorIcon::Default;
+            }
+        }
+    }
+}
+
+/// Shows a pointer over the rectangles guests define with `define_link`, and navigates to a
+/// rectangle's host when it's clicked. Where rectangles overlap, the last one defined wins.
+fn handle_link_areas(
+    wasm_store: Option<ResMut<WasmStore>>,
+    scene: Res<GuestScene>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut hovering: Local<bool>,
+) {
+    let Ok(mut primary_window) = windows.get_single_mut() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+    let cursor = primary_window
+        .cursor_position()
+        .and_then(|p| camera.viewport_to_world_2d(camera_transform, p));
+    let url = cursor.and_then(|cursor| scene.link_area_at(cursor));
+    if url.is_some() != *hovering {
+        *hovering = url.is_some();
+        primary_window.cursor.icon = if *hovering {
+            CursorIcon::Hand
+        } else {
+            CursorIcon::Default
+        };
+    }
+    if let (Some(url), Some(mut wasm_store)) = (url, wasm_store) {
+        if mouse_buttons.just_pressed(MouseButton::Left) {
+            // `handle_navigation` checks that it's an http(s) URL, like any other navigation
+            wasm_store.store.data_mut().navigation = Some(url.clone());
+        }
+    }
+}
+
+/// Loads the guest a guest asked for with `navigate`, clearing away the one that asked so that it
+/// neither runs nor stays on screen while the new one loads.
+fn handle_navigation(
+    mut commands: Commands,
+    wasm_store: Option<ResMut<WasmStore>>,
+    wasm_bindings: Option<Res<WasmBindings>>,
+    mut scene: ResMut<GuestScene>,
+    mut text_input_q: Query<&mut CosmicText, With<AddressBar>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+) {
+    let Some(mut wasm_store) = wasm_store else {
+        return;
+    };
+    let Some(url) = wasm_store.store.data_mut().navigation.take() else {
+        return;
+    };
+    // Guests only ever get to go to the web, never to a file the address bar would open
+    let Some(url) = guest_navigation_url(&url) else {
+        eprintln!("ignoring navigation to {url}, which isn't an http(s) URL");
+        return;
+    };
+    let Ok(primary_window) = windows.get_single() else {
+        return;
+    };
+    let (canvas_global_transform, canvas_node) = canvas_q.single();
+    let (camera, camera_transform) = camera_q.single();
+    let Some(canvas_position) = get_position(
+        canvas_global_transform,
+        primary_window,
+        camera,
+        camera_transform,
+    ) else {
+        return;
+    };
+    if let Some(bindings) = wasm_bindings {
+        run_on_unload(&mut wasm_store, &bindings, &config);
+    }
+    for entity in std::mem::take(&mut *scene).entities() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<WasmStore>();
+    commands.remove_resource::<WasmBindings>();
+    for mut text_setter in text_input_q.iter_mut() {
+        *text_setter = CosmicText::OneStyle(url.clone());
+    }
+    let canvas = Canvas {
+        size: canvas_node.size(),
+        position: canvas_position,
+    };
+    spawn_get_wasm(&runtime, url, canvas, config.clone(), LoadSource::Guest);
+}
+
+fn handle_get_wasm(
+    editor_q: Query<&CosmicEditor, With<AddressBar>>,
+    keys: Res<Input<KeyCode>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+) {
+    if windows.iter().len() == 0 {
+        return;
+    }
+    let primary_window = windows.single();
+    let command = command_pressed(&keys);
+    if !keys.just_pressed(KeyCode::Return) && !(command && keys.just_pressed(KeyCode::R)) {
+        return;
+    }
+    for editor in editor_q.iter() {
+        let text = editor.get_text();
+        let (canvas_global_transform, canvas_node) = canvas_q.single();
+        let (camera, camera_transform) = camera_q.single();
+        let canvas_position = get_position(
+            canvas_global_transform,
+            &primary_window,
+            camera,
+            camera_transform,
+        );
+        if canvas_position.is_none() {
+            return;
+        }
+        let canvas = Canvas {
+            size: canvas_node.size(),
+            position: canvas_position.unwrap(),
+        };
+        spawn_get_wasm(&runtime, text, canvas, config.clone(), LoadSource::User);
+    }
+}
+
+/// The URL of the guest that's running, so that it can be reloaded without retyping it
+#[derive(Resource)]
+struct LastLoadedUrl(String);
+
+/// The URLs of the guests loaded so far, for going back and forward between them
+#[derive(Resource, Default)]
+struct History {
+    entries: Vec<String>,
+    /// The entry of the guest that's running, meaningless while `entries` is empty
+    index: usize,
+    /// The entry being loaded by going back or forward, which becomes the current one rather
+    /// than being pushed again once it loads
+    pending: Option<usize>,
+}
+
+impl History {
+    /// Records a successful load of `url`, dropping the entries ahead of the current one unless
+    /// it was loaded from history. Reloading the current entry changes nothing.
+    fn visited(&mut self, url: &str) {
+        if let Some(pending) = self.pending.take() {
+            if self.entries.get(pending).is_some_and(|entry| entry == url) {
+                self.index = pending;
+                return;
+            }
+        }
+        if self
+            .entries
+            .get(self.index)
+            .is_some_and(|entry| entry == url)
+        {
+            return;
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.index + 1);
+        }
+        self.entries.push(url.to_string());
+        self.index = self.entries.len() - 1;
+    }
+
+    /// Starts going `steps` entries back (negative) or forward, returning the URL to load, or
+    /// `None` past either end.
+    fn go(&mut self, steps: isize) -> Option<String> {
+        let target = self.index.checked_add_signed(steps)?;
+        let url = self.entries.get(target)?.clone();
+        self.pending = Some(target);
+        Some(url)
+    }
+}
+
+/// Goes back through the history on Alt+Left or the back key, and forward on Alt+Right or the
+/// forward key.
+fn handle_history(
+    keys: Res<Input<KeyCode>>,
+    mut history: ResMut<History>,
+    mut text_input_q: Query<&mut CosmicText, With<AddressBar>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+) {
+    let alt = alt_pressed(&keys);
+    let steps = if keys.just_pressed(KeyCode::NavigateBackward)
+        || (alt && keys.just_pressed(KeyCode::Left))
+    {
+        -1
+    } else if keys.just_pressed(KeyCode::NavigateForward)
+        || (alt && keys.just_pressed(KeyCode::Right))
+    {
+        1
+    } else {
+        return;
+    };
+    let Ok(primary_window) = windows.get_single() else {
+        return;
+    };
+    let (canvas_global_transform, canvas_node) = canvas_q.single();
+    let (camera, camera_transform) = camera_q.single();
+    let Some(canvas_position) = get_position(
+        canvas_global_transform,
+        primary_window,
+        camera,
+        camera_transform,
+    ) else {
+        return;
+    };
+    let Some(url) = history.go(steps) else {
+        return;
+    };
+    for mut text_setter in text_input_q.iter_mut() {
+        *text_setter = CosmicText::OneStyle(url.clone());
+    }
+    let canvas = Canvas {
+        size: canvas_node.size(),
+        position: canvas_position,
+    };
+    spawn_get_wasm(&runtime, url, canvas, config.clone(), LoadSource::User);
+}
+
+/// A tab's guest while another tab is showing. The showing tab's guest lives in the `WasmStore`,
+/// `WasmBindings`, `GuestScene`, `History` and `LastLoadedUrl` resources instead, so the systems
+/// that run it don't need to know about tabs, and it's moved into its `Tab` when another tab is
+/// shown.
+#[derive(Default)]
+struct Tab {
+    id: u64,
+    store: Option<WasmStore>,
+    bindings: Option<WasmBindings>,
+    /// Hidden rather than despawned, so that switching back is instant
+    scene: GuestScene,
+    history: History,
+    last_loaded: Option<LastLoadedUrl>,
+    /// What was in the address bar
+    address: String,
+}
+
+impl Tab {
+    /// Puts a guest that finished loading while the tab was in the background in the tab,
+    /// returning the old guest's scene to despawn.
+    fn loaded(
+        &mut self,
+        store: WasmStore,
+        bindings: WasmBindings,
+        url: String,
+        config: &PortalConfig,
+    ) -> GuestScene {
+        if let (Some(old_store), Some(old_bindings)) = (&mut self.store, &self.bindings) {
+            run_on_unload(old_store, old_bindings, config);
+        }
+        self.store = Some(store);
+        self.bindings = Some(bindings);
+        self.history.visited(&url);
+        self.last_loaded = Some(LastLoadedUrl(url));
+        std::mem::take(&mut self.scene)
+    }
+}
+
+/// The open tabs, with the showing one's guest checked out into the guest resources.
+#[derive(Resource)]
+struct Tabs {
+    tabs: Vec<Tab>,
+    active: usize,
+    next_id: u64,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Self {
+            tabs: vec![Tab::default()],
+            active: 0,
+            next_id: 1,
+        }
+    }
+}
+
+impl Tabs {
+    fn active_id(&self) -> u64 {
+        self.tabs[self.active].id
+    }
+
+    /// The tab with `id`, unless it's closed or showing.
+    fn background_mut(&mut self, id: u64) -> Option<&mut Tab> {
+        let active = self.active;
+        self.tabs
+            .iter_mut()
+            .enumerate()
+            .find(|(i, tab)| *i != active && tab.id == id)
+            .map(|(_, tab)| tab)
+    }
+
+    fn new_tab(&mut self) -> Tab {
+        let id = self.next_id;
+        self.next_id += 1;
+        Tab { id, ..default() }
+    }
+}
+
+enum TabAction {
+    Open,
+    Close,
+    Next,
+    Previous,
+}
+
+/// Opens a tab after the showing one on Ctrl+T, closes the showing one on Ctrl+W, and shows the
+/// next or previous one on Ctrl+Tab or Ctrl+Shift+Tab, with Cmd instead of Ctrl on macOS. Guests
+/// in the background don't run and can't be heard.
+fn handle_tabs(world: &mut World) {
+    let keys = world.resource::<Input<KeyCode>>();
+    if !command_pressed(keys) {
+        return;
+    }
+    let action = if keys.just_pressed(KeyCode::T) {
+        TabAction::Open
+    } else if keys.just_pressed(KeyCode::W) {
+        TabAction::Close
+    } else if keys.just_pressed(KeyCode::Tab) {
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            TabAction::Previous
+        } else {
+            TabAction::Next
+        }
+    } else {
+        return;
+    };
+    let (active, count) = {
+        let tabs = world.resource::<Tabs>();
+        (tabs.active, tabs.tabs.len())
+    };
+    stash_tab(world);
+    let target = match action {
+        TabAction::Open => {
+            let mut tabs = world.resource_mut::<Tabs>();
+            let tab = tabs.new_tab();
+            tabs.tabs.insert(active + 1, tab);
+            active + 1
+        }
+        TabAction::Close => {
+            let mut tabs = world.resource_mut::<Tabs>();
+            // Closing the last tab leaves an empty one in its place
+            let closed = if count == 1 {
+                let tab = tabs.new_tab();
+                std::mem::replace(&mut tabs.tabs[0], tab)
+            } else {
+                tabs.tabs.remove(active)
+            };
+            if let (Some(mut store), Some(bindings)) = (closed.store, &closed.bindings) {
+                run_on_unload(&mut store, bindings, world.resource::<PortalConfig>());
+            }
+            for entity in closed.scene.entities() {
+                if let Some(entity) = world.get_entity_mut(entity) {
+                    entity.despawn_recursive();
+                }
+            }
+            active.min(count.saturating_sub(2))
+        }
+        TabAction::Next => (active + 1) % count,
+        TabAction::Previous => (active + count - 1) % count,
+    };
+    world.resource_mut::<Tabs>().active = target;
+    show_tab(world);
+}
+
+/// Moves the showing tab's guest out of the guest resources into its `Tab`, hiding its drawing
+/// and stopping its sounds.
+fn stash_tab(world: &mut World) {
+    let scene = std::mem::take(&mut *world.resource_mut::<GuestScene>());
+    for entity in scene.entities() {
+        if let Some(mut visibility) = world.get_mut::<Visibility>(entity) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+    stop_guest_sounds(world);
+    let address = world
+        .query_filtered::<&CosmicEditor, With<AddressBar>>()
+        .iter(world)
+        .next()
+        .map(|editor| editor.get_text())
+        .unwrap_or_default();
+    let tab = Tab {
+        id: world.resource::<Tabs>().active_id(),
+        store: world.remove_resource(),
+        bindings: world.remove_resource(),
+        scene,
+        history: std::mem::take(&mut *world.resource_mut::<History>()),
+        last_loaded: world.remove_resource(),
+        address,
+    };
+    let mut tabs = world.resource_mut::<Tabs>();
+    let active = tabs.active;
+    tabs.tabs[active] = tab;
+}
+
+/// Moves the now active tab's guest into the guest resources and shows its drawing.
+fn show_tab(world: &mut World) {
+    let tab = {
+        let mut tabs = world.resource_mut::<Tabs>();
+        let active = tabs.active;
+        let id = tabs.tabs[active].id;
+        std::mem::replace(&mut tabs.tabs[active], Tab { id, ..default() })
+    };
+    for entity in tab.scene.entities() {
+        if let Some(mut visibility) = world.get_mut::<Visibility>(entity) {
+            *visibility = Visibility::Inherited;
+        }
+    }
+    world.resource_mut::<Diagnostics>().clear_guest_times();
+    let status = if tab.store.is_some() {
+        LoadStatus::Ready
+    } else {
+        LoadStatus::Idle
+    };
+    if let Some(store) = tab.store {
+        world.insert_resource(store);
+    }
+    if let Some(bindings) = tab.bindings {
+        world.insert_resource(bindings);
+    }
+    if let Some(last_loaded) = tab.last_loaded {
+        world.insert_resource(last_loaded);
+    }
+    world.insert_resource(tab.scene);
+    world.insert_resource(tab.history);
+    world.insert_resource(status);
+    for mut text in world
+        .query_filtered::<&mut CosmicText, With<AddressBar>>()
+        .iter_mut(world)
+    {
+        *text = CosmicText::OneStyle(tab.address.clone());
+    }
+}
+
+/// Reloads the running guest on F5, even after its address was edited, and runs its `setup` again.
+fn handle_reload(
+    keys: Res<Input<KeyCode>>,
+    last_loaded: Option<Res<LastLoadedUrl>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    portal_canvas: PortalCanvas,
+    config: Res<PortalConfig>,
+) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let Some(last_loaded) = last_loaded else {
+        return;
+    };
+    let Some(canvas) = portal_canvas.get() else {
+        return;
+    };
+    spawn_get_wasm(
+        &runtime,
+        last_loaded.0.clone(),
+        canvas,
+        config.clone(),
+        LoadSource::User,
+    );
+}
+
+/// Switches reader mode for the running guest's host on F4, or forgets what it was allowed and
+/// denied on Shift+F4, saying which for a moment, and reloads the guest so it only gets the
+/// imports it's now allowed.
+fn handle_reader_mode(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    last_loaded: Option<Res<LastLoadedUrl>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    portal_canvas: PortalCanvas,
+    mut config: ResMut<PortalConfig>,
+    mut notice_q: NoticeQuery,
+) {
+    if !keys.just_pressed(KeyCode::F4) {
+        return;
+    }
+    let Some(last_loaded) = last_loaded else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        let Some(host) = capabilities::host_key(&last_loaded.0) else {
+            let message = format!("{} has no host to reset permissions for", last_loaded.0);
+            show_notice(&mut notice_q, &message, now);
+            return;
+        };
+        config.permissions.reset(&host);
+        show_notice(&mut notice_q, &format!("Permissions reset for {host}"), now);
+    } else {
+        let message = match config.reader_hosts.toggle(&last_loaded.0) {
+            Ok(Capabilities::Reader) => {
+                "Reader mode on: this host's guests can't fetch, store, navigate or read files"
+            }
+            Ok(Capabilities::Full) => "Reader mode off",
+            Err(e) => {
+                let message = format!("Can't switch reader mode: {e}");
+                show_notice(&mut notice_q, &message, now);
+                return;
+            }
+        };
+        show_notice(&mut notice_q, message, now);
+    }
+    if let Some(canvas) = portal_canvas.get() {
+        spawn_get_wasm(
+            &runtime,
+            last_loaded.0.clone(),
+            canvas,
+            config.clone(),
+            LoadSource::User,
+        );
+    }
+}
+
+/// Shows the oldest permission request guests are waiting on, and saves the answer when one of
+/// its buttons is pressed. Allowing reloads the running guest if it's from that host, so it can
+/// start over with what it asked for rather than having to try again.
+fn handle_permission_prompt(
+    config: Res<PortalConfig>,
+    last_loaded: Option<Res<LastLoadedUrl>>,
+    runtime: ResMut<TokioTasksRuntime>,
+    portal_canvas: PortalCanvas,
+    mut prompt_q: Query<&mut Style, With<PermissionPrompt>>,
+    mut text_q: Query<&mut Text, With<PermissionPromptText>>,
+    buttons_q: Query<(&Interaction, &PermissionButton), Changed<Interaction>>,
+) {
+    let pending = config.permissions.pending();
+    if let Some((host, permission)) = &pending {
+        for (interaction, button) in buttons_q.iter() {
+            if *interaction != Interaction::Pressed {
+                continue;
+            }
+            config.permissions.decide(host, *permission, button.allow);
+            let running = last_loaded
+                .as_ref()
+                .filter(|url| capabilities::host_key(&url.0).as_ref() == Some(host));
+            if let (true, Some(url), Some(canvas)) = (button.allow, running, portal_canvas.get()) {
+                spawn_get_wasm(
+                    &runtime,
+                    url.0.clone(),
+                    canvas,
+                    config.clone(),
+                    LoadSource::User,
+                );
+            }
+            return;
+        }
+    }
+    let display = if pending.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    for mut style in prompt_q.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    if let Some((host, permission)) = pending {
+        let question = format!("{host} wants to {}", permission.request());
+        for mut text in text_q.iter_mut() {
+            if text.sections[0].value != question {
+                text.sections[0].value = question.clone();
+            }
+        }
+    }
+}
+
+/// Where the canvas guests draw on is, for systems that load guests onto it.
+#[derive(SystemParam)]
+struct PortalCanvas<'w, 's> {
+    canvas_q: Query<'w, 's, (&'static GlobalTransform, &'static bevy::ui::Node), With<Portal>>,
+    camera_q: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+}
+
+impl PortalCanvas<'_, '_> {
+    /// The canvas's size and position, or `None` without a window.
+    fn get(&self) -> Option<Canvas> {
+        let primary_window = self.windows.get_single().ok()?;
+        let (canvas_global_transform, canvas_node) = self.canvas_q.single();
+        let (camera, camera_transform) = self.camera_q.single();
+        let canvas_position = get_position(
+            canvas_global_transform,
+            primary_window,
+            camera,
+            camera_transform,
+        )?;
+        Some(Canvas {
+            size: canvas_node.size(),
+            position: canvas_position,
+        })
+    }
+}
+
+fn get_position(
+    global_transform: &GlobalTransform,
+    primary_window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let world_position = global_transform.affine().translation;
+    let point = Vec2::new(world_position.x, primary_window.height() - world_position.y);
+    camera.viewport_to_world_2d(camera_transform, point)
+}
+
+/// The `events` exports of a guest, each `None` if the guest doesn't export it.
+#[derive(Clone, Copy, Default)]
+struct GuestHooks {
+    on_resize: Option<TypedFunc<(f32, f32), ()>>,
+    on_mouse_down: Option<MouseDownHook>,
+    on_mouse_up: Option<MouseHook>,
+    on_drag_start: Option<MouseHook>,
+    on_drag: Option<DragHook>,
+    on_drag_end: Option<DragHook>,
+    on_key_down: Option<KeyHook>,
+    on_key_up: Option<KeyHook>,
+    on_scroll: Option<TypedFunc<(f32, f32), ()>>,
+    on_region_enter: Option<TypedFunc<(u32,), ()>>,
+    on_region_leave: Option<TypedFunc<(u32,), ()>>,
+    on_timer: Option<TypedFunc<(u32,), ()>>,
+    on_unload: Option<TypedFunc<(), ()>>,
+}
+
+type MouseHook = TypedFunc<(f32, f32, levo::portal::my_imports::MouseButton), ()>;
+type MouseDownHook = TypedFunc<(f32, f32, levo::portal::my_imports::MouseButton, u32), ()>;
+type DragHook = TypedFunc<(f32, f32, f32, f32, levo::portal::my_imports::MouseButton), ()>;
+type KeyHook = TypedFunc<
+    (
+        levo::portal::my_imports::KeyCode,
+        levo::portal::my_imports::Modifiers,
+    ),
+    (),
+>;
+
+impl GuestHooks {
+    fn new(store: &mut Store<MyCtx>, instance: &Instance) -> wasmtime::Result<Self> {
+        let mut exports = instance.exports(store);
+        let Some(mut events) = exports.instance("levo:portal/events") else {
+            return Ok(Self::default());
+        };
+        Ok(Self {
+            on_resize: optional_hook(&mut events, "on-resize")?,
+            on_mouse_down: optional_hook(&mut events, "on-mouse-down")?,
+            on_mouse_up: optional_hook(&mut events, "on-mouse-up")?,
+            on_drag_start: optional_hook(&mut events, "on-drag-start")?,
+            on_drag: optional_hook(&mut events, "on-drag")?,
+            on_drag_end: optional_hook(&mut events, "on-drag-end")?,
+            on_key_down: optional_hook(&mut events, "on-key-down")?,
+            on_key_up: optional_hook(&mut events, "on-key-up")?,
+            on_scroll: optional_hook(&mut events, "on-scroll")?,
+            on_region_enter: optional_hook(&mut events, "on-region-enter")?,
+            on_region_leave: optional_hook(&mut events, "on-region-leave")?,
+            on_timer: optional_hook(&mut events, "on-timer")?,
+            on_unload: optional_hook(&mut events, "on-unload")?,
+        })
+    }
+}
+
+/// A hook the guest may leave out. One it exports with the wrong signature is an error, since
+/// the guest was built against some other version of the interface.
+fn optional_hook<Params, Results>(
+    events: &mut ExportInstance<'_, '_>,
+    name: &str,
+) -> wasmtime::Result<Option<TypedFunc<Params, Results>>>
+where
+    Params: ComponentNamedList + Lower,
+    Results: ComponentNamedList + Lift,
+{
+    if events.func(name).is_none() {
+        return Ok(None);
+    }
+    let hook = events
+        .typed_func(name)
+        .map_err(|e| e.context(format!("guest export `{name}` has the wrong type")))?;
+    Ok(Some(hook))
+}
+
+/// Gives a guest that's about to be dropped, for another guest or because its tab closed, a last
+/// call to `on-unload` on fresh budgets, so it can save what it needs to. A guest that fails in it
+/// is dropped all the same, as is one whose `setup` never ran.
+fn run_on_unload(store: &mut WasmStore, bindings: &WasmBindings, config: &PortalConfig) {
+    if bindings.first_run || bindings.hooks.on_unload.is_none() {
+        return;
+    }
+    store
+        .store
+        .set_fuel(config.fuel_per_frame)
+        .expect("fuel should be enabled in get_wasm");
+    store.store.set_epoch_deadline(config.call_timeout_ms);
+    if let Err(e) = call_hook(&mut store.store, bindings.hooks.on_unload, ()) {
+        eprintln!("guest on-unload failed: {e:?}");
+    }
+}
+
+fn call_drag_hooks(
+    store: &mut Store<MyCtx>,
+    hooks: &GuestHooks,
+    events: Vec<DragEvent>,
+) -> Result<(), (&'static str, wasmtime::Error)> {
+    for event in events {
+        match event {
+            DragEvent::Start(p, button) => {
+                call_hook(store, hooks.on_drag_start, (p.x, p.y, button.into()))
+                    .map_err(|e| ("on-drag-start", e))?;
+            }
+            DragEvent::Move(p, delta, button) => {
+                let params = (p.x, p.y, delta.x, delta.y, button.into());
+                call_hook(store, hooks.on_drag, params).map_err(|e| ("on-drag", e))?;
+            }
+            DragEvent::End(p, delta, button) => {
+                let params = (p.x, p.y, delta.x, delta.y, button.into());
+                call_hook(store, hooks.on_drag_end, params).map_err(|e| ("on-drag-end", e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn call_hook<Params, Results>(
+    store: &mut Store<MyCtx>,
+    hook: Option<TypedFunc<Params, Results>>,
+    params: Params,
+) -> wasmtime::Result<()>
+where
+    Params: ComponentNamedList + Lower,
+    Results: ComponentNamedList + Lift,
+{
+    if let Some(hook) = hook {
+        hook.call(&mut *store, params)?;
+        hook.post_return(store)?;
+    }
+    Ok(())
+}
+
+/// Whether the platform's shortcut modifier, Cmd on macOS and Ctrl elsewhere, is held.
+fn command_pressed(keys: &Input<KeyCode>) -> bool {
+    if cfg!(target_os = "macos") {
+        keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight])
+    } else {
+        keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    }
+}
+
+fn alt_pressed(keys: &Input<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])
+}
+
+/// Keys the portal handles itself, which the guest's key hooks don't see.
+fn is_reserved_key(key: KeyCode, keys: &Input<KeyCode>) -> bool {
+    match key {
+        KeyCode::Return | KeyCode::F3 | KeyCode::F4 | KeyCode::F5 | KeyCode::Grave => true,
+        KeyCode::M | KeyCode::R | KeyCode::T | KeyCode::W | KeyCode::Tab => command_pressed(keys),
+        KeyCode::NavigateBackward | KeyCode::NavigateForward => true,
+        KeyCode::Left | KeyCode::Right => alt_pressed(keys),
+        _ => false,
+    }
+}
+
+fn modifiers(keys: &Input<KeyCode>) -> levo::portal::my_imports::Modifiers {
+    use levo::portal::my_imports::Modifiers;
+    let mut modifiers = Modifiers::empty();
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        modifiers |= Modifiers::SHIFT;
+    }
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        modifiers |= Modifiers::CTRL;
+    }
+    if alt_pressed(keys) {
+        modifiers |= Modifiers::ALT;
+    }
+    modifiers
+}
+
+/// Pixels per line for wheels that scroll by lines, about a line of text.
+const SCROLL_LINE_HEIGHT: f32 = 20.;
+
+/// Adds up the scrolling in `events` in pixels, positive towards the bottom right of the page.
+fn scroll_delta(events: &mut EventReader<MouseWheel>) -> Vec2 {
+    events
+        .read()
+        .map(|event| {
+            // Bevy's wheel deltas are positive when scrolling up or left
+            let delta = -Vec2::new(event.x, event.y);
+            match event.unit {
+                MouseScrollUnit::Line => delta * SCROLL_LINE_HEIGHT,
+                MouseScrollUnit::Pixel => delta,
+            }
+        })
+        .sum()
+}
+
+fn run_wasm_update(
+    mut commands: Commands,
+    wasm_instance: Option<ResMut<WasmBindings>>,
+    wasm_store: Option<ResMut<WasmStore>>,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut mouse_button_events: EventReader<MouseButtonInput>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    canvas_q: Query<(&GlobalTransform, &bevy::ui::Node), With<Portal>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<PortalConfig>,
+    mut diagnostics: ResMut<Diagnostics>,
+    scene: Res<GuestScene>,
+) {
+    if windows.iter().len() == 0 {
+        return;
+    }
+    let primary_window = windows.single();
+    let mouse_button_events: Vec<_> = mouse_button_events.read().cloned().collect();
+    let key_events: Vec<_> = keyboard_events
+        .read()
+        .filter_map(|event| Some((event.key_code?, event.state)))
+        .filter(|&(key, _)| !is_reserved_key(key, &keys))
+        .collect();
+    let scroll = scroll_delta(&mut mouse_wheel_events);
+    if let Some(mut wasm_resource) = wasm_instance {
+        let mut store = wasm_store.unwrap();
+        let now = time.elapsed_seconds();
+        // A freshly loaded guest sees a zero delta on its first frame, and its clock starts there.
+        // Frames skipped while the guest was idle still count towards the next delta.
+        let delta_seconds = now - wasm_resource.last_update_at.unwrap_or(now);
+        let started_at = *wasm_resource.started_at.get_or_insert(now);
+        let elapsed_seconds = now - started_at;
+        let (redraw, resized, hovered_region) = {
+            let (canvas_global_transform, canvas_node) = canvas_q.single();
+            let (camera, camera_transform) = camera_q.single();
+            let canvas_position = get_position(
+                canvas_global_transform,
+                primary_window,
+                camera,
+                camera_transform,
+            );
+            let data = store.store.data_mut();
+            let previous_canvas = (data.canvas.size, data.canvas.position);
+            let previous_scale_factor = data.scale_factor;
+            let previous_cursor_position = data.inputs.cursor_position;
+
+            data.delta_seconds = delta_seconds;
+            data.timers.set_now(time.elapsed_seconds_f64());
+
+            data.inputs.keys_just_pressed.clear();
+            data.inputs
+                .keys_just_pressed
+                .extend(keys.get_just_pressed());
+
+            data.inputs.keys_pressed.clear();
+            data.inputs.keys_pressed.extend(keys.get_pressed());
+
+            data.inputs.keys_just_released.clear();
+            data.inputs
+                .keys_just_released
+                .extend(keys.get_just_released());
+
+            data.inputs.mouse_buttons_just_pressed.clear();
+            data.inputs
+                .mouse_buttons_just_pressed
+                .extend(mouse_buttons.get_just_pressed());
+
+            data.inputs.mouse_buttons_pressed.clear();
+            data.inputs
+                .mouse_buttons_pressed
+                .extend(mouse_buttons.get_pressed());
+
+            data.inputs.mouse_buttons_just_released.clear();
+            data.inputs
+                .mouse_buttons_just_released
+                .extend(mouse_buttons.get_just_released());
+
+            data.scale_factor = primary_window.scale_factor() as f32;
+            if let Some(pos) = canvas_position {
+                data.canvas = Canvas {
+                    size: canvas_node.size(),
+                    position: pos,
+                }
+            }
+            // Guest coordinates are world coordinates shifted by the canvas position, the other
+            // way around from how drawing commands are shifted when they're queued, and mapped
+            // back through the canvas origin
+            let origin = data.canvas_config.transform(data.canvas.size).inverse();
+            let world_cursor = q_windows
+                .get_single()
+                .ok()
+                .and_then(|w| w.cursor_position())
+                .and_then(|p| camera.viewport_to_world_2d(camera_transform, p));
+            data.inputs.cursor_position =
+                world_cursor.map(|p| origin.transform_point2(p + data.canvas.position));
+            let hovered_region = world_cursor.and_then(|p| scene.region_at(p));
+            if let Some(p) = data.inputs.cursor_position {
+                data.inputs.last_cursor_position = p;
+            }
+
+            // Input and canvas changes count as a redraw request, since the guest couldn't
+            // react to them otherwise
+            let inputs = &data.inputs;
+            let input_changed = !inputs.keys_just_pressed.is_empty()
+                || !inputs.keys_pressed.is_empty()
+                || !inputs.keys_just_released.is_empty()
+                || !inputs.mouse_buttons_just_pressed.is_empty()
+                || !inputs.mouse_buttons_pressed.is_empty()
+                || !inputs.mouse_buttons_just_released.is_empty()
+                || inputs.cursor_position != previous_cursor_position
+                || scroll != Vec2::ZERO
+                || (data.canvas.size, data.canvas.position) != previous_canvas
+                || data.scale_factor != previous_scale_factor
+                || hovered_region != data.hovered_region;
+            let redraw = wasm_resource.last_update_at.is_none()
+                || data.continuous_redraw
+                || std::mem::take(&mut data.redraw_requested)
+                || input_changed
+                || data.fetches.newly_finished()
+                || data.readbacks.newly_finished()
+                || data.timers.any_due();
+            (
+                redraw,
+                data.canvas.size != previous_canvas.0,
+                hovered_region,
+            )
+        };
+        if !redraw {
+            return;
+        }
+
+        // The budgets are refilled every frame, so they only cut short a single runaway frame
+        store
+            .store
+            .set_fuel(config.fuel_per_frame)
+            .expect("fuel should be enabled in get_wasm");
+        store.store.set_epoch_deadline(config.call_timeout_ms);
+        let started = std::time::Instant::now();
+
+        // Hooks share the frame's budgets with `update`
+        let hooks = wasm_resource.hooks;
+        if resized {
+            let data = store.store.data();
+            let size = data.canvas_config.visible_size(data.canvas.size);
+            if let Err(e) = call_hook(&mut store.store, hooks.on_resize, (size.x, size.y)) {
+                guest_failed(&mut commands, "on-resize", &e);
+                return;
+            }
+        }
+        let previous_region = store.store.data().hovered_region;
+        if hovered_region != previous_region {
+            store.store.data_mut().hovered_region = hovered_region;
+            if let Some(id) = previous_region {
+                if let Err(e) = call_hook(&mut store.store, hooks.on_region_leave, (id,)) {
+                    guest_failed(&mut commands, "on-region-leave", &e);
+                    return;
+                }
+            }
+            if let Some(id) = hovered_region {
+                if let Err(e) = call_hook(&mut store.store, hooks.on_region_enter, (id,)) {
+                    guest_failed(&mut commands, "on-region-enter", &e);
+                    return;
+                }
+            }
+        }
+        let cursor = store.store.data().inputs.last_cursor_position;
+        // Drags in progress follow the cursor to where it is this frame before this frame's
+        // presses and releases
+        let mut drags = Vec::new();
+        store
+            .store
+            .data_mut()
+            .inputs
+            .drags
+            .moved(cursor, &mut drags);
+        if let Err((name, e)) = call_drag_hooks(&mut store.store, &hooks, drags) {
+            guest_failed(&mut commands, name, &e);
+            return;
+        }
+        for event in mouse_button_events {
+            let mut drags = Vec::new();
+            let inputs = &mut store.store.data_mut().inputs;
+            match event.state {
+                ButtonState::Pressed => inputs.drags.press(event.button, cursor),
+                ButtonState::Released => inputs.drags.release(event.button, cursor, &mut drags),
+            }
+            if let Err((name, e)) = call_drag_hooks(&mut store.store, &hooks, drags) {
+                guest_failed(&mut commands, name, &e);
+                return;
+            }
+            let result = match event.state {
+                ButtonState::Pressed => {
+                    let count = store.store.data_mut().inputs.clicks.press(
+                        event.button,
+                        time.elapsed_seconds_f64(),
+                        cursor,
+                        config.multi_click_interval,
+                    );
+                    let params = (cursor.x, cursor.y, event.button.into(), count);
+                    call_hook(&mut store.store, hooks.on_mouse_down, params)
+                        .map_err(|e| ("on-mouse-down", e))
+                }
+                ButtonState::Released => {
+                    let params = (cursor.x, cursor.y, event.button.into());
+                    call_hook(&mut store.store, hooks.on_mouse_up, params)
+                        .map_err(|e| ("on-mouse-up", e))
+                }
+            };
+            if let Err((name, e)) = result {
+                guest_failed(&mut commands, name, &e);
+                return;
+            }
+        }
+        let modifiers = modifiers(&keys);
+        for (key, state) in key_events {
+            let (name, hook) = match state {
+                ButtonState::Pressed => ("on-key-down", hooks.on_key_down),
+                ButtonState::Released => ("on-key-up", hooks.on_key_up),
+            };
+            if let Err(e) = call_hook(&mut store.store, hook, (key.into(), modifiers)) {
+                guest_failed(&mut commands, name, &e);
+                return;
+            }
+        }
+        if scroll != Vec2::ZERO {
+            if let Err(e) = call_hook(&mut store.store, hooks.on_scroll, (scroll.x, scroll.y)) {
+                guest_failed(&mut commands, "on-scroll", &e);
+                return;
+            }
+        }
+        let due = store.store.data().timers.due();
+        for handle in due {
+            if !store.store.data_mut().timers.fire(handle) {
+                continue;
+            }
+            if let Err(e) = call_hook(&mut store.store, hooks.on_timer, (handle,)) {
+                guest_failed(&mut commands, "on-timer", &e);
+                return;
+            }
+        }
+
+        if let Err(e) =
+            wasm_resource
+                .bindings
+                .call_update(&mut store.store, delta_seconds, elapsed_seconds)
+        {
+            guest_failed(&mut commands, "update", &e);
+            return;
+        }
+        diagnostics.push_guest_time(started.elapsed());
+        store.store.data_mut().frame_ready = true;
+        wasm_resource.last_update_at = Some(now);
+    }
+}
+
+fn run_wasm_setup(
+    mut commands: Commands,
+    wasm_instance: Option<ResMut<WasmBindings>>,
+    wasm_store: Option<ResMut<WasmStore>>,
+    config: Res<PortalConfig>,
+    fonts: Res<Assets<Font>>,
+) {
+    if let Some(mut wasm_resource) = wasm_instance {
+        if wasm_resource.first_run {
+            wasm_resource.first_run = false;
+            let mut store = wasm_store.unwrap();
+            let data = store.store.data_mut();
+            data.allow_read = config.allow_read.clone();
+            data.text_measurer.default_font = fonts
+                .get(&Handle::<Font>::default())
+                .map(|font| font.font.clone());
+            store
+                .store
+                .set_fuel(config.fuel_per_frame)
+                .expect("fuel should be enabled in get_wasm");
+            store.store.set_epoch_deadline(config.call_timeout_ms);
+            let started = std::time::Instant::now();
+            if let Err(e) = wasm_resource.bindings.call_setup(&mut store.store) {
+                guest_failed(&mut commands, "setup", &e);
+                return;
+            }
+            // `setup` runs once, so it's logged rather than averaged in with the frames
+            eprintln!(
+                "guest setup took {:.2} ms",
+                started.elapsed().as_secs_f64() * 1000.
+            );
+        }
+    }
+}
+
+/// Why a guest call was cut short by one of the portal's budgets, if it was.
+fn budget_exceeded(error: &wasmtime::Error) -> Option<&'static str> {
+    if error.is::<MemoryLimitExceeded>() {
+        return Some("exceeded its memory limit");
+    }
+    match error.downcast_ref::<wasmtime::Trap>()? {
+        wasmtime::Trap::OutOfFuel => Some("ran out of fuel"),
+        wasmtime::Trap::Interrupt => Some("ran past its time limit"),
+        _ => None,
+    }
+}
+
+/// Unloads a guest after `call` returned an error, since a trap leaves its instance unusable.
+fn guest_failed(commands: &mut Commands, call: &str, error: &wasmtime::Error) {
+    eprintln!("guest {call} failed: {error:?}");
+    let mut reason = match budget_exceeded(error) {
+        Some(reason) => format!("{call} {reason}"),
+        None => format!("{call} trapped: {}", error.root_cause()),
+    };
+    // The whole backtrace goes to the console, and where it stopped to the status line
+    let backtrace = trap_backtrace(error);
+    let mut details = reason.clone();
+    for frame in &backtrace {
+        details.push_str(&format!("\n    at {frame}"));
+    }
+    commands.add(move |world: &mut World| {
+        let now = world.resource::<Time>().elapsed_seconds();
+        world.resource_scope(|world, mut console: Mut<Console>| {
+            // A panicking guest writes its message to stderr just before it traps, and the
+            // store it's in goes with the guest
+            if let Some(mut store) = world.get_resource_mut::<WasmStore>() {
+                print_guest_output(store.store.data_mut(), now, &mut console);
+            }
+            console.push(now, ConsoleSource::Log(LogLevel::Error), &details);
+        });
+    });
+    if let Some(innermost) = backtrace.first() {
+        reason.push_str(&format!(" in {innermost}"));
+    }
+    unload_guest(commands, &reason);
+}
+
+/// The guest functions a trap unwound through, innermost first. Functions are named from the
+/// guest's name section, and given a source location if it has DWARF debug info and
+/// `WASMTIME_BACKTRACE_DETAILS=1` is set.
+fn trap_backtrace(error: &wasmtime::Error) -> Vec<String> {
+    let Some(backtrace) = error.downcast_ref::<wasmtime::WasmBacktrace>() else {
+        return Vec::new();
+    };
+    backtrace
+        .frames()
+        .iter()
+        .map(|frame| {
+            let symbol = frame.symbols().first();
+            let name = match symbol
+                .and_then(|symbol| symbol.name())
+                .or(frame.func_name())
+            {
+                Some(name) => name.to_string(),
+                None => format!("function {}", frame.func_index()),
+            };
+            let location = symbol.and_then(|symbol| Some((symbol.file()?, symbol.line()?)));
+            match location {
+                Some((file, line)) => format!("{name} ({file}:{line})"),
+                None => name,
+            }
+        })
+        .collect()
+}
+
+/// Stops calling a guest that can't carry on. Its last frame stays on screen until it's reloaded
+/// or another guest is loaded.
+fn unload_guest(commands: &mut Commands, reason: &str) {
+    eprintln!("stopped the guest: {reason}");
+    commands.insert_resource(LoadStatus::Failed(format!(
+        "The guest stopped: its {reason}. Press F5 to reload it."
+    )));
+    commands.remove_resource::<WasmBindings>();
+    commands.remove_resource::<WasmStore>();
+}
+
+/// Port guests are fetched from over WebTransport unless the address names another one
+#[cfg(feature = "webtransport")]
+const DEFAULT_WEBTRANSPORT_PORT: u16 = 4433;
+
+/// The port written in `url`, even one that's the default for its scheme, which `Url::port`
+/// leaves out.
+#[cfg(feature = "webtransport")]
+fn typed_port(url: &str) -> Option<u16> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // An IPv6 host has colons of its own, so only look past its closing bracket
+    let after_host = match host_port.strip_prefix('[') {
+        Some(ipv6) => ipv6.split_once(']')?.1,
+        None => host_port,
+    };
+    after_host.rsplit_once(':')?.1.parse().ok()
+}
+
+/// How often download progress is reported, since every report waits for the main thread
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+async fn report_progress(
+    ctx: &mut bevy_tokio_tasks::TaskContext,
+    received: u64,
+    total: Option<u64>,
+) {
+    ctx.run_on_main_thread(move |ctx| {
+        ctx.world
+            .insert_resource(LoadStatus::Loading { received, total })
+    })
+    .await;
+}
+
+/// Set to cancel the latest load, which gives up at the next chunk it receives
+#[derive(Resource)]
+struct LoadAbort(std::sync::Arc<AtomicBool>);
+
+/// The error a load fails with once it's been cancelled
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("the load was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The error a load fails with when its server stops answering, saying what it was waiting for
+#[derive(Debug)]
+struct TimedOut(&'static str);
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "server timed out {}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// The error a load fails with when the connection to its server breaks
+#[derive(Debug)]
+struct ConnectionFailed(String);
+
+impl std::fmt::Display for ConnectionFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConnectionFailed {}
+
+/// Whether a failed download might work if it's tried again: the connection failed or timed
+/// out, rather than the server answering with something that isn't a component.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    if error.is::<TimedOut>() || error.is::<ConnectionFailed>() {
+        return true;
+    }
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(e) => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+        None => false,
+    }
+}
+
+/// Waits for `future`, failing with `TimedOut` if it takes longer than `timeout`, which drops it.
+async fn with_timeout<T>(
+    timeout: Duration,
+    waiting_for: &'static str,
+    future: impl std::future::Future<Output = T>,
+) -> Result<T, TimedOut> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| TimedOut(waiting_for))
+}
+
+fn check_cancelled(abort: &AtomicBool) -> Result<(), Cancelled> {
+    if abort.load(Ordering::Relaxed) {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Cancels the load in progress on Escape.
+fn handle_abort(keys: Res<Input<KeyCode>>, status: Res<LoadStatus>, abort: Option<Res<LoadAbort>>) {
+    let loading = matches!(
+        *status,
+        LoadStatus::Loading { .. } | LoadStatus::Retrying { .. }
+    );
+    if !keys.just_pressed(KeyCode::Escape) || !loading {
+        return;
+    }
+    if let Some(abort) = abort {
+        abort.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Who started a load, which decides whether it may open a file on the user's disk.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LoadSource {
+    /// Typed in the address bar, or reloaded, reached through history or allowed by the user
+    User,
+    /// A guest's link or `navigate` call
+    Guest,
+}
+
+/// Loads the component at `url` on a background task, reporting how it went in `LoadStatus`.
+fn spawn_get_wasm(
+    runtime: &TokioTasksRuntime,
+    url: String,
+    canvas: Canvas,
+    config: PortalConfig,
+    source: LoadSource,
+) {
+    runtime.spawn_background_task(move |mut ctx| async move {
+        let abort = std::sync::Arc::new(AtomicBool::new(false));
+        let token = abort.clone();
+        // The guest goes to the tab that was showing when the load started
+        let tab = ctx
+            .run_on_main_thread(move |ctx| {
+                ctx.world.insert_resource(LoadAbort(token));
+                ctx.world.resource::<Tabs>().active_id()
+            })
+            .await;
+        report_progress(&mut ctx, 0, None).await;
+        let loaded = get_wasm(&mut ctx, url.clone(), canvas, config, source, &abort, tab).await;
+        let status = match loaded {
+            Ok(_) => LoadStatus::Ready,
+            Err(e) if e.is::<Cancelled>() => {
+                eprintln!("cancelled loading '{url}'");
+                LoadStatus::Cancelled
+            }
+            Err(e) => {
+                eprintln!("failed to get wasm for '{url}': {e}");
+                LoadStatus::Failed(format!("Failed to load {url}: {e}"))
+            }
+        };
+        ctx.run_on_main_thread(move |ctx| {
+            if ctx.world.resource::<Tabs>().active_id() == tab {
+                ctx.world.insert_resource(status)
+            }
+        })
+        .await;
+    });
+}
+
+enum Fetched {
+    Component {
+        bytes: Vec<u8>,
+        etag: Option<String>,
+        /// The `Content-Encoding` the server sent, see `compression::decompress`
+        encoding: Option<String>,
+    },
+    NotModified,
+}
+
+/// Downloads the still compressed component at `valid_url`, and with an `etag` only if it
+/// changed since.
+///
+/// WebTransport has no way to ask whether a component changed, so it's always downloaded and
+/// tagged with a hash of its contents instead.
+async fn fetch_component(
+    ctx: &mut bevy_tokio_tasks::TaskContext,
+    valid_url: &str,
+    config: &PortalConfig,
+    #[cfg_attr(feature = "webtransport", allow(unused_variables))] etag: Option<&str>,
+    abort: &AtomicBool,
+) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let initial_buffer_size = 65536;
+    let mut buffer = Vec::with_capacity(initial_buffer_size);
+    let response_etag;
+    let encoding;
+    let mut last_report = std::time::Instant::now();
+
+    #[cfg(feature = "webtransport")]
+    {
+        use connections::WebTransportConnections;
+        use url::Url;
+        use wtransport::ClientConfig;
+        use wtransport::Endpoint;
+
+        let uri = Url::parse(valid_url).map_err(|e| format!("invalid URL '{valid_url}': {e}"))?;
+        let host = uri
+            .host_str()
+            .ok_or_else(|| format!("URL '{valid_url}' has no host"))?;
+        let path = uri.path();
+        // `Url` drops a port that matches the scheme's default, so the port is read from the
+        // address as it was typed: an explicit `:443` means 443. IPv6 hosts keep their brackets.
+        let port = typed_port(valid_url).unwrap_or(DEFAULT_WEBTRANSPORT_PORT);
+        let builder = ClientConfig::builder().with_bind_default();
+        let builder = match &config.tls_mode {
+            TlsMode::NoValidation => builder.with_no_cert_validation(),
+            TlsMode::SystemRoots => builder.with_native_certs(),
+            TlsMode::PinnedCert(path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                roots.add(&rustls::Certificate(load_certificate(path)?))?;
+                let mut tls = rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth();
+                tls.alpn_protocols = vec![b"h3".to_vec()];
+                builder.with_custom_tls(tls)
+            }
+        };
+        let client_config = builder.enable_key_log().build();
+        let address = format!("https://{}:{}{}", host, port, path);
+        let connect = async {
+            let endpoint = Endpoint::client(client_config)
+                .map_err(|e| format!("failed to create a WebTransport endpoint: {e}"))?;
+            let connection = endpoint
+                .connect(&address)
+                .await
+                .map_err(|e| format!("failed to connect to {address}: {e}"))?;
+            Ok::<_, String>((endpoint, connection))
+        };
+        // Reloads reuse the connection the last load to this server opened
+        let connections = ctx
+            .run_on_main_thread(|ctx| {
+                ctx.world
+                    .get_resource_or_insert_with(WebTransportConnections::default)
+                    .clone()
+            })
+            .await;
+        let server = format!("{host}:{port}");
+        let opening = connections.open_stream(&server, connect);
+        let mut stream = with_timeout(config.connect_timeout, "connecting", opening)
+            .await?
+            .map_err(|e| ConnectionFailed(format!("failed to open a stream to {address}: {e}")))?;
+        stream.0.write_all(b"WASM").await.map_err(|e| {
+            ConnectionFailed(format!("failed to ask {address} for the component: {e}"))
+        })?;
+
+        loop {
+            let mut chunk = vec![0; 65536];
+            let reading = stream.1.read(&mut chunk);
+            let read =
+                match with_timeout(config.read_timeout, "sending the component", reading).await {
+                    Ok(read) => read.map_err(|e| ConnectionFailed(e.to_string()).into()),
+                    Err(timed_out) => Err(timed_out.into()),
+                };
+            let read = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    // The kept connection may be what's broken, so the next load opens a new one
+                    connections.forget(&server);
+                    return Err(e);
+                }
+            };
+            match read {
+                Some(bytes_read) => {
+                    check_cancelled(abort)?;
+                    buffer.extend_from_slice(&chunk[..bytes_read]);
+                    if last_report.elapsed() >= PROGRESS_INTERVAL {
+                        report_progress(ctx, buffer.len() as u64, None).await;
+                        last_report = std::time::Instant::now();
+                    }
+                }
+                None => break, // End of stream
+            }
+        }
+        response_etag = Some(content_hash(&buffer));
+        encoding = None;
+    }
+
+    #[cfg(not(feature = "webtransport"))]
+    {
+        let mut request = http_client(&config.tls_mode)?.get(valid_url).header(
+            reqwest::header::ACCEPT_ENCODING,
+            compression::ACCEPT_ENCODING,
+        );
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let mut response =
+            with_timeout(config.connect_timeout, "connecting", request.send()).await??;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+        response_etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from);
+        encoding = response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|encoding| encoding.to_str().ok())
+            .map(String::from);
+        let total = response.content_length();
+        while let Some(chunk) = with_timeout(
+            config.read_timeout,
+            "sending the component",
+            response.chunk(),
+        )
+        .await??
+        {
+            check_cancelled(abort)?;
+            buffer.extend_from_slice(&chunk);
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                report_progress(ctx, buffer.len() as u64, total).await;
+                last_report = std::time::Instant::now();
+            }
+        }
+    }
+
+    if buffer.is_empty() {
+        return Err(
+            format!("{valid_url} closed the connection without sending a component").into(),
+        );
+    }
+    Ok(Fetched::Component {
+        bytes: buffer,
+        etag: response_etag,
+        encoding,
+    })
+}
+
+/// `fetch_component`, tried up to `load_attempts` times while the connection keeps failing, with
+/// the delay before each retry doubling from `retry_delay`.
+async fn fetch_with_retries(
+    ctx: &mut bevy_tokio_tasks::TaskContext,
+    valid_url: &str,
+    config: &PortalConfig,
+    etag: Option<&str>,
+    abort: &AtomicBool,
+) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let mut attempt = 1;
+    loop {
+        match fetch_component(ctx, valid_url, config, etag, abort).await {
+            Err(e) if attempt < config.load_attempts && is_transient(&*e) => {
+                let delay = config.retry_delay * 2u32.saturating_pow(attempt - 1);
+                eprintln!("retrying '{valid_url}' in {delay:?}: {e}");
+                attempt += 1;
+                let attempts = config.load_attempts;
+                ctx.run_on_main_thread(move |ctx| {
+                    ctx.world
+                        .insert_resource(LoadStatus::Retrying { attempt, attempts })
+                })
+                .await;
+                tokio::time::sleep(delay).await;
+                check_cancelled(abort)?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// An HTTP client that checks certificates as `tls_mode` says.
+fn http_client(tls_mode: &TlsMode) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    Ok(http_client_builder(tls_mode)?.build()?)
+}
+
+/// A builder for an HTTP client that checks certificates as `tls_mode` says, for clients that
+/// need more set up than `http_client` does.
+fn http_client_builder(
+    tls_mode: &TlsMode,
+) -> Result<reqwest::ClientBuilder, Box<dyn std::error::Error>> {
+    let builder = reqwest::Client::builder();
+    Ok(match tls_mode {
+        TlsMode::NoValidation => builder.danger_accept_invalid_certs(true),
+        TlsMode::SystemRoots => builder,
+        TlsMode::PinnedCert(path) => builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_der(&load_certificate(path)?)?),
+    })
+}
+
+async fn get_wasm(
+    ctx: &mut bevy_tokio_tasks::TaskContext,
+    url: String,
+    canvas: Canvas,
+    config: PortalConfig,
+    source: LoadSource,
+    abort: &AtomicBool,
+    tab: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let valid_url = make_url_valid(url);
+
+    let engine = guest_engine()?;
+
+    let component = match local_path(&valid_url) {
+        // Local components may be precompiled, and deserializing one runs whatever it contains
+        Some(_) if source == LoadSource::Guest => {
+            return Err(format!("guests can't open local files like {valid_url}").into());
+        }
+        Some(path) => load_local(&engine, &path)?,
+        None => download_component(ctx, &engine, &valid_url, &config, abort).await?,
+    };
+
+    let (store, bindings) = instantiate_guest(engine, &component, &valid_url, canvas, &config)?;
+    // Compiling and instantiating can take a while, so a cancel may come in after the download
+    check_cancelled(abort)?;
+    ctx.run_on_main_thread(move |ctx| {
+        // A tab that was switched away from while it loaded gets the guest in the background, and
+        // one that was closed doesn't get it at all
+        let config = ctx.world.resource::<PortalConfig>().clone();
+        if ctx.world.resource::<Tabs>().active_id() != tab {
+            let old_scene = ctx
+                .world
+                .resource_mut::<Tabs>()
+                .background_mut(tab)
+                .map(|background| background.loaded(store, bindings, valid_url, &config))
+                .unwrap_or_default();
+            for entity in old_scene.entities() {
+                if let Some(entity) = ctx.world.get_entity_mut(entity) {
+                    entity.despawn_recursive();
+                }
+            }
+            return;
+        }
+        // The old guest gets to save its state before the new one replaces it
+        if let (Some(mut old_store), Some(old_bindings)) = (
+            ctx.world.remove_resource::<WasmStore>(),
+            ctx.world.remove_resource::<WasmBindings>(),
+        ) {
+            run_on_unload(&mut old_store, &old_bindings, &config);
+        }
+        // The new guest starts from a blank canvas rather than from the old guest's last frame,
+        // layers included, whose cameras go with them
+        let old_scene = ctx
+            .world
+            .get_resource_mut::<GuestScene>()
+            .map(|mut scene| std::mem::take(&mut *scene))
+            .unwrap_or_default();
+        for entity in old_scene.entities() {
+            if let Some(entity) = ctx.world.get_entity_mut(entity) {
+                entity.despawn_recursive();
+            }
+        }
+        // Nor does it hear the old guest's sounds and music
+        stop_guest_sounds(ctx.world);
+        ctx.world.resource_mut::<Diagnostics>().clear_guest_times();
+        ctx.world.resource_mut::<History>().visited(&valid_url);
+        ctx.world.insert_resource(LastLoadedUrl(valid_url));
+        ctx.world.insert_resource(bindings);
+        ctx.world.insert_resource(store);
+    })
+    .await;
+
+    Ok(())
+}
+
+/// Downloads the component at `valid_url`, or takes it from the cache if it's unchanged there.
+async fn download_component(
+    ctx: &mut bevy_tokio_tasks::TaskContext,
+    engine: &Engine,
+    valid_url: &str,
+    config: &PortalConfig,
+    abort: &AtomicBool,
+) -> Result<Component, Box<dyn std::error::Error>> {
+    let cache = config
+        .cache_dir
+        .as_deref()
+        .map(|dir| ComponentCache::new(dir, valid_url));
+    let (cached_component, cached_etag) = match cache.as_ref().and_then(|c| c.load(engine)) {
+        Some((component, etag)) => (Some(component), etag),
+        None => (None, None),
+    };
+    let fetched = fetch_with_retries(ctx, valid_url, config, cached_etag.as_deref(), abort).await?;
+    match (fetched, cached_component) {
+        (Fetched::NotModified, Some(component)) => Ok(component),
+        // Downloaded again, but unchanged, so compiling it can still be skipped
+        (Fetched::Component { etag, .. }, Some(component))
+            if etag.is_some() && etag == cached_etag =>
+        {
+            Ok(component)
+        }
+        (Fetched::NotModified, None) => {
+            Err(format!("{valid_url} wasn't modified, but it isn't cached either").into())
+        }
+        (
+            Fetched::Component {
+                bytes,
+                etag,
+                encoding,
+            },
+            _,
+        ) => {
+            let decoded_input = compression::decompress(&bytes, encoding.as_deref())
+                .map_err(|e| format!("{valid_url}: {e}"))?;
+            let component = Component::new(engine, decoded_input)?;
+            if let Some(cache) = &cache {
+                if let Err(e) = cache.store(&component, etag.as_deref()) {
+                    eprintln!("failed to cache {valid_url}: {e}");
+                }
+            }
+            Ok(component)
+        }
+    }
+}
+
+/// Reads a component from disk, for guest developers trying out a build without serving it.
+///
+/// Local components are neither cached nor need compressing, but a compressed one is decoded as
+/// if it had been downloaded. A precompiled `.cwasm` made for another Wasmtime build or engine
+/// configuration is compiled from the `.wasm` next to it instead, if there is one.
+fn load_local(engine: &Engine, path: &Path) -> Result<Component, Box<dyn std::error::Error>> {
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))
+    };
+    let bytes = read(path)?;
+    match compile_component(engine, &bytes) {
+        Err(e) if Engine::detect_precompiled(&bytes).is_some() => {
+            let source = path.with_extension("wasm");
+            if source == path || !source.is_file() {
+                return Err(format!("{}: {e}", path.display()).into());
+            }
+            eprintln!(
+                "{}: {e}, compiling {} instead",
+                path.display(),
+                source.display()
+            );
+            compile_component(engine, &read(&source)?)
+                .map_err(|e| format!("{}: {e}", source.display()).into())
+        }
+        result => result.map_err(|e| format!("{}: {e}", path.display()).into()),
+    }
+}
+
+/// Compiles a component, which may be compressed, or deserializes it if it was precompiled with
+/// `wasmtime compile` or `Component::serialize`, which Wasmtime recognizes by its header.
+///
+/// Only components the user chose may be precompiled: deserializing runs their machine code as it
+/// is, so one a guest picked could do anything the portal can.
+fn compile_component(engine: &Engine, bytes: &[u8]) -> Result<Component, String> {
+    match Engine::detect_precompiled(bytes) {
+        // SAFETY: this is only reached for files the user typed into the address bar or passed
+        // with `--run`, since `get_wasm` refuses local files for `LoadSource::Guest` loads and
+        // downloads never get here. `deserialize` rejects components from another Wasmtime
+        // version or engine configuration.
+        Some(Precompiled::Component) => unsafe { Component::deserialize(engine, bytes) }
+            .map_err(|e| format!("can't use this precompiled component: {e}")),
+        Some(Precompiled::Module) => Err("this is a precompiled module, not a component".into()),
+        None => {
+            let decoded_input = compression::decompress(bytes, None)?;
+            Component::new(engine, decoded_input).map_err(|e| format!("{e:#}"))
+        }
+    }
+}
+
+/// An engine for running guests, with the fuel and epochs their budgets need.
+fn guest_engine() -> wasmtime::Result<Engine> {
+    let mut engine_config = Config::new();
+    engine_config
+        .wasm_component_model(true)
+        .async_support(false)
+        .consume_fuel(true)
+        .epoch_interruption(true);
+    Engine::new(&engine_config)
+}
+
+/// Milliseconds a component's start functions may run for while it's instantiated.
+const INSTANTIATE_TIMEOUT_MS: u64 = 1_000;
+
+/// Instantiates `component` as the guest served from `valid_url`, ready for its `setup`.
+///
+/// Must be called on the Tokio runtime, which the guest's fetches run on.
+fn instantiate_guest(
+    engine: Engine,
+    component: &Component,
+    valid_url: &str,
+    canvas: Canvas,
+    config: &PortalConfig,
+) -> Result<(WasmStore, WasmBindings), Box<dyn std::error::Error>> {
+    // Set up Wasmtime linker
+    let mut linker = Linker::new(&engine);
+    sync::add_to_linker(&mut linker)?;
+    let table = Table::new();
+    let stdout = GuestOutput::default();
+    let stderr = GuestOutput::default();
+    let mut guest_args = vec![valid_url.to_string()];
+    guest_args.extend(config.guest_args.iter().cloned());
+    let capabilities = config.reader_hosts.capabilities(valid_url);
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.insecure_random_seed(config.random_seed.into())
+        .args(&guest_args)
+        .envs(&guest_env(valid_url, &config.guest_env))
+        .stdout(stdout.clone())
+        .stderr(stderr.clone());
+    // WASI has no way to trap a guest opening files, so in reader mode it just has none
+    let files = match capabilities {
+        Capabilities::Full => open_guest_files(config, valid_url),
+        Capabilities::Reader => None,
+    };
+    if let Some(dir) = files {
+        let (dir_perms, file_perms) = if config.writable_files {
+            (DirPerms::all(), FilePerms::all())
+        } else {
+            (DirPerms::READ, FilePerms::READ)
+        };
+        wasi.preopened_dir(dir, dir_perms, file_perms, "/");
+    }
+    let wasi = wasi.build();
+    MyWorld::add_to_linker(&mut linker, |state: &mut MyCtx| state)?;
+    // Set up Wasmtime store
+    let mut store = Store::new(
+        &engine,
+        MyCtx {
+            table,
+            wasi,
+            queue: Vec::new(),
+            draw_state: DrawState::default(),
+            saved_draw_states: Vec::new(),
+            frame_ready: false,
+            redraw_requested: false,
+            continuous_redraw: false,
+            show_diagnostics: None,
+            delta_seconds: 0.0,
+            limits: config.limits,
+            inputs: Default::default(),
+            canvas,
+            scale_factor: 1.,
+            allow_read: None,
+            printed: Vec::new(),
+            stdout,
+            stderr,
+            colors: ColorCache::default(),
+            gradients: Vec::new(),
+            patterns: Vec::new(),
+            images: Vec::new(),
+            pending_images: Vec::new(),
+            image_pixels: 0,
+            fonts: Vec::new(),
+            pending_fonts: Vec::new(),
+            layers: Vec::new(),
+            text_measurer: TextMeasurer::default(),
+            font: FontChoice::default(),
+            saved_fonts: Vec::new(),
+            capabilities,
+            host: capabilities::host_key(valid_url),
+            permissions: config.permissions.clone(),
+            storage: match capabilities {
+                Capabilities::Full => GuestStorage::open(&config.storage_dir, valid_url),
+                Capabilities::Reader => None,
+            },
+            fetches: GuestFetches::new(
+                tokio::runtime::Handle::current(),
+                http_client_builder(&config.tls_mode)?,
+                valid_url,
+            )?,
+            readbacks: GuestReadbacks::default(),
+            navigation: None,
+            hovered_region: None,
+            audio: GuestAudio::default(),
+            timers: GuestTimers::default(),
+            canvas_config: config.canvas,
+            random: GuestRandom::new(config.random_seed),
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    // Instantiating runs the component's start functions, which get a frame's fuel like `setup`
+    // but more time, since this background task can't freeze the app. A start function that
+    // never returns still has to fail, or it would keep a Tokio worker forever. `setup` and
+    // `update` get a fresh budget before every call.
+    let epoch_ticker = EpochTicker::spawn(engine);
+    store.set_fuel(config.fuel_per_frame)?;
+    store.set_epoch_deadline(INSTANTIATE_TIMEOUT_MS);
+    let instance = match linker.instantiate(&mut store, component) {
+        Ok(instance) => instance,
+        Err(e) => match budget_exceeded(&e) {
+            Some(reason) => return Err(format!("its start function {reason}").into()),
+            None => return Err(e.into()),
+        },
+    };
+    check_world_exports(&mut store, &instance)?;
+    let bindings = MyWorld::new(&mut store, &instance)
+        .map_err(|e| format!("this component doesn't implement my-world: {e:#}"))?;
+    let hooks = GuestHooks::new(&mut store, &instance)?;
+    let bindings = WasmBindings {
+        bindings,
+        hooks,
+        first_run: true,
+        started_at: None,
+        last_update_at: None,
+    };
+    let store = WasmStore {
+        store,
+        _epoch_ticker: epoch_ticker,
+    };
+    Ok((store, bindings))
+}
+
+/// Fails with the names of the `my-world` functions the guest doesn't export, which the bindings
+/// would only report one at a time, and in terms of wasmtime's own lookup.
+fn check_world_exports(store: &mut Store<MyCtx>, instance: &Instance) -> Result<(), String> {
+    let mut exports = instance.exports(store);
+    let mut root = exports.root();
+    let missing: Vec<&str> = ["setup", "update"]
+        .into_iter()
+        .filter(|name| root.func(name).is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "this component doesn't implement my-world: it doesn't export {}",
+        missing.join(" or ")
+    ))
+}
+
+/// The directory of files the host `url` is served from shares with its guests, created if it
+/// doesn't exist yet. `None`, leaving the guest without a filesystem, for URLs without a host or
+/// if the directory can't be opened.
+fn open_guest_files(config: &PortalConfig, url: &str) -> Option<cap_std::fs::Dir> {
+    let path = config.files_dir.join(storage::host_file_name(url)?);
+    let dir = std::fs::create_dir_all(&path)
+        .and_then(|_| cap_std::fs::Dir::open_ambient_dir(&path, cap_std::ambient_authority()));
+    match dir {
+        Ok(dir) => Some(dir),
+        Err(e) => {
+            eprintln!("not giving the guest {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// The environment a guest at `url` gets: the portal's `--guest-env` variables, then one for each
+/// parameter in the URL's query string, so `example.com/app.wasm?theme=dark` sets `THEME=dark`.
+///
+/// Parameter names are upper-cased, with anything but ASCII letters, digits and underscores
+/// replaced by underscores. A name given twice keeps its last value.
+fn guest_env(url: &str, configured: &[(String, String)]) -> Vec<(String, String)> {
+    let mut env = configured.to_vec();
+    if let Ok(url) = Url::parse(url) {
+        for (name, value) in url.query_pairs() {
+            let name: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            if !name.is_empty() {
+                env.push((name, value.into_owned()));
+            }
+        }
+    }
+    let mut deduplicated: Vec<(String, String)> = Vec::with_capacity(env.len());
+    for (name, value) in env {
+        deduplicated.retain(|(existing, _)| *existing != name);
+        deduplicated.push((name, value));
+    }
+    deduplicated
+}
+
+/// Reads the first certificate from a PEM file, or the whole file as DER if it isn't PEM.
+fn load_certificate(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("failed to read certificate {}: {e}", path.display()))?;
+    match rustls_pemfile::certs(&mut bytes.as_slice()) {
+        Ok(certs) if !certs.is_empty() => Ok(certs.into_iter().next().unwrap()),
+        _ => Ok(bytes),
+    }
+}
+
+#[cfg(feature = "webtransport")]
+fn content_hash(bytes: &[u8]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn canonicalize_path(path: &Path) -> Result<PathBuf, String> {
+    Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Error canonicalizing path {}: {}", path.display(), e))
+}
+
+fn is_path_within_allowed_directory(allowed_path: &Path, target_path: &Path) -> bool {
+    target_path.starts_with(allowed_path)
+}
+
+/// Makes what was typed in the address bar a URL: paths to files that exist become `file://`
+/// URLs, and anything else without a scheme is taken to be served over HTTPS.
+fn make_url_valid(url: String) -> String {
+    if url.starts_with("http") || url.starts_with("file://") {
+        url
+    } else if let Some(file_url) = local_file_url(&url) {
+        file_url
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+/// Makes a URL a guest asked to go to valid like `make_url_valid`, except that only `http(s)`
+/// URLs and bare hosts are taken: a guest mustn't open what's on the user's disk.
+fn guest_navigation_url(url: &str) -> Option<String> {
+    let url = if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("https://{url}")
+    };
+    Url::parse(&url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(String::from)
+}
+
+fn local_file_url(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    if !path.is_file() {
+        return None;
+    }
+    Url::from_file_path(path.canonicalize().ok()?)
+        .ok()
+        .map(String::from)
+}
+
+/// The file a `file://` URL points at, `None` for other URLs.
+fn local_path(valid_url: &str) -> Option<PathBuf> {
+    Url::parse(valid_url)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_prototype_lyon::prelude::tess::path::PathEvent;
+
+    /// A guest that exports `setup` and `update` and does nothing, for tests that queue host
+    /// events on its context themselves.
+    const IDLE_GUEST: &str = r#"
+    (component
+      (core module $guest (func (export "setup")) (func (export "update") (param f32 f32)))
+      (core instance $guest (instantiate $guest))
+      (func (export "setup") (canon lift (core func $guest "setup")))
+      (func (export "update") (param "delta-seconds" float32) (param "elapsed-seconds" float32)
+        (canon lift (core func $guest "update"))))
+    "#;
+
+    /// A guest whose core module's start function loops forever.
+    const LOOPING_GUEST: &str = r#"
+    (component
+      (core module $guest
+        (func $start (loop $forever (br $forever)))
+        (start $start)
+        (func (export "setup"))
+        (func (export "update") (param f32 f32)))
+      (core instance $guest (instantiate $guest))
+      (func (export "setup") (canon lift (core func $guest "setup")))
+      (func (export "update") (param "delta-seconds" float32) (param "elapsed-seconds" float32)
+        (canon lift (core func $guest "update"))))
+    "#;
+
+    /// The guest `wat` on an 800x600 canvas, in a portal started with `args`, with the runtime its
+    /// fetches would run on, or why it couldn't be instantiated.
+    fn instantiate(
+        wat: &str,
+        args: &[&str],
+    ) -> (tokio::runtime::Runtime, Result<WasmStore, String>) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let guard = runtime.enter();
+        let args = Args::parse_from(["portal", "--no-cache"].iter().chain(args));
+        let config = PortalConfig::from(&args);
+        let engine = guest_engine().unwrap();
+        let component = Component::new(&engine, wat).unwrap();
+        let canvas = Canvas {
+            size: Vec2::new(800., 600.),
+            position: Vec2::ZERO,
+        };
+        let store = instantiate_guest(engine, &component, "file:///guest.wasm", canvas, &config)
+            .map(|(store, _)| store)
+            .map_err(|e| e.to_string());
+        drop(guard);
+        (runtime, store)
+    }
+
+    /// An idle guest, see `instantiate`.
+    fn idle_guest(args: &[&str]) -> (tokio::runtime::Runtime, WasmStore) {
+        let (runtime, store) = instantiate(IDLE_GUEST, args);
+        (runtime, store.unwrap())
+    }
+
+    /// The frame `draw` resolves to on an idle guest started with `args`.
+    fn resolve(args: &[&str], draw: impl FnOnce(&mut MyCtx)) -> ResolvedFrame {
+        let (_runtime, mut store) = idle_guest(args);
+        let data = store.store.data_mut();
+        draw(data);
+        let mut app = App::new();
+        app.add_plugins((bevy::MinimalPlugins, bevy::asset::AssetPlugin::default()));
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        resolve_draw_commands(
+            data,
+            &Camera::default(),
+            &GlobalTransform::IDENTITY,
+            &mut GuestFonts::default(),
+            &asset_server,
+        )
+    }
+
+    #[test]
+    fn fill_rect_is_centered_on_its_rectangle() {
+        let frame = resolve(&["--origin", "top-left"], |data| {
+            data.fill_rect(10., 20., 30., 40.).unwrap();
+        });
+        let [DrawCommand::Shape(shape)] = &frame.screen[..] else {
+            panic!("expected one shape");
+        };
+        assert_eq!(shape.geometry, Geometry::Rect(Vec2::new(30., 40.)));
+        // The rectangle's center, (25, 40) from the top left, is 375 left of the middle of the
+        // canvas and 260 above it
+        let center = shape.transform.translation.truncate();
+        assert!(center.abs_diff_eq(Vec2::new(-375., 260.), 1e-4), "{center}");
+    }
+
+    #[test]
+    fn arc_without_a_rect_before_it_is_where_the_guest_put_it() {
+        let path = build_path(&[
+            PathCommand::Begin,
+            PathCommand::Arc(Arc {
+                x: 50.,
+                y: -20.,
+                radius: 10.,
+                sweep_angle: std::f32::consts::TAU,
+                x_rotation: 0.,
+            }),
+        ]);
+        // A whole circle around (50, -20), starting on its right, with no line from anywhere else
+        let bounds = path_bounds(&path);
+        let circle = Rect::from_center_size(Vec2::new(50., -20.), Vec2::splat(20.));
+        assert!(bounds.min.abs_diff_eq(circle.min, 0.5), "{bounds:?}");
+        assert!(bounds.max.abs_diff_eq(circle.max, 0.5), "{bounds:?}");
+        let first = path.0.iter().next();
+        assert!(
+            matches!(first, Some(PathEvent::Begin { at }) if at.x == 60. && at.y == -20.),
+            "{first:?}"
+        );
+    }
+
+    /// A PNG that's only a header saying it's `width` by `height`, with no pixels.
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chunk = |kind: &[u8], data: &[u8]| {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let mut crc = flate2::Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        };
+        // 8-bit RGBA
+        let header = [
+            &width.to_be_bytes()[..],
+            &height.to_be_bytes(),
+            &[8, 6, 0, 0, 0],
+        ]
+        .concat();
+        chunk(b"IHDR", &header);
+        chunk(b"IDAT", &[]);
+        chunk(b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn load_image_fails_for_images_too_big_to_decode() {
+        let (_runtime, mut store) = idle_guest(&[]);
+        let data = store.store.data_mut();
+        let loaded = data.load_image(png_header(30_000, 30_000)).unwrap();
+        assert_eq!(
+            loaded,
+            Err("images are at most 4096 pixels across, not 30000x30000".to_string())
+        );
+        data.image_pixels = MAX_IMAGE_PIXELS - 1;
+        let loaded = data.load_image(png_header(1, 2)).unwrap();
+        assert_eq!(
+            loaded,
+            Err("the guest's images would add up to over 134217728 pixels".to_string())
+        );
+        assert!(data.images.is_empty() && data.pending_images.is_empty());
+    }
+
+    #[test]
+    fn start_function_that_never_returns_runs_out_of_fuel() {
+        let (_runtime, store) = instantiate(LOOPING_GUEST, &["--fuel-per-frame", "1000000"]);
+        assert_eq!(
+            store.err().as_deref(),
+            Some("its start function ran out of fuel")
+        );
+    }
+
+    #[test]
+    fn guest_failed_says_the_guest_ran_out_of_fuel() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Console>();
+        let mut queue = bevy::ecs::system::CommandQueue::default();
+        let error = wasmtime::Error::new(wasmtime::Trap::OutOfFuel);
+        guest_failed(&mut Commands::new(&mut queue, &world), "update", &error);
+        queue.apply(&mut world);
+        let LoadStatus::Failed(message) = world.resource::<LoadStatus>() else {
+            panic!("the guest should have been unloaded");
+        };
+        assert!(message.contains("update ran out of fuel"), "{message}");
+    }
+}
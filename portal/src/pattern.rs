@@ -0,0 +1,146 @@
+use crate::levo::portal::my_imports::PatternRepeat;
+use bevy::asset::{load_internal_asset, Asset, Assets, Handle};
+use bevy::math::{Mat4, Vec2};
+use bevy::prelude::{
+    Added, App, Commands, Component, Entity, Image, Plugin, PostUpdate, Query, Res, ResMut, Shader,
+    Transform,
+};
+use bevy::reflect::TypePath;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef, ShaderType};
+use bevy::sprite::{Material2d, Material2dPlugin};
+
+const PATTERN_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0xd64a_0f1b_93c8_4e27_b5a0_2e7f_c81d_6394);
+
+/// Draws shapes that carry a `PatternFill`.
+///
+/// Like gradient filled shapes, pattern filled ones are tessellated white and drawn with
+/// `PatternMaterial`, whose shader tiles the image across the shape.
+pub struct PatternPlugin;
+
+impl Plugin for PatternPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            PATTERN_SHADER_HANDLE,
+            "pattern.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(Material2dPlugin::<PatternMaterial>::default())
+            .add_systems(PostUpdate, prepare_pattern_fills);
+    }
+}
+
+/// An image from `create_pattern` repeated across a fill.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub image: Handle<Image>,
+    pub repeat: PatternRepeat,
+    /// Where the top left corner of the first tile is, at the origin of the coordinates shapes
+    /// are drawn in until it's translated
+    pub offset: Vec2,
+    /// Whether the image's top is towards positive y, for guests whose y goes up
+    pub flip_y: bool,
+    pub alpha: f32,
+}
+
+impl Pattern {
+    pub fn new(image: Handle<Image>, repeat: PatternRepeat, flip_y: bool) -> Self {
+        Self {
+            image,
+            repeat,
+            offset: Vec2::ZERO,
+            flip_y,
+            alpha: 1.,
+        }
+    }
+
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha *= alpha;
+        self
+    }
+
+    /// The same pattern in coordinates shifted by `offset`.
+    pub fn translated(mut self, offset: Vec2) -> Self {
+        self.offset += offset;
+        self
+    }
+}
+
+/// Fills the shape's mesh with a pattern given in the mesh's local coordinates.
+#[derive(Component)]
+pub struct PatternFill(pub Pattern);
+
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+pub struct PatternMaterial {
+    #[uniform(0)]
+    pattern: PatternUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    image: Handle<Image>,
+}
+
+impl Material2d for PatternMaterial {
+    fn fragment_shader() -> ShaderRef {
+        PATTERN_SHADER_HANDLE.into()
+    }
+}
+
+/// Mirrors `Pattern` in `pattern.wgsl`.
+#[derive(ShaderType, Clone, Default, Debug)]
+struct PatternUniform {
+    /// Takes fragments back to the mesh's local coordinates, which the pattern is given in
+    world_to_local: Mat4,
+    offset: Vec2,
+    /// The image's size, which is the size of a tile
+    size: Vec2,
+    alpha: f32,
+    /// 1 where the pattern repeats along the axis, 0 where there's a single tile
+    repeat_x: u32,
+    repeat_y: u32,
+    flip_y: u32,
+}
+
+impl PatternUniform {
+    fn new(pattern: &Pattern, size: Vec2, transform: &Transform) -> Self {
+        let (repeat_x, repeat_y) = match pattern.repeat {
+            PatternRepeat::Repeat => (true, true),
+            PatternRepeat::RepeatX => (true, false),
+            PatternRepeat::RepeatY => (false, true),
+            PatternRepeat::NoRepeat => (false, false),
+        };
+        Self {
+            world_to_local: transform.compute_matrix().inverse(),
+            offset: pattern.offset,
+            size,
+            alpha: pattern.alpha,
+            repeat_x: repeat_x.into(),
+            repeat_y: repeat_y.into(),
+            flip_y: pattern.flip_y.into(),
+        }
+    }
+}
+
+/// Gives shapes spawned with a `PatternFill` their material.
+///
+/// Guest shapes have no parent, so their `Transform` is already their world transform. Images are
+/// added to the assets before the frame that uses them is spawned, so the pattern's size is known.
+fn prepare_pattern_fills(
+    mut commands: Commands,
+    fills: Query<(Entity, &PatternFill, &Transform), Added<PatternFill>>,
+    images: Res<Assets<Image>>,
+    mut materials: ResMut<Assets<PatternMaterial>>,
+) {
+    for (entity, PatternFill(pattern), transform) in fills.iter() {
+        let Some(image) = images.get(&pattern.image) else {
+            continue;
+        };
+        let size = image.texture_descriptor.size;
+        let size = Vec2::new(size.width as f32, size.height as f32);
+        let material = materials.add(PatternMaterial {
+            pattern: PatternUniform::new(pattern, size, transform),
+            image: pattern.image.clone(),
+        });
+        commands.entity(entity).insert(material);
+    }
+}
@@ -0,0 +1,37 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+// Mirrors `PatternUniform` in `pattern.rs`.
+struct Pattern {
+    world_to_local: mat4x4<f32>,
+    offset: vec2<f32>,
+    size: vec2<f32>,
+    alpha: f32,
+    repeat_x: u32,
+    repeat_y: u32,
+    flip_y: u32,
+};
+
+@group(1) @binding(0) var<uniform> pattern: Pattern;
+@group(1) @binding(1) var pattern_texture: texture_2d<f32>;
+@group(1) @binding(2) var pattern_sampler: sampler;
+
+fn outside_tile(t: f32) -> bool {
+    return t < 0.0 || t >= 1.0;
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let position = (pattern.world_to_local * mesh.world_position).xy;
+    var t = (position - pattern.offset) / pattern.size;
+    if pattern.flip_y == 1u {
+        t.y = 1.0 - t.y;
+    }
+    // Past the one tile along an axis that doesn't repeat is transparent, as on a canvas
+    if (pattern.repeat_x == 0u && outside_tile(t.x)) || (pattern.repeat_y == 0u && outside_tile(t.y)) {
+        return vec4<f32>(0.0);
+    }
+    // Sampled at an explicit level, since implicit derivatives aren't allowed after the early
+    // return and would be wrong across tile edges anyway
+    let color = textureSampleLevel(pattern_texture, pattern_sampler, fract(t), 0.0);
+    return vec4<f32>(color.rgb, color.a * pattern.alpha);
+}
@@ -0,0 +1,174 @@
+use crate::levo::portal::my_imports::{FetchStatus, ImageData};
+use bevy::math::{Rect, URect, UVec2, Vec2};
+use bevy::render::render_resource::TextureFormat;
+use bevy::render::texture::Image;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Past this many unfinished or untaken readbacks, `get_image_data` fails.
+pub const MAX_READBACKS: usize = 4;
+
+pub type ReadbackResult = Arc<Mutex<Option<Result<ImageData, String>>>>;
+
+/// The pixels guests asked for with `get_image_data`, read back from the window once the frame
+/// they asked in has been rendered.
+#[derive(Default)]
+pub struct GuestReadbacks {
+    next_handle: u32,
+    readbacks: HashMap<u32, Readback>,
+}
+
+struct Readback {
+    /// The region in guest coordinates, until a screenshot has been asked for
+    region: Option<Rect>,
+    /// Filled in once the screenshot is taken
+    result: ReadbackResult,
+    /// Whether the guest has been updated since the pixels came back
+    announced: bool,
+}
+
+impl GuestReadbacks {
+    pub fn request(&mut self, region: Rect) -> Result<u32, String> {
+        if self.readbacks.len() >= MAX_READBACKS {
+            return Err(format!(
+                "{MAX_READBACKS} readbacks are already waiting to be taken"
+            ));
+        }
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.readbacks.insert(
+            handle,
+            Readback {
+                region: Some(region),
+                result: ReadbackResult::default(),
+                announced: false,
+            },
+        );
+        Ok(handle)
+    }
+
+    pub fn poll(&self, handle: u32) -> FetchStatus {
+        match self.readbacks.get(&handle) {
+            Some(readback) if readback.result.lock().unwrap().is_some() => FetchStatus::Ready,
+            Some(_) => FetchStatus::Pending,
+            None => FetchStatus::Unknown,
+        }
+    }
+
+    /// The pixels of a finished readback, which frees its handle.
+    pub fn take(&mut self, handle: u32) -> Result<ImageData, String> {
+        let readback = self
+            .readbacks
+            .get(&handle)
+            .ok_or_else(|| format!("no readback with handle {handle}"))?;
+        let result = readback
+            .result
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| format!("readback {handle} hasn't finished"))?;
+        self.readbacks.remove(&handle);
+        result
+    }
+
+    /// The regions that still need a screenshot, with where their pixels go.
+    pub fn take_requested(&mut self) -> Vec<(Rect, ReadbackResult)> {
+        self.readbacks
+            .values_mut()
+            .filter_map(|readback| Some((readback.region.take()?, readback.result.clone())))
+            .collect()
+    }
+
+    /// Whether a readback finished since this was last asked, which the guest needs an `update`
+    /// to find out about.
+    pub fn newly_finished(&mut self) -> bool {
+        let mut finished = false;
+        for readback in self.readbacks.values_mut() {
+            if !readback.announced && readback.result.lock().unwrap().is_some() {
+                readback.announced = true;
+                finished = true;
+            }
+        }
+        finished
+    }
+}
+
+/// The RGBA pixels of `screenshot` inside `bounds`, clamped to `canvas` and the screenshot, all in
+/// the window's physical pixels. The screenshot is of the whole window, so without the canvas
+/// guests could read the address bar and the rest of the browser around them.
+pub fn crop(screenshot: &Image, bounds: Rect, canvas: Rect) -> Result<ImageData, String> {
+    let size = screenshot.texture_descriptor.size;
+    let size = UVec2::new(size.width, size.height);
+    let bounds = bounds.intersect(canvas);
+    if bounds.is_empty() {
+        return Ok(ImageData {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        });
+    }
+    let min = bounds.min.round().max(Vec2::ZERO).as_uvec2().min(size);
+    let max = bounds.max.round().max(Vec2::ZERO).as_uvec2().min(size);
+    let region = URect::from_corners(min, max);
+    let bgra = match screenshot.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(format!("can't read back pixels in the {format:?} format")),
+    };
+    let mut pixels = Vec::with_capacity((region.width() * region.height() * 4) as usize);
+    for y in region.min.y..region.max.y {
+        let start = ((y * size.x + region.min.x) * 4) as usize;
+        let end = start + (region.width() * 4) as usize;
+        let row = screenshot
+            .data
+            .get(start..end)
+            .ok_or("the screenshot is smaller than its size")?;
+        for pixel in row.chunks_exact(4) {
+            if bgra {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+    Ok(ImageData {
+        width: region.width(),
+        height: region.height(),
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    /// A 4x4 screenshot whose pixels are their own `x`, `y` and then 0 and 255.
+    fn screenshot() -> Image {
+        let pixels = (0..4u8)
+            .flat_map(|y| (0..4u8).flat_map(move |x| [x, y, 0, 255]))
+            .collect();
+        Image::new(
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::Rgba8Unorm,
+        )
+    }
+
+    #[test]
+    fn crop_stops_at_the_canvas() {
+        let canvas = Rect::new(1., 1., 3., 3.);
+        let image = crop(&screenshot(), Rect::new(-10., 0., 10., 2.), canvas).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixels, [1, 1, 0, 255, 2, 1, 0, 255]);
+        // Nothing of the window past the canvas, even where the screenshot has pixels
+        let outside = crop(&screenshot(), Rect::new(3., 0., 4., 4.), canvas).unwrap();
+        assert_eq!((outside.width, outside.height), (0, 0));
+        assert!(outside.pixels.is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Past this many bytes a value isn't stored.
+pub const MAX_VALUE_BYTES: usize = 64 << 10;
+/// Past this many bytes of keys and values a host's storage is full.
+pub const MAX_STORAGE_BYTES: usize = 5 << 20;
+
+/// The strings a guest keeps between sessions with `storage_set`, saved as a JSON object in a
+/// file of its host's own.
+///
+/// Every guest from a host shares the file, and guests from other hosts never see it.
+pub struct GuestStorage {
+    path: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl GuestStorage {
+    /// The storage of the host `url` is served from, read from `dir`. `None` for URLs without a
+    /// host, which get no storage.
+    pub fn open(dir: &Path, url: &str) -> Option<Self> {
+        let path = dir.join(format!("{}.json", host_file_name(url)?));
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("ignoring unreadable storage {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Some(Self { path, entries })
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries.get(key).cloned()
+    }
+
+    /// Stores `value` under `key` and writes the storage to disk, failing if either would go past
+    /// the size caps.
+    pub fn set(&mut self, key: String, value: String) -> Result<(), String> {
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(format!(
+                "values are limited to {MAX_VALUE_BYTES} bytes, not {}",
+                value.len()
+            ));
+        }
+        let replaced = self
+            .entries
+            .get(&key)
+            .map_or(0, |old| key.len() + old.len());
+        if self.size() - replaced + key.len() + value.len() > MAX_STORAGE_BYTES {
+            return Err(format!("storage is limited to {MAX_STORAGE_BYTES} bytes"));
+        }
+        self.entries.insert(key, value);
+        self.save()
+            .map_err(|e| format!("failed to save {}: {e}", self.path.display()))
+    }
+
+    fn size(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum()
+    }
+
+    /// Writes to a temporary file first, so a crash mid-write can't lose what was stored.
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(&self.entries)?)?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+/// A file name for the host `url` is served from, with its port if it has one, that can't point
+/// outside the directory it's joined to. `None` for URLs without a host.
+pub fn host_file_name(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let mut name: String = url
+        .host_str()?
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if let Some(port) = url.port() {
+        name.push_str(&format!("_{port}"));
+    }
+    Some(name)
+}
+
+/// `$XDG_DATA_HOME/levo/storage`, falling back to `~/.local/share/levo/storage` and then the
+/// temp directory.
+pub fn default_storage_dir() -> PathBuf {
+    data_dir().join("storage")
+}
+
+/// `$XDG_DATA_HOME/levo/files`, with the same fallbacks as `default_storage_dir`.
+pub fn default_files_dir() -> PathBuf {
+    data_dir().join("files")
+}
+
+/// `$XDG_DATA_HOME/levo/settings.json`, where the portal keeps its own settings between
+/// sessions.
+pub fn default_settings_path() -> PathBuf {
+    data_dir().join("settings.json")
+}
+
+/// `$XDG_DATA_HOME/levo/reader-hosts.json`, the hosts whose guests run in reader mode.
+pub fn default_reader_hosts_path() -> PathBuf {
+    data_dir().join("reader-hosts.json")
+}
+
+/// `$XDG_DATA_HOME/levo/permissions.json`, what the user allowed and denied each host.
+pub fn default_permissions_path() -> PathBuf {
+    data_dir().join("permissions.json")
+}
+
+fn data_dir() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("levo")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of the test's own, emptied first.
+    fn storage_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("levo-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Storage for `example.com` that's `free` bytes short of full, without writing it all out.
+    fn nearly_full(dir: &Path, free: usize) -> GuestStorage {
+        let mut storage = GuestStorage::open(dir, "https://example.com/guest.wasm").unwrap();
+        let filler = "x".repeat(MAX_STORAGE_BYTES - "filler".len() - free);
+        storage.entries.insert("filler".to_string(), filler);
+        storage
+    }
+
+    #[test]
+    fn values_over_the_cap_are_not_stored() {
+        let dir = storage_dir("value-cap");
+        let mut storage = nearly_full(&dir, MAX_STORAGE_BYTES / 2);
+        let value = "x".repeat(MAX_VALUE_BYTES + 1);
+        assert_eq!(
+            storage.set("key".to_string(), value),
+            Err(format!(
+                "values are limited to {MAX_VALUE_BYTES} bytes, not {}",
+                MAX_VALUE_BYTES + 1
+            ))
+        );
+        assert_eq!(storage.get("key"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn storage_fills_up_with_keys_and_values() {
+        let dir = storage_dir("storage-cap");
+        let mut storage = nearly_full(&dir, 10);
+        assert_eq!(
+            storage.set("k".to_string(), "x".repeat(10)),
+            Err(format!("storage is limited to {MAX_STORAGE_BYTES} bytes"))
+        );
+        assert_eq!(storage.get("k"), None);
+        assert_eq!(storage.set("k".to_string(), "x".repeat(9)), Ok(()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replacing_a_value_only_counts_the_new_one() {
+        let dir = storage_dir("replace");
+        let mut storage = nearly_full(&dir, 10);
+        storage.set("k".to_string(), "a".repeat(9)).unwrap();
+        // Full now, but the old value makes way for the new one
+        storage.set("k".to_string(), "b".repeat(9)).unwrap();
+        let reopened = GuestStorage::open(&dir, "https://example.com/other.wasm").unwrap();
+        assert_eq!(reopened.get("k"), Some("b".repeat(9)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn host_file_names_stay_in_their_directory() {
+        assert_eq!(
+            host_file_name("https://example.com/guest.wasm").as_deref(),
+            Some("example.com")
+        );
+        // The scheme's default port is left out, any other is kept
+        assert_eq!(
+            host_file_name("https://example.com:443/").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            host_file_name("http://example.com:8080/").as_deref(),
+            Some("example.com_8080")
+        );
+        assert_eq!(
+            host_file_name("http://[::1]:4433/").as_deref(),
+            Some("___1__4433")
+        );
+        assert_eq!(host_file_name("file:///home/guest.wasm"), None);
+        for url in [
+            "http://../",
+            "http://%2e%2e/",
+            "http://a%2Fb/",
+            "http://[::ffff:1.2.3.4]/",
+        ] {
+            if let Some(name) = host_file_name(url) {
+                let path = Path::new("/storage").join(format!("{name}.json"));
+                assert_eq!(path.parent(), Some(Path::new("/storage")), "{url}");
+            }
+        }
+    }
+}
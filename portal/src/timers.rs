@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+/// Past this many timers set at once, setting another traps.
+pub const MAX_TIMERS: usize = 10_000;
+
+/// The timers a guest set with `set_timeout` and `set_interval`, which fire through its
+/// `on-timer` export once they're due.
+///
+/// Times are Bevy's `Time::elapsed_seconds_f64`, updated every frame, so timers fire at the start
+/// of the first frame they're due by, and an interval fires at most once a frame.
+#[derive(Default)]
+pub struct GuestTimers {
+    now: f64,
+    next_handle: u32,
+    timers: HashMap<u32, Timer>,
+}
+
+struct Timer {
+    due: f64,
+    /// `None` for timeouts, which are cleared once they fire
+    period: Option<f64>,
+}
+
+impl GuestTimers {
+    pub fn set_now(&mut self, now: f64) {
+        self.now = now;
+    }
+
+    /// Sets a timer to fire in `ms` milliseconds, and then every `ms` if it `repeats`.
+    pub fn set(&mut self, ms: u32, repeats: bool) -> wasmtime::Result<u32> {
+        if self.timers.len() >= MAX_TIMERS {
+            wasmtime::bail!("guest set more than {MAX_TIMERS} timers at once");
+        }
+        let seconds = f64::from(ms) / 1000.;
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.timers.insert(
+            handle,
+            Timer {
+                due: self.now + seconds,
+                period: repeats.then_some(seconds),
+            },
+        );
+        Ok(handle)
+    }
+
+    pub fn clear(&mut self, handle: u32) {
+        self.timers.remove(&handle);
+    }
+
+    pub fn any_due(&self) -> bool {
+        self.timers.values().any(|timer| timer.due <= self.now)
+    }
+
+    /// The timers that are due, earliest first.
+    pub fn due(&self) -> Vec<u32> {
+        let mut due: Vec<_> = self
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.due <= self.now)
+            .map(|(handle, timer)| (timer.due, *handle))
+            .collect();
+        due.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        due.into_iter().map(|(_, handle)| handle).collect()
+    }
+
+    /// Marks a due timer as fired, returning whether it's still set. A timer cleared by the hooks
+    /// of timers that fired before it in the same frame doesn't fire.
+    pub fn fire(&mut self, handle: u32) -> bool {
+        let Some(timer) = self.timers.get_mut(&handle) else {
+            return false;
+        };
+        match timer.period {
+            None => {
+                self.timers.remove(&handle);
+            }
+            Some(period) => {
+                timer.due += period;
+                // An interval that fell behind skips the firings it missed rather than bursting
+                if timer.due <= self.now {
+                    timer.due = self.now + period;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_timers_fire_earliest_first() {
+        let mut timers = GuestTimers::default();
+        let late = timers.set(300, false).unwrap();
+        let early = timers.set(100, false).unwrap();
+        let interval = timers.set(200, true).unwrap();
+        let tied = timers.set(200, false).unwrap();
+        timers.set_now(0.05);
+        assert!(!timers.any_due());
+        assert!(timers.due().is_empty());
+        timers.set_now(0.25);
+        assert!(timers.any_due());
+        // Timers due at the same time fire in the order they were set
+        assert_eq!(timers.due(), [early, interval, tied]);
+        timers.set_now(0.5);
+        assert_eq!(timers.due(), [early, interval, tied, late]);
+    }
+
+    #[test]
+    fn timeouts_fire_once_and_intervals_again() {
+        let mut timers = GuestTimers::default();
+        let timeout = timers.set(100, false).unwrap();
+        let interval = timers.set(100, true).unwrap();
+        timers.set_now(0.15);
+        assert!(timers.fire(timeout));
+        assert!(timers.fire(interval));
+        assert!(!timers.fire(timeout));
+        assert!(timers.due().is_empty());
+        timers.set_now(0.2);
+        assert_eq!(timers.due(), [interval]);
+    }
+
+    #[test]
+    fn intervals_that_fell_behind_skip_what_they_missed() {
+        let mut timers = GuestTimers::default();
+        let interval = timers.set(100, true).unwrap();
+        // Frames stalled for close to a second, which would be nine firings
+        timers.set_now(0.95);
+        assert_eq!(timers.due(), [interval]);
+        assert!(timers.fire(interval));
+        assert!(timers.due().is_empty());
+        timers.set_now(1.0);
+        assert!(timers.due().is_empty());
+        timers.set_now(1.1);
+        assert_eq!(timers.due(), [interval]);
+    }
+
+    #[test]
+    fn timers_cleared_while_firing_dont_fire() {
+        let mut timers = GuestTimers::default();
+        let first = timers.set(100, false).unwrap();
+        let second = timers.set(100, true).unwrap();
+        timers.set_now(0.1);
+        let due = timers.due();
+        assert_eq!(due, [first, second]);
+        // The first timer's hook clears the second
+        assert!(timers.fire(first));
+        timers.clear(second);
+        assert!(!timers.fire(second));
+        timers.set_now(10.);
+        assert!(timers.due().is_empty());
+    }
+}
@@ -1,10 +1,19 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::{
-    default, AssetServer, BuildChildren, ButtonBundle, Camera2dBundle, Color, Commands, NodeBundle,
-    Res, TextBundle,
+    default, AssetServer, BuildChildren, ButtonBundle, Camera, Camera2d, Camera2dBundle, Color,
+    Commands, DetectChanges, Input, KeyCode, NodeBundle, Query, Res, ResMut, Resource, TextBundle,
+    With,
+};
+use bevy::render::view::RenderLayers;
+use bevy::text::{Text, TextSection, TextStyle};
+use bevy::ui::{
+    AlignItems, AlignSelf, BorderColor, Display, FlexDirection, PositionType, Style,
+    UiCameraConfig, UiRect, ZIndex,
 };
-use bevy::text::{Text, TextStyle};
-use bevy::ui::{AlignItems, Display, FlexDirection, Style, UiRect};
 use bevy_cosmic_edit::*;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
 #[derive(bevy::prelude::Component)]
 pub struct Portal;
@@ -12,12 +21,204 @@ pub struct Portal;
 #[derive(bevy::prelude::Component)]
 pub struct AddressBar;
 
+#[derive(bevy::prelude::Component)]
+pub struct AddressBarFrame;
+
 #[derive(bevy::prelude::Component)]
 pub struct RefreshButton;
 
+#[derive(bevy::prelude::Component)]
+pub struct StatusText;
+
+#[derive(bevy::prelude::Component)]
+pub struct ProgressBar;
+
+#[derive(bevy::prelude::Component)]
+pub struct ConsoleText;
+
+#[derive(bevy::prelude::Component)]
+pub struct DiagnosticsText;
+
+/// Asks whether a host's guests may use something that needs permission, see
+/// `HostPermissions::pending`.
+#[derive(bevy::prelude::Component)]
+pub struct PermissionPrompt;
+
+#[derive(bevy::prelude::Component)]
+pub struct PermissionPromptText;
+
+/// One of the prompt's answers.
+#[derive(bevy::prelude::Component)]
+pub struct PermissionButton {
+    pub allow: bool,
+}
+
+/// Says what changed for a moment after a shortcut changes something, like the volume.
+#[derive(bevy::prelude::Component, Default)]
+pub struct NoticeText {
+    /// `Time::elapsed_seconds` when it's hidden again
+    pub hide_at: f32,
+}
+
+/// Lines kept by the console, older ones are dropped
+const CONSOLE_CAPACITY: usize = 200;
+/// Lines the console overlay shows, counting back from the newest
+const CONSOLE_VISIBLE_LINES: usize = 20;
+
+/// What the guest printed, shown in an overlay toggled with the backtick key.
+#[derive(Resource, Default)]
+pub struct Console {
+    lines: VecDeque<(ConsoleSource, String)>,
+    visible: bool,
+    /// Lines below this level are kept but not shown, cycled through with Shift+backtick
+    min_level: LogLevel,
+}
+
+/// How severe a guest says what it logged is.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum LogLevel {
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn next(self) -> Self {
+        match self {
+            LogLevel::Debug => LogLevel::Info,
+            LogLevel::Info => LogLevel::Warn,
+            LogLevel::Warn => LogLevel::Error,
+            LogLevel::Error => LogLevel::Debug,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+/// How a line got to the console, which picks its color.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleSource {
+    /// The `log` import, or `print`, which logs at info
+    Log(LogLevel),
+    /// WASI's stdout
+    Stdout,
+    /// WASI's stderr
+    Stderr,
+}
+
+impl ConsoleSource {
+    fn color(self) -> Color {
+        match self {
+            ConsoleSource::Log(LogLevel::Debug) => Color::GRAY,
+            ConsoleSource::Log(LogLevel::Info) | ConsoleSource::Stdout => Color::WHITE,
+            ConsoleSource::Log(LogLevel::Warn) => Color::rgb(1., 0.85, 0.3),
+            ConsoleSource::Log(LogLevel::Error) | ConsoleSource::Stderr => {
+                Color::rgb(1., 0.45, 0.45)
+            }
+        }
+    }
+
+    /// The level the console filters the line by. WASI output has none, so it's shown unless
+    /// only errors are.
+    fn level(self) -> LogLevel {
+        match self {
+            ConsoleSource::Log(level) => level,
+            ConsoleSource::Stdout | ConsoleSource::Stderr => LogLevel::Info,
+        }
+    }
+}
+
+impl Console {
+    /// Adds `text` stamped with `seconds`, one line per line of text.
+    pub fn push(&mut self, seconds: f32, source: ConsoleSource, text: &str) {
+        for line in text.lines() {
+            if self.lines.len() == CONSOLE_CAPACITY {
+                self.lines.pop_front();
+            }
+            self.lines
+                .push_back((source, format!("[{seconds:9.3}] {line}")));
+        }
+    }
+}
+
+/// Guest frames `Diagnostics::guest_time` averages over
+const GUEST_TIME_FRAMES: usize = 60;
+
+/// Frame rate and guest render statistics, shown in an overlay toggled with F3 or by the guest.
+#[derive(Resource, Default)]
+pub struct Diagnostics {
+    pub visible: bool,
+    /// Commands in the guest's latest frame
+    pub draw_commands: usize,
+    /// Entities spawned for those commands, counting their children
+    pub entities: usize,
+    /// How long the latest frames the guest ran took inside the guest, newest last
+    guest_times: VecDeque<Duration>,
+}
+
+impl Diagnostics {
+    /// Records how long a frame's `update`, and the hooks before it, ran for.
+    pub fn push_guest_time(&mut self, time: Duration) {
+        if self.guest_times.len() == GUEST_TIME_FRAMES {
+            self.guest_times.pop_front();
+        }
+        self.guest_times.push_back(time);
+    }
+
+    /// The average time the guest took over its latest frames. Frames the guest wasn't updated
+    /// in don't count, so an idle guest keeps its last average.
+    pub fn guest_time(&self) -> Duration {
+        match self.guest_times.len() {
+            0 => Duration::ZERO,
+            len => self.guest_times.iter().sum::<Duration>() / len as u32,
+        }
+    }
+
+    /// Forgets the previous guest's times, for when another guest is shown.
+    pub fn clear_guest_times(&mut self) {
+        self.guest_times.clear();
+    }
+}
+
+/// How loading the guest typed into the address bar went.
+#[derive(Resource, Default, Debug)]
+pub enum LoadStatus {
+    /// Nothing has been loaded yet
+    #[default]
+    Idle,
+    /// Downloading, with the bytes received so far and the total if the server sent it
+    Loading {
+        received: u64,
+        total: Option<u64>,
+    },
+    /// Waiting to try the download again after the connection failed, `attempt` being the try
+    /// that's next
+    Retrying {
+        attempt: u32,
+        attempts: u32,
+    },
+    Ready,
+    /// Stopped with Escape before the guest was loaded
+    Cancelled,
+    Failed(String),
+}
+
 #[derive(bevy::prelude::Component)]
 pub struct MainCamera;
 
+/// The order of the camera the UI is drawn with, after the main camera and the guest's clip
+/// cameras, whose orders are at most their render layers, so that guests can't draw over it.
+const UI_CAMERA_ORDER: isize = RenderLayers::TOTAL_LAYERS as isize;
+
 pub fn bevy_color_to_cosmic(color: bevy::prelude::Color) -> CosmicColor {
     CosmicColor::rgba(
         (color.r() * 255.) as u8,
@@ -27,20 +228,162 @@ pub fn bevy_color_to_cosmic(color: bevy::prelude::Color) -> CosmicColor {
     )
 }
 
+/// Past this many different colors the cache starts over, so a guest can't grow it without end.
+const COLOR_CACHE_CAPACITY: usize = 256;
+
+/// The colors a guest has set before, so that a frame setting the same few colors over and over
+/// only parses each the first time.
+#[derive(Default)]
+pub struct ColorCache {
+    colors: HashMap<String, Color>,
+}
+
+impl ColorCache {
+    pub fn parse(&mut self, str: String) -> Color {
+        if let Some(&color) = self.colors.get(&str) {
+            return color;
+        }
+        if self.colors.len() >= COLOR_CACHE_CAPACITY {
+            self.colors.clear();
+        }
+        let color = parse_color(&str);
+        self.colors.insert(str, color);
+        color
+    }
+}
+
 pub fn string_to_bevy_color(str: String) -> bevy::prelude::Color {
-    match str.as_str() {
+    parse_color(&str)
+}
+
+fn parse_color(str: &str) -> Color {
+    match str {
         "white" => Color::WHITE,
         "black" => Color::BLACK,
         "red" => Color::RED,
         "blue" => Color::BLUE,
         "royal_purple" => Color::hex("#8C49A3").unwrap(),
-        _ => Color::BLACK,
+        other => parse_rgb_color(other)
+            .or_else(|| parse_hsl_color(other))
+            .or_else(|| parse_hex_color(other))
+            .unwrap_or(Color::BLACK),
+    }
+}
+
+/// Parses CSS style `rgb(r, g, b)` and `rgba(r, g, b, a)` colors.
+///
+/// Channels are 0-255 and clamped. Alpha is a 0-1 fraction, clamped too, and a missing alpha
+/// means opaque.
+fn parse_rgb_color(str: &str) -> Option<bevy::prelude::Color> {
+    let str = str.trim();
+    let args = str
+        .strip_prefix("rgba(")
+        .or_else(|| str.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let values = args
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let channel = |v: f32| v.clamp(0., 255.).round() as u8;
+    let (r, g, b, a) = match values[..] {
+        [r, g, b] => (r, g, b, 1.),
+        [r, g, b, a] => (r, g, b, a),
+        _ => return None,
+    };
+    let mut color = Color::rgb_u8(channel(r), channel(g), channel(b));
+    color.set_a(a.clamp(0., 1.));
+    Some(color)
+}
+
+/// Parses CSS style `hsl(h, s, l)` and `hsla(h, s, l, a)` colors.
+///
+/// The hue is in degrees, optionally suffixed with `deg`, and wraps around. Saturation and
+/// lightness are percentages with or without the `%`, clamped to 0-100. Alpha is a 0-1 fraction
+/// or a percentage, and a missing alpha means opaque.
+fn parse_hsl_color(str: &str) -> Option<bevy::prelude::Color> {
+    let str = str.trim();
+    let args = str
+        .strip_prefix("hsla(")
+        .or_else(|| str.strip_prefix("hsl("))?
+        .strip_suffix(')')?;
+    let values: Vec<&str> = args.split(',').map(str::trim).collect();
+    let (hue, saturation, lightness, alpha) = match values[..] {
+        [h, s, l] => (h, s, l, None),
+        [h, s, l, a] => (h, s, l, Some(a)),
+        _ => return None,
+    };
+    // `parse` takes `inf` and `NaN`, which would make no color at all
+    let number = |v: &str| v.parse::<f32>().ok().filter(|v| v.is_finite());
+    let hue = number(hue.strip_suffix("deg").unwrap_or(hue))?;
+    let percentage = |v: &str| {
+        let v = number(v.strip_suffix('%').unwrap_or(v).trim())?;
+        Some(v.clamp(0., 100.) / 100.)
+    };
+    let alpha = match alpha {
+        None => 1.,
+        Some(a) if a.ends_with('%') => percentage(a)?,
+        Some(a) => number(a)?.clamp(0., 1.),
+    };
+    let color = Color::hsla(
+        hue.rem_euclid(360.),
+        percentage(saturation)?,
+        percentage(lightness)?,
+        alpha,
+    );
+    Some(color.as_rgba())
+}
+
+/// Parses `rgb`, `rrggbb` and `rrggbbaa` hex colors, with or without a leading `#`.
+fn parse_hex_color(str: &str) -> Option<bevy::prelude::Color> {
+    let hex = str.strip_prefix('#').unwrap_or(str);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, len: usize| u8::from_str_radix(&hex[i..i + len], 16).ok();
+    match hex.len() {
+        // `#fa0` is shorthand for `#ffaa00`
+        3 => Some(Color::rgba_u8(
+            channel(0, 1)? * 17,
+            channel(1, 1)? * 17,
+            channel(2, 1)? * 17,
+            255,
+        )),
+        6 => Some(Color::rgba_u8(
+            channel(0, 2)?,
+            channel(2, 2)?,
+            channel(4, 2)?,
+            255,
+        )),
+        8 => Some(Color::rgba_u8(
+            channel(0, 2)?,
+            channel(2, 2)?,
+            channel(4, 2)?,
+            channel(6, 2)?,
+        )),
+        _ => None,
     }
 }
 
 pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let icon_font = asset_server.load("fonts/MaterialIcons-Regular-subset.ttf");
-    commands.spawn((Camera2dBundle::default(), MainCamera));
+    commands.spawn((
+        Camera2dBundle::default(),
+        UiCameraConfig { show_ui: false },
+        MainCamera,
+    ));
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: UI_CAMERA_ORDER,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        RenderLayers::none(),
+    ));
     let root = commands
         .spawn(NodeBundle {
             style: Style {
@@ -94,6 +437,7 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             CosmicSource(editor),
+            AddressBarFrame,
         ))
         .id();
     commands.insert_resource(Focus(Some(editor)));
@@ -144,9 +488,470 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         })
         .id();
 
+    let progress_bar = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: bevy::prelude::Val::Percent(0.),
+                    height: bevy::prelude::Val::Px(3.),
+                    align_self: AlignSelf::Start,
+                    ..default()
+                },
+                background_color: Color::GRAY.into(),
+                ..default()
+            },
+            ProgressBar,
+        ))
+        .id();
+    let status = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.,
+                    ..default()
+                },
+            ),
+            StatusText,
+        ))
+        .id();
+
     commands.entity(panel).add_child(edit);
     commands.entity(panel).add_child(refresh_button);
 
     commands.entity(root).add_child(panel);
+    commands.entity(root).add_child(progress_bar);
+    commands.entity(root).add_child(status);
     commands.entity(root).add_child(portal);
+
+    let console = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    left: bevy::prelude::Val::Px(0.),
+                    bottom: bevy::prelude::Val::Px(0.),
+                    width: bevy::prelude::Val::Percent(100.),
+                    padding: UiRect::all(bevy::prelude::Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            ConsoleText,
+        ))
+        .id();
+    commands.entity(root).add_child(console);
+
+    let diagnostics = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    right: bevy::prelude::Val::Px(0.),
+                    top: bevy::prelude::Val::Px(0.),
+                    padding: UiRect::all(bevy::prelude::Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            DiagnosticsText,
+        ))
+        .id();
+    commands.entity(root).add_child(diagnostics);
+
+    let notice = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    bottom: bevy::prelude::Val::Px(24.),
+                    align_self: AlignSelf::Center,
+                    padding: UiRect::all(bevy::prelude::Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.7).into(),
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            NoticeText::default(),
+        ))
+        .id();
+    commands.entity(root).add_child(notice);
+
+    let prompt = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    top: bevy::prelude::Val::Px(48.),
+                    align_self: AlignSelf::Center,
+                    align_items: AlignItems::Center,
+                    column_gap: bevy::prelude::Val::Px(8.),
+                    padding: UiRect::all(bevy::prelude::Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_a(0.85).into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            PermissionPrompt,
+        ))
+        .id();
+    let question = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            PermissionPromptText,
+        ))
+        .id();
+    commands.entity(prompt).add_child(question);
+    for (label, allow) in [("Allow", true), ("Deny", false)] {
+        let button = commands
+            .spawn((
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(
+                            bevy::prelude::Val::Px(12.),
+                            bevy::prelude::Val::Px(4.),
+                        ),
+                        ..default()
+                    },
+                    background_color: Color::DARK_GRAY.into(),
+                    ..default()
+                },
+                PermissionButton { allow },
+            ))
+            .id();
+        let text = commands
+            .spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 18.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ))
+            .id();
+        commands.entity(button).add_child(text);
+        commands.entity(prompt).add_child(button);
+    }
+    commands.entity(root).add_child(prompt);
+}
+
+/// Toggles the console overlay with the backtick key and keeps it showing the newest lines.
+pub fn handle_console(
+    keys: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut console_q: Query<(&mut Text, &mut Style), With<ConsoleText>>,
+) {
+    if keys.just_pressed(KeyCode::Grave) {
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            console.min_level = console.min_level.next();
+        } else {
+            console.visible = !console.visible;
+        }
+    }
+    if !console.is_changed() {
+        return;
+    }
+    let min_level = console.min_level;
+    let shown: Vec<_> = console
+        .lines
+        .iter()
+        .filter(|(source, _)| source.level() >= min_level)
+        .collect();
+    let start = shown.len().saturating_sub(CONSOLE_VISIBLE_LINES);
+    for (mut text, mut style) in console_q.iter_mut() {
+        let mut text_style = text.sections[0].style.clone();
+        let mut lines: Vec<(Color, String)> = Vec::new();
+        if min_level > LogLevel::Debug {
+            let note = format!("showing {} and above, Shift+` to change", min_level.name());
+            lines.push((Color::GRAY, note));
+        }
+        lines.extend(
+            shown
+                .iter()
+                .skip(start)
+                .map(|(source, line)| (source.color(), line.clone())),
+        );
+        // One section per line, so that each can have its own color
+        let mut sections: Vec<TextSection> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, (color, line))| {
+                text_style.color = color;
+                let value = if i == 0 { line } else { format!("\n{line}") };
+                TextSection::new(value, text_style.clone())
+            })
+            .collect();
+        if sections.is_empty() {
+            sections.push(TextSection::new("", text_style));
+        }
+        text.sections = sections;
+        style.display = if console.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+/// Toggles the diagnostics overlay with F3 and refreshes it every frame while it's shown.
+pub fn handle_diagnostics(
+    keys: Res<Input<KeyCode>>,
+    store: Res<DiagnosticsStore>,
+    mut diagnostics: ResMut<Diagnostics>,
+    mut diagnostics_q: Query<(&mut Text, &mut Style), With<DiagnosticsText>>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        diagnostics.visible = !diagnostics.visible;
+    }
+    let smoothed = |id| store.get(id).and_then(|d| d.smoothed());
+    let fps = smoothed(FrameTimeDiagnosticsPlugin::FPS).unwrap_or(0.);
+    let frame_time = smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME).unwrap_or(0.);
+    for (mut text, mut style) in diagnostics_q.iter_mut() {
+        style.display = if diagnostics.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if diagnostics.visible {
+            text.sections[0].value = format!(
+                "{fps:.0} fps ({frame_time:.2} ms)\nguest: {:.2} ms\n{} draw commands\n{} entities",
+                diagnostics.guest_time().as_secs_f64() * 1000.,
+                diagnostics.draw_commands,
+                diagnostics.entities
+            );
+        }
+    }
+}
+
+/// Shows the `LoadStatus` under the address bar, and tints the address bar red on failure.
+pub fn handle_load_status(
+    status: Res<LoadStatus>,
+    mut status_text_q: Query<&mut Text, With<StatusText>>,
+    mut address_bar_q: Query<&mut BorderColor, With<AddressBarFrame>>,
+    mut progress_bar_q: Query<&mut Style, With<ProgressBar>>,
+) {
+    if !status.is_changed() {
+        return;
+    }
+    let (message, color) = match status.as_ref() {
+        LoadStatus::Idle | LoadStatus::Ready => (String::new(), Color::hex("#ededed").unwrap()),
+        LoadStatus::Loading {
+            received,
+            total: Some(total),
+        } if *total > 0 => (
+            format!("Loading... {}%", received * 100 / total),
+            Color::GRAY,
+        ),
+        LoadStatus::Loading { received, .. } => (
+            format!("Loading... {}", format_bytes(*received)),
+            Color::GRAY,
+        ),
+        LoadStatus::Retrying { attempt, attempts } => {
+            (format!("Retrying {attempt}/{attempts}..."), Color::GRAY)
+        }
+        LoadStatus::Cancelled => ("Cancelled".to_string(), Color::GRAY),
+        LoadStatus::Failed(e) => (e.clone(), Color::RED),
+    };
+    for mut text in status_text_q.iter_mut() {
+        text.sections[0].value = message.clone();
+        text.sections[0].style.color = color;
+    }
+    for mut border_color in address_bar_q.iter_mut() {
+        *border_color = color.into();
+    }
+    // Without a total there's nothing to fill the bar up to, so only the text shows progress
+    let progress = match status.as_ref() {
+        LoadStatus::Loading {
+            received,
+            total: Some(total),
+        } if *total > 0 => (*received as f32 / *total as f32).min(1.),
+        _ => 0.,
+    };
+    for mut style in progress_bar_q.iter_mut() {
+        style.width = bevy::prelude::Val::Percent(progress * 100.);
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f32 / 1024.),
+        _ => format!("{:.1} MiB", bytes as f32 / 1048576.),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_short_hex_colors() {
+        assert_eq!(
+            parse_hex_color("#fff"),
+            Some(Color::rgba_u8(255, 255, 255, 255))
+        );
+        assert_eq!(
+            parse_hex_color("fa0"),
+            Some(Color::rgba_u8(255, 170, 0, 255))
+        );
+    }
+
+    #[test]
+    fn parses_long_hex_colors() {
+        assert_eq!(
+            parse_hex_color("#ffffff"),
+            Some(Color::rgba_u8(255, 255, 255, 255))
+        );
+        assert_eq!(
+            parse_hex_color("#8C49A3"),
+            Some(Color::rgba_u8(140, 73, 163, 255))
+        );
+    }
+
+    #[test]
+    fn parses_hex_colors_with_alpha() {
+        assert_eq!(
+            parse_hex_color("#80808080"),
+            Some(Color::rgba_u8(128, 128, 128, 128))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        for malformed in [
+            "", "#", "#ff", "#ffff", "#fffff", "#fffffff", "#ggg", "#12345z", "#+ff",
+        ] {
+            assert_eq!(parse_hex_color(malformed), None, "{malformed:?}");
+        }
+    }
+
+    #[test]
+    fn parses_rgb_colors_with_spaces() {
+        assert_eq!(
+            parse_rgb_color("  rgb( 255 ,0,  128 ) "),
+            Some(Color::rgba_u8(255, 0, 128, 255))
+        );
+    }
+
+    #[test]
+    fn rgb_colors_without_alpha_are_opaque() {
+        assert_eq!(parse_rgb_color("rgb(1, 2, 3)").map(|c| c.a()), Some(1.));
+        assert_eq!(parse_rgb_color("rgba(1, 2, 3)").map(|c| c.a()), Some(1.));
+    }
+
+    #[test]
+    fn clamps_rgb_channels_and_alpha() {
+        assert_eq!(
+            parse_rgb_color("rgba(300, -20, 255, 0.5)"),
+            Some(Color::rgba(1., 0., 1., 0.5))
+        );
+        assert_eq!(
+            parse_rgb_color("rgba(0, 0, 0, 128)").map(|c| c.a()),
+            Some(1.)
+        );
+        assert_eq!(
+            parse_rgb_color("rgba(0, 0, 0, -1)").map(|c| c.a()),
+            Some(0.)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rgb_colors() {
+        for malformed in [
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(1, 2, x)",
+            "rgb(1, 2, 3",
+        ] {
+            assert_eq!(parse_rgb_color(malformed), None, "{malformed:?}");
+        }
+    }
+
+    #[test]
+    fn parses_hsl_colors() {
+        assert_eq!(
+            parse_hsl_color("hsl(0, 100%, 50%)"),
+            Some(Color::rgba(1., 0., 0., 1.))
+        );
+        assert_eq!(
+            parse_hsl_color("hsl(120deg, 100, 50)"),
+            Some(Color::rgba(0., 1., 0., 1.))
+        );
+    }
+
+    #[test]
+    fn wraps_hsl_hues_around() {
+        let red = parse_hsl_color("hsl(0, 100%, 50%)");
+        assert_eq!(parse_hsl_color("hsl(360, 100%, 50%)"), red);
+        assert_eq!(parse_hsl_color("hsl(-360deg, 100%, 50%)"), red);
+        assert_eq!(
+            parse_hsl_color("hsl(480, 100%, 50%)"),
+            parse_hsl_color("hsl(120, 100%, 50%)")
+        );
+    }
+
+    #[test]
+    fn parses_hsl_alpha_as_a_fraction_or_percentage() {
+        let alpha = |color: &str| parse_hsl_color(color).map(|c| c.a());
+        assert_eq!(alpha("hsl(0, 0%, 0%)"), Some(1.));
+        assert_eq!(alpha("hsla(0, 0%, 0%, 0.25)"), Some(0.25));
+        assert_eq!(alpha("hsla(0, 0%, 0%, 25%)"), Some(0.25));
+        assert_eq!(alpha("hsla(0, 0%, 0%, 2)"), Some(1.));
+        assert_eq!(alpha("hsla(0, 0%, 0%, 150%)"), Some(1.));
+    }
+
+    #[test]
+    fn rejects_non_finite_hsl_values() {
+        for malformed in [
+            "hsl(inf, 50%, 50%)",
+            "hsl(NaN, 50%, 50%)",
+            "hsl(0, inf%, 50%)",
+            "hsl(0, 50%, NaN)",
+            "hsla(0, 50%, 50%, inf)",
+            "hsla(0, 50%, 50%, NaN%)",
+        ] {
+            assert_eq!(parse_hsl_color(malformed), None, "{malformed:?}");
+        }
+    }
 }
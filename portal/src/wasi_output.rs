@@ -0,0 +1,66 @@
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use wasmtime_wasi::preview2::{HostOutputStream, StdoutStream, StreamResult, Subscribe};
+
+/// Past this many bytes without a line break, what a guest wrote is shown anyway.
+const MAX_PENDING_BYTES: usize = 16 << 10;
+/// Past this many bytes waiting for the console, more output is dropped rather than stalling or
+/// trapping the guest.
+const MAX_BUFFERED_BYTES: usize = 1 << 20;
+
+/// A guest's WASI stdout or stderr, buffered for the console overlay to pick up line by line.
+#[derive(Clone, Default)]
+pub struct GuestOutput(Arc<Mutex<Vec<u8>>>);
+
+impl GuestOutput {
+    /// The complete lines written since this was last called. A partial line waits for the rest
+    /// of it, unless it's grown too long to wait for.
+    pub fn take_lines(&self) -> Vec<String> {
+        let mut buffer = self.0.lock().unwrap();
+        let end = match buffer.iter().rposition(|&byte| byte == b'\n') {
+            Some(newline) => newline + 1,
+            None if buffer.len() >= MAX_PENDING_BYTES => buffer.len(),
+            None => return Vec::new(),
+        };
+        let taken: Vec<u8> = buffer.drain(..end).collect();
+        String::from_utf8_lossy(&taken)
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl StdoutStream for GuestOutput {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(GuestOutputStream(self.clone()))
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+struct GuestOutputStream(GuestOutput);
+
+#[async_trait::async_trait]
+impl Subscribe for GuestOutputStream {
+    // Writes never block, so the stream is always ready
+    async fn ready(&mut self) {}
+}
+
+impl HostOutputStream for GuestOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut buffer = (self.0).0.lock().unwrap();
+        let room = MAX_BUFFERED_BYTES.saturating_sub(buffer.len());
+        buffer.extend_from_slice(&bytes[..bytes.len().min(room)]);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(MAX_BUFFERED_BYTES)
+    }
+}
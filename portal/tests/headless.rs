@@ -0,0 +1,63 @@
+use clap::Parser;
+use portal::headless::run_headless;
+use portal::{Args, PortalConfig};
+
+/// A guest that fills the same rectangle every frame, importing nothing but `fill-rect`.
+const FILL_RECT_GUEST: &str = r#"
+(component
+  (import "levo:portal/my-imports" (instance $host
+    (export "fill-rect" (func
+      (param "x" float32) (param "y" float32) (param "width" float32) (param "height" float32)))))
+  (core func $fill-rect (canon lower (func $host "fill-rect")))
+  (core instance $host-core (export "fill-rect" (func $fill-rect)))
+  (core module $guest
+    (import "host" "fill-rect" (func $fill-rect (param f32 f32 f32 f32)))
+    (func (export "setup"))
+    (func (export "update") (param f32 f32)
+      (call $fill-rect (f32.const 10) (f32.const 20) (f32.const 30) (f32.const 40))))
+  (core instance $guest (instantiate $guest (with "host" (instance $host-core))))
+  (func (export "setup") (canon lift (core func $guest "setup")))
+  (func (export "update") (param "delta-seconds" float32) (param "elapsed-seconds" float32)
+    (canon lift (core func $guest "update"))))
+"#;
+
+/// A guest that only exports `setup`, so it doesn't implement `my-world`.
+const SETUP_ONLY_GUEST: &str = r#"
+(component
+  (core module $guest (func (export "setup")))
+  (core instance $guest (instantiate $guest))
+  (func (export "setup") (canon lift (core func $guest "setup"))))
+"#;
+
+/// Runs the guest written in `wat` for `frames` frames, returning what it queued each frame as
+/// the events' `Debug` formats.
+fn run(wat: &str, frames: u32) -> Result<Vec<Vec<String>>, String> {
+    let component = wat::parse_str(wat).expect("the guest should be valid WAT");
+    let config = PortalConfig::from(&Args::parse_from(["portal", "--no-cache"]));
+    let recorded = run_headless(&component, "file:///guest.wasm", frames, &[], &config)
+        .map_err(|e| e.to_string())?;
+    Ok(recorded
+        .iter()
+        .map(|events| events.iter().map(|event| format!("{event:?}")).collect())
+        .collect())
+}
+
+#[test]
+fn records_what_the_guest_queues_each_frame() {
+    let frames = run(FILL_RECT_GUEST, 3).unwrap();
+    assert_eq!(frames.len(), 3);
+    for events in frames {
+        assert_eq!(
+            events,
+            ["FillRect(FillRect { x: 10.0, y: 20.0, width: 30.0, height: 40.0 })"]
+        );
+    }
+}
+
+#[test]
+fn says_which_exports_a_guest_is_missing() {
+    assert_eq!(
+        run(SETUP_ONLY_GUEST, 1).unwrap_err(),
+        "this component doesn't implement my-world: it doesn't export update"
+    );
+}